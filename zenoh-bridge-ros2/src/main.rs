@@ -39,7 +39,7 @@ macro_rules! insert_json5 {
     };
 }
 
-fn parse_args() -> (Config, Option<f32>) {
+fn parse_args() -> (Config, Option<f32>, bool) {
     let mut app = App::new("zenoh bridge for DDS")
         .version(zenoh_plugin_ros2::GIT_VERSION)
         .long_version(zenoh_plugin_ros2::LONG_VERSION.as_str())
@@ -145,6 +145,12 @@ queries any other remote bridge for discovery information and for historical dat
 (i.e. if the query to the remote bridge exceed the timeout, some historical samples might be not routed to the Readers, but the route will not be blocked forever)."#
         ))
         .arg(Arg::from_usage(
+r#"--adminspace \
+'Start the zenoh admin space, allowing to change the "allow", "deny", "pub_max_frequencies", "reliable_routes_blocking" and "queries_timeout" settings at runtime
+(e.g. with a PUT on "@/router/local/config/plugins/ros2/allow" using the REST plugin).
+WARNING: any zenoh peer or client able to reach the bridge can then change those settings. Not active by default.'"#
+        ))
+        .arg(Arg::from_usage(
 r#"--watchdog=[PERIOD]   'Experimental!! Run a watchdog thread that monitors the bridge's async executor and reports as error log any stalled status during the specified period (default: 1.0 second)'"#
         ).default_missing_value("1.0"));
     let args = app.get_matches();
@@ -214,7 +220,9 @@ r#"--watchdog=[PERIOD]   'Experimental!! Run a watchdog thread that monitors the
         None
     };
 
-    (config, watchdog_period)
+    let adminspace = args.is_present("adminspace");
+
+    (config, watchdog_period, adminspace)
 }
 
 #[async_std::main]
@@ -222,7 +230,7 @@ async fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("z=info")).init();
     log::info!("zenoh-bridge-ros2 {}", *zenoh_plugin_ros2::LONG_VERSION);
 
-    let (config, watchdog_period) = parse_args();
+    let (config, watchdog_period, adminspace) = parse_args();
     let rest_plugin = config.plugin("rest").is_some();
 
     if let Some(period) = watchdog_period {
@@ -238,9 +246,23 @@ async fn main() {
         zenoh_plugin_rest::RestPlugin::start("rest", &runtime).unwrap();
     }
 
-    // start DDS plugin
+    // start ROS2 plugin, keeping it running to validate its configuration changes
     use zenoh_plugin_trait::Plugin;
-    zenoh_plugin_ros2::ROS2Plugin::start("ros2", &runtime).unwrap();
+    let ros2_plugin = zenoh_plugin_ros2::ROS2Plugin::start("ros2", &runtime).unwrap();
+
+    // if required, start the admin space, allowing to change the plugin's configuration at runtime
+    if adminspace {
+        runtime
+            .config
+            .lock()
+            .add_plugin_validator("ros2", ros2_plugin.config_checker());
+        zenoh::runtime::AdminSpace::start(
+            &runtime,
+            zenoh::plugins::PluginsManager::static_plugins_only(),
+            zenoh_plugin_ros2::LONG_VERSION.clone(),
+        )
+        .await;
+    }
     async_std::future::pending::<()>().await;
 }
 
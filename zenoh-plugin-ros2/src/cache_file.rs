@@ -0,0 +1,445 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use flume::{unbounded, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use zenoh::prelude::*;
+use zenoh::time::Timestamp;
+
+use crate::instance_cache::InstanceKey;
+
+// The extension of the cache files in the "persistence_dir" directory
+pub const CACHE_FILE_EXTENSION: &str = "cache";
+
+// A record of a cache file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CacheRecord {
+    // the first record of a file: the cached route's key expression and its depth per instance
+    Header {
+        key_expr: String,
+        depth: usize,
+    },
+    // a cached sample
+    Sample {
+        instance: InstanceKey,
+        payload: Vec<u8>,
+        timestamp: Option<Timestamp>,
+    },
+    // the removal of all the samples of an instance (e.g. when disposed)
+    Dispose {
+        instance: InstanceKey,
+    },
+}
+
+// An append-only file persisting the samples of an InstanceCache, so they can be reloaded after a restart.
+// The records are appended as they come, and the file is periodically re-written with the cached samples only.
+// Note: the records are buffered and the file is not synced, so a crash might lose the last records.
+pub struct CacheFile {
+    path: PathBuf,
+    file: BufWriter<File>,
+    // the number of records in the file (excluding the header)
+    records: usize,
+}
+
+impl CacheFile {
+    // Return the path of the cache file for a key expression in the persistence directory.
+    // The file is named after a hash of the key expression to not exceed the file name length limit
+    // (the key expression itself is in the header).
+    pub fn path_for(dir: &Path, key_expr: &keyexpr) -> PathBuf {
+        dir.join(format!(
+            "{:016x}.{CACHE_FILE_EXTENSION}",
+            fnv1a_hash(key_expr.as_bytes())
+        ))
+    }
+
+    // Read all the records of a cache file, returning the key expression and the depth from its header.
+    // A truncated last record (e.g. after a crash while writing) is ignored.
+    pub fn read(path: &Path) -> Result<(OwnedKeyExpr, usize, Vec<CacheRecord>), String> {
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open cache file {}: {e}", path.display()))?;
+        let mut reader = BufReader::new(file);
+        let (key_expr, depth) = match bincode::deserialize_from(&mut reader) {
+            Ok(CacheRecord::Header { key_expr, depth }) => (
+                OwnedKeyExpr::try_from(key_expr).map_err(|e| {
+                    format!(
+                        "Invalid key expression in cache file {}: {e}",
+                        path.display()
+                    )
+                })?,
+                depth,
+            ),
+            Ok(_) => return Err(format!("Missing header in cache file {}", path.display())),
+            Err(e) => return Err(format!("Failed to read cache file {}: {e}", path.display())),
+        };
+        let mut records = Vec::new();
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(record) => records.push(record),
+                Err(e) => {
+                    if !matches!(&*e, bincode::ErrorKind::Io(io) if io.kind() == ErrorKind::UnexpectedEof)
+                    {
+                        log::warn!(
+                            "Cache file {}: ignore records after the {}th: {e}",
+                            path.display(),
+                            records.len()
+                        );
+                    }
+                    break;
+                }
+            }
+        }
+        Ok((key_expr, depth, records))
+    }
+
+    // Create (or replace) a cache file with the specified records.
+    // The file is written aside and then renamed, so a crash doesn't lose the previous one.
+    pub fn create<'a, I>(
+        path: PathBuf,
+        key_expr: &keyexpr,
+        depth: usize,
+        records: I,
+    ) -> Result<CacheFile, String>
+    where
+        I: IntoIterator<Item = &'a CacheRecord>,
+    {
+        let tmp_path = path.with_extension("tmp");
+        let mut count = 0;
+        {
+            let file = File::create(&tmp_path)
+                .map_err(|e| format!("Failed to create cache file {}: {e}", tmp_path.display()))?;
+            let mut writer = BufWriter::new(file);
+            let header = CacheRecord::Header {
+                key_expr: key_expr.to_string(),
+                depth,
+            };
+            bincode::serialize_into(&mut writer, &header)
+                .map_err(|e| format!("Failed to write cache file {}: {e}", tmp_path.display()))?;
+            for record in records {
+                bincode::serialize_into(&mut writer, record).map_err(|e| {
+                    format!("Failed to write cache file {}: {e}", tmp_path.display())
+                })?;
+                count += 1;
+            }
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to write cache file {}: {e}", tmp_path.display()))?;
+        }
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Failed to rename cache file {}: {e}", tmp_path.display()))?;
+        let file = OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open cache file {}: {e}", path.display()))?;
+        Ok(CacheFile {
+            path,
+            file: BufWriter::new(file),
+            records: count,
+        })
+    }
+
+    pub fn append(&mut self, record: &CacheRecord) -> Result<(), String> {
+        // serialize first, to write the record at once
+        let bytes =
+            bincode::serialize(record).map_err(|e| format!("Failed to serialize record: {e}"))?;
+        self.file
+            .write_all(&bytes)
+            .map_err(|e| format!("Failed to write cache file {}: {e}", self.path.display()))?;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.file
+            .flush()
+            .map_err(|e| format!("Failed to write cache file {}: {e}", self.path.display()))
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn records(&self) -> usize {
+        self.records
+    }
+}
+
+// The writes requested to the thread of a CacheWriter
+enum WriteCommand {
+    Append(CacheRecord),
+    // re-write the file with the specified records only
+    Rewrite(Vec<CacheRecord>),
+}
+
+// A CacheFile written by a background thread, so the callers (e.g. the DDS Reader's listener caching
+// the routed samples) are never blocked by the file I/O. The written records are flushed to the file
+// each time the thread has no more records waiting.
+// Dropping the CacheWriter waits for all the records to be written.
+pub struct CacheWriter {
+    path: PathBuf,
+    tx: Option<Sender<WriteCommand>>,
+    thread: Option<JoinHandle<()>>,
+    // the number of records in the file once all the requested writes are done (excluding the header)
+    records: usize,
+}
+
+impl CacheWriter {
+    pub fn new(
+        file: CacheFile,
+        key_expr: OwnedKeyExpr,
+        depth: usize,
+    ) -> Result<CacheWriter, String> {
+        let path = file.path().to_path_buf();
+        let records = file.records();
+        let (tx, rx) = unbounded();
+        let thread = thread::Builder::new()
+            .name(format!("cache-writer {key_expr}"))
+            .spawn(move || run_writer(file, key_expr, depth, rx))
+            .map_err(|e| {
+                format!(
+                    "Failed to start writer thread for cache file {}: {e}",
+                    path.display()
+                )
+            })?;
+        Ok(CacheWriter {
+            path,
+            tx: Some(tx),
+            thread: Some(thread),
+            records,
+        })
+    }
+
+    pub fn append(&mut self, record: CacheRecord) {
+        self.send(WriteCommand::Append(record));
+        self.records += 1;
+    }
+
+    // Re-write the file with the specified records only (e.g. to remove the obsolete records)
+    pub fn rewrite(&mut self, records: Vec<CacheRecord>) {
+        self.records = records.len();
+        self.send(WriteCommand::Rewrite(records));
+    }
+
+    fn send(&self, command: WriteCommand) {
+        if let Some(tx) = &self.tx {
+            if tx.send(command).is_err() {
+                log::error!(
+                    "Cache file {}: writer thread stopped, record not persisted",
+                    self.path.display()
+                );
+            }
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn records(&self) -> usize {
+        self.records
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        // close the channel and wait for the thread to write the remaining records
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run_writer(
+    mut file: CacheFile,
+    key_expr: OwnedKeyExpr,
+    depth: usize,
+    rx: Receiver<WriteCommand>,
+) {
+    while let Ok(command) = rx.recv() {
+        write_command(&mut file, &key_expr, depth, command);
+        // write all the waiting records before flushing
+        for command in rx.try_iter() {
+            write_command(&mut file, &key_expr, depth, command);
+        }
+        if let Err(e) = file.flush() {
+            log::warn!("Instance cache for {key_expr}: {e}");
+        }
+    }
+}
+
+fn write_command(file: &mut CacheFile, key_expr: &keyexpr, depth: usize, command: WriteCommand) {
+    let result = match command {
+        WriteCommand::Append(record) => file.append(&record),
+        WriteCommand::Rewrite(records) => {
+            CacheFile::create(file.path().to_path_buf(), key_expr, depth, &records)
+                .map(|f| *file = f)
+        }
+    };
+    if let Err(e) = result {
+        log::warn!("Instance cache for {key_expr}: {e}");
+    }
+}
+
+// The 64-bit FNV-1a hash, stable across the Rust versions (contrary to the std DefaultHasher)
+fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// Create an empty temporary directory for the tests using cache files
+#[cfg(test)]
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("zenoh-plugin-ros2-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use zenoh::time::new_reception_timestamp;
+
+    fn sample(i: u8, payload: &[u8], timestamp: Option<Timestamp>) -> CacheRecord {
+        CacheRecord::Sample {
+            instance: [i; 16],
+            payload: payload.to_vec(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_path_for() {
+        let dir = Path::new("/tmp");
+        let short = keyexpr::new("chatter").unwrap();
+        let long = OwnedKeyExpr::try_from("robot/".repeat(100) + "chatter").unwrap();
+        assert_eq!(
+            CacheFile::path_for(dir, short),
+            CacheFile::path_for(dir, keyexpr::new("chatter").unwrap())
+        );
+        assert_ne!(
+            CacheFile::path_for(dir, short),
+            CacheFile::path_for(dir, keyexpr::new("chatter2").unwrap())
+        );
+        // the file name has a fixed length whatever the key expression
+        for ke in [short, &*long] {
+            let path = CacheFile::path_for(dir, ke);
+            assert_eq!(path.parent(), Some(dir));
+            assert_eq!(path.extension(), Some(OsStr::new(CACHE_FILE_EXTENSION)));
+            assert_eq!(
+                path.file_name().unwrap().len(),
+                16 + 1 + CACHE_FILE_EXTENSION.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp_dir("cache-file-round-trip");
+        let key_expr = keyexpr::new("robot_1/chatter").unwrap();
+        let path = CacheFile::path_for(&dir, key_expr);
+        let mut records = vec![
+            sample(1, b"a1", Some(new_reception_timestamp())),
+            CacheRecord::Dispose { instance: [1; 16] },
+        ];
+        let mut file = CacheFile::create(path.clone(), key_expr, 10, &records).unwrap();
+        assert_eq!(file.records(), 2);
+        records.push(sample(2, b"b1", None));
+        file.append(&records[2]).unwrap();
+        assert_eq!(file.records(), 3);
+        drop(file);
+        let (ke, depth, read) = CacheFile::read(&path).unwrap();
+        assert_eq!(&*ke, key_expr);
+        assert_eq!(depth, 10);
+        assert_eq!(read, records);
+
+        // re-creating the file replaces its records
+        let file = CacheFile::create(path.clone(), key_expr, 5, &records[2..]).unwrap();
+        assert_eq!(file.records(), 1);
+        assert!(!path.with_extension("tmp").exists());
+        let (_, depth, read) = CacheFile::read(&path).unwrap();
+        assert_eq!(depth, 5);
+        assert_eq!(read, &records[2..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_tail() {
+        let dir = temp_dir("cache-file-truncated-tail");
+        let key_expr = keyexpr::new("chatter").unwrap();
+        let path = CacheFile::path_for(&dir, key_expr);
+        let records = vec![sample(1, b"a1", None), sample(1, b"a2", None)];
+        let mut file = CacheFile::create(path.clone(), key_expr, 1, &records).unwrap();
+        file.append(&sample(1, b"a3", None)).unwrap();
+        drop(file);
+        // simulate a crash while writing the last record
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        let (_, _, read) = CacheFile::read(&path).unwrap();
+        assert_eq!(read, records);
+
+        // a file without header is rejected
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(0)
+            .unwrap();
+        assert!(CacheFile::read(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_writer() {
+        let dir = temp_dir("cache-file-writer");
+        let key_expr = OwnedKeyExpr::try_from("chatter").unwrap();
+        let path = CacheFile::path_for(&dir, &key_expr);
+        let records = vec![sample(1, b"a1", None), sample(1, b"a2", None)];
+        let file = CacheFile::create(path.clone(), &key_expr, 1, &records[..1]).unwrap();
+        let mut writer = CacheWriter::new(file, key_expr.clone(), 1).unwrap();
+        writer.append(records[1].clone());
+        assert_eq!(writer.records(), 2);
+        // the records are written when the writer is dropped
+        drop(writer);
+        assert_eq!(CacheFile::read(&path).unwrap().2, records);
+
+        let file = CacheFile::create(path.clone(), &key_expr, 1, &records).unwrap();
+        let mut writer = CacheWriter::new(file, key_expr.clone(), 1).unwrap();
+        writer.append(sample(1, b"a3", None));
+        writer.rewrite(vec![sample(1, b"a3", None)]);
+        writer.append(sample(1, b"a4", None));
+        assert_eq!(writer.records(), 2);
+        drop(writer);
+        assert_eq!(
+            CacheFile::read(&path).unwrap().2,
+            vec![sample(1, b"a3", None), sample(1, b"a4", None)]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -12,9 +12,10 @@
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
 use regex::Regex;
-use serde::{de, de::Visitor, Deserialize, Deserializer};
+use serde::{de, de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 use std::env;
 use std::fmt;
+use std::str::FromStr;
 use std::time::Duration;
 use zenoh::prelude::*;
 
@@ -23,7 +24,9 @@ pub const DEFAULT_NODENAME: &str = "zenoh_bridge_ros2";
 pub const DEFAULT_DOMAIN: u32 = 0;
 pub const DEFAULT_RELIABLE_ROUTES_BLOCKING: bool = true;
 pub const DEFAULT_TRANSIENT_LOCAL_CACHE_MULTIPLIER: usize = 10;
+pub const DEFAULT_TRANSIENT_LOCAL_CACHE_MAX_BYTES: usize = 256 * 1024 * 1024;
 pub const DEFAULT_QUERIES_TIMEOUT: f32 = 5.0;
+pub const DEFAULT_ACTION_GET_RESULT_TIMEOUT: f32 = 300.0;
 pub const DEFAULT_DDS_LOCALHOST_ONLY: bool = false;
 
 #[derive(Deserialize, Debug)]
@@ -43,11 +46,25 @@ pub struct Config {
     pub allowance: Option<Allowance>,
     #[serde(default, deserialize_with = "deserialize_max_frequencies")]
     pub pub_max_frequencies: Vec<(Regex, f32)>,
+    #[serde(default, deserialize_with = "deserialize_max_frequencies")]
+    pub sub_max_frequencies: Vec<(Regex, f32)>,
+    #[serde(default, deserialize_with = "deserialize_max_sample_ages")]
+    pub max_sample_ages: Vec<(Regex, f32)>,
     #[serde(default)]
     #[cfg(feature = "dds_shm")]
     pub shm_enabled: bool,
     #[serde(default = "default_transient_local_cache_multiplier")]
     pub transient_local_cache_multiplier: usize,
+    #[serde(default = "default_transient_local_cache_max_bytes")]
+    pub transient_local_cache_max_bytes: usize,
+    #[serde(default, deserialize_with = "deserialize_cache_topic_max_bytes")]
+    pub transient_local_cache_topic_max_bytes: Vec<(Regex, usize)>,
+    #[serde(default)]
+    pub persistence_dir: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_persistent_caches")]
+    pub persistent_caches: Vec<(Regex, usize)>,
+    #[serde(default, deserialize_with = "deserialize_latch_retention")]
+    pub latch_retention: Vec<(Regex, f32)>,
     #[serde(
         default = "default_queries_timeout",
         deserialize_with = "deserialize_duration"
@@ -56,11 +73,166 @@ pub struct Config {
     #[serde(default = "default_reliable_routes_blocking")]
     pub reliable_routes_blocking: bool,
     #[serde(default)]
+    pub qos_conflict_policy: QosConflictPolicy,
+    #[serde(default, deserialize_with = "deserialize_qos_overrides")]
+    pub qos_overrides: Vec<QosOverride>,
+    #[serde(default)]
+    pub partitions_as_key_prefix: bool,
+    #[serde(default, deserialize_with = "deserialize_service_selection")]
+    pub service_selection: Vec<(Regex, ServiceSelectionPolicy)>,
+    #[serde(default, deserialize_with = "deserialize_service_timeouts")]
+    pub service_timeouts: Vec<(Regex, f32)>,
+    #[serde(default, deserialize_with = "deserialize_action_get_result_timeouts")]
+    pub action_get_result_timeouts: Vec<(Regex, f32)>,
+    #[serde(default)]
     __required__: bool,
     #[serde(default, deserialize_with = "deserialize_paths")]
     __path__: Vec<String>,
 }
 
+// The policy used by a Service or Action Client route to select the remote Server(s) to which a request is routed
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ServiceSelectionPolicy {
+    // the first remote Server replying (the request is routed to all remote Servers)
+    First,
+    // the nearest remote Server, i.e. the one with the lowest latency measured from the previous requests
+    #[default]
+    Nearest,
+    // each remote Server in turn
+    RoundRobin,
+    // the remote Server served by the bridge with the specified id
+    Pinned(OwnedKeyExpr),
+}
+
+impl FromStr for ServiceSelectionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ServiceSelectionPolicy::*;
+        match s {
+            "first" => Ok(First),
+            "nearest" => Ok(Nearest),
+            "round_robin" => Ok(RoundRobin),
+            _ => match s.strip_prefix("pinned:") {
+                Some(id) if !id.contains('/') => OwnedKeyExpr::try_from(id)
+                    .map(Pinned)
+                    .map_err(|e| format!("invalid bridge id in '{s}': {e}")),
+                _ => Err(format!(
+                    "unknown policy '{s}' (expected: first, nearest, round_robin or pinned:<id>)"
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ServiceSelectionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ServiceSelectionPolicy::*;
+        match self {
+            First => write!(f, "first"),
+            Nearest => write!(f, "nearest"),
+            RoundRobin => write!(f, "round_robin"),
+            Pinned(id) => write!(f, "pinned:{id}"),
+        }
+    }
+}
+
+impl Serialize for ServiceSelectionPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+// The policy applied when the QoS of a local ROS2 Publisher or Subscriber conflicts with the QoS
+// of the DDS entity created by the route for this topic (i.e. they can't match)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QosConflictPolicy {
+    // log the conflict and report it in the route's status in admin space
+    #[default]
+    Report,
+    // re-create the route's DDS entity with a QoS compatible with all the local nodes
+    Upgrade,
+}
+
+// A rule overriding some QoS of the DDS Readers and Writers created by the bridge for a set of topics
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct QosOverride {
+    // the regex matching the ROS2 topic names (e.g. "/camera/.*")
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub topics: Option<Regex>,
+    #[serde(default)]
+    pub reliability: Option<ReliabilityOverride>,
+    #[serde(default)]
+    pub durability: Option<DurabilityOverride>,
+    #[serde(default, deserialize_with = "deserialize_history_override")]
+    pub history: Option<HistoryOverride>,
+    // the deadline period in seconds
+    #[serde(default, deserialize_with = "deserialize_option_duration")]
+    pub deadline: Option<Duration>,
+    // the lifespan duration in seconds
+    #[serde(default, deserialize_with = "deserialize_option_duration")]
+    pub lifespan: Option<Duration>,
+}
+
+impl QosOverride {
+    pub fn is_matching(&self, ros2_name: &str) -> bool {
+        self.topics
+            .as_ref()
+            .map(|re| re.is_match(ros2_name))
+            .unwrap_or(false)
+    }
+
+    // true if the rule overrides at least 1 QoS policy
+    fn has_qos(&self) -> bool {
+        self.reliability.is_some()
+            || self.durability.is_some()
+            || self.history.is_some()
+            || self.deadline.is_some()
+            || self.lifespan.is_some()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReliabilityOverride {
+    BestEffort,
+    Reliable,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DurabilityOverride {
+    Volatile,
+    TransientLocal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryOverride {
+    KeepLast(i32),
+    KeepAll,
+}
+
+impl FromStr for HistoryOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep_all" => Ok(HistoryOverride::KeepAll),
+            _ => match s.strip_prefix("keep_last:").map(str::parse::<i32>) {
+                Some(Ok(depth)) if depth > 0 => Ok(HistoryOverride::KeepLast(depth)),
+                _ => Err(format!(
+                    "invalid history '{s}' (expected: keep_all or keep_last:<depth>)"
+                )),
+            },
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub enum Allowance {
     #[serde(rename = "allow")]
@@ -269,6 +441,10 @@ fn default_transient_local_cache_multiplier() -> usize {
     DEFAULT_TRANSIENT_LOCAL_CACHE_MULTIPLIER
 }
 
+fn default_transient_local_cache_max_bytes() -> usize {
+    DEFAULT_TRANSIENT_LOCAL_CACHE_MAX_BYTES
+}
+
 fn default_queries_timeout() -> Duration {
     Duration::from_secs_f32(DEFAULT_QUERIES_TIMEOUT)
 }
@@ -281,6 +457,45 @@ where
     Ok(Duration::from_secs_f32(seconds))
 }
 
+fn deserialize_option_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds: f32 = Deserialize::deserialize(deserializer)?;
+    if !(seconds.is_finite() && seconds >= 0.0) {
+        return Err(de::Error::custom(format!(
+            "Invalid duration: {seconds} (expected a positive number of seconds)"
+        )));
+    }
+    Ok(Some(Duration::from_secs_f32(seconds)))
+}
+
+fn deserialize_qos_overrides<'de, D>(deserializer: D) -> Result<Vec<QosOverride>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let rules: Vec<QosOverride> = Deserialize::deserialize(deserializer)?;
+    if let Some(rule) = rules.iter().find(|r| !r.has_qos()) {
+        return Err(de::Error::custom(format!(
+            "Invalid rule in 'qos_overrides': no QoS to override for topics '{}'",
+            rule.topics.as_ref().map(Regex::as_str).unwrap_or_default()
+        )));
+    }
+    Ok(rules)
+}
+
+fn deserialize_history_override<'de, D>(
+    deserializer: D,
+) -> Result<Option<HistoryOverride>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    s.parse()
+        .map(Some)
+        .map_err(|e| de::Error::custom(format!("Invalid 'history' in 'qos_overrides': {e}")))
+}
+
 fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
 where
     D: Deserializer<'de>,
@@ -336,12 +551,301 @@ where
         let regex = Regex::new(&s[0..i]).map_err(|e| {
             de::Error::custom(format!("Invalid regex for 'max_frequency': '{s}': {e}"))
         })?;
-        let frequency: f32 = s[i + 1..].parse().map_err(|e| {
+        // the frequency is converted into a period, which must be representable as a Duration
+        let frequency: f32 = match s[i + 1..].parse() {
+            Ok(f) if f > 0.0 && f.is_finite() && Duration::try_from_secs_f32(1.0 / f).is_ok() => f,
+            _ => {
+                return Err(de::Error::custom(format!(
+                    "Invalid frequency for 'max_frequency': '{s}': must be a positive number of Hz"
+                )))
+            }
+        };
+        result.push((regex, frequency));
+    }
+    Ok(result)
+}
+
+fn deserialize_persistent_caches<'de, D>(deserializer: D) -> Result<Vec<(Regex, usize)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_limits(deserializer, "persistent_caches", "number of samples")
+}
+
+fn deserialize_cache_topic_max_bytes<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(Regex, usize)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_limits(
+        deserializer,
+        "transient_local_cache_topic_max_bytes",
+        "number of bytes",
+    )
+}
+
+fn deserialize_limits<'de, D>(
+    deserializer: D,
+    option: &str,
+    what: &str,
+) -> Result<Vec<(Regex, usize)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strs: Vec<String> = Deserialize::deserialize(deserializer)?;
+    let mut result: Vec<(Regex, usize)> = Vec::with_capacity(strs.len());
+    for s in strs {
+        let i = s
+            .find('=')
+            .ok_or_else(|| de::Error::custom(format!("Invalid '{option}': {s}")))?;
+        let regex = Regex::new(&s[0..i])
+            .map_err(|e| de::Error::custom(format!("Invalid regex for '{option}': '{s}': {e}")))?;
+        let limit: usize = match s[i + 1..].parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(de::Error::custom(format!(
+                    "Invalid {what} for '{option}': '{s}': must be a positive integer"
+                )))
+            }
+        };
+        result.push((regex, limit));
+    }
+    Ok(result)
+}
+
+fn deserialize_latch_retention<'de, D>(deserializer: D) -> Result<Vec<(Regex, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strs: Vec<String> = Deserialize::deserialize(deserializer)?;
+    let mut result: Vec<(Regex, f32)> = Vec::with_capacity(strs.len());
+    for s in strs {
+        let i = s
+            .find('=')
+            .ok_or_else(|| de::Error::custom(format!("Invalid 'latch_retention': {s}")))?;
+        let regex = Regex::new(&s[0..i]).map_err(|e| {
+            de::Error::custom(format!("Invalid regex for 'latch_retention': '{s}': {e}"))
+        })?;
+        // "inf" is parsed as f32::INFINITY, meaning indefinitely
+        let retention: f32 = match s[i + 1..].parse() {
+            Ok(r) if r > 0.0 => r,
+            _ => {
+                return Err(de::Error::custom(format!(
+                    "Invalid retention for 'latch_retention': '{s}': must be a positive number of seconds or 'inf'"
+                )))
+            }
+        };
+        result.push((regex, retention));
+    }
+    Ok(result)
+}
+
+fn deserialize_service_selection<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(Regex, ServiceSelectionPolicy)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strs: Vec<String> = Deserialize::deserialize(deserializer)?;
+    let mut result: Vec<(Regex, ServiceSelectionPolicy)> = Vec::with_capacity(strs.len());
+    for s in strs {
+        let i = s
+            .find('=')
+            .ok_or_else(|| de::Error::custom(format!("Invalid 'service_selection': {s}")))?;
+        let regex = Regex::new(&s[0..i]).map_err(|e| {
+            de::Error::custom(format!("Invalid regex for 'service_selection': '{s}': {e}"))
+        })?;
+        let policy: ServiceSelectionPolicy = s[i + 1..].parse().map_err(|e| {
             de::Error::custom(format!(
-                "Invalid float value for 'max_frequency': '{s}': {e}"
+                "Invalid policy for 'service_selection': '{s}': {e}"
             ))
         })?;
-        result.push((regex, frequency));
+        result.push((regex, policy));
     }
     Ok(result)
 }
+
+fn deserialize_service_timeouts<'de, D>(deserializer: D) -> Result<Vec<(Regex, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_timeouts(deserializer, "service_timeouts")
+}
+
+fn deserialize_max_sample_ages<'de, D>(deserializer: D) -> Result<Vec<(Regex, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_timeouts(deserializer, "max_sample_ages")
+}
+
+fn deserialize_action_get_result_timeouts<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(Regex, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_timeouts(deserializer, "action_get_result_timeouts")
+}
+
+fn deserialize_timeouts<'de, D>(
+    deserializer: D,
+    option: &str,
+) -> Result<Vec<(Regex, f32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strs: Vec<String> = Deserialize::deserialize(deserializer)?;
+    let mut result: Vec<(Regex, f32)> = Vec::with_capacity(strs.len());
+    for s in strs {
+        let i = s
+            .find('=')
+            .ok_or_else(|| de::Error::custom(format!("Invalid '{option}': {s}")))?;
+        let regex = Regex::new(&s[0..i])
+            .map_err(|e| de::Error::custom(format!("Invalid regex for '{option}': '{s}': {e}")))?;
+        let timeout: f32 = s[i + 1..].parse().map_err(|e| {
+            de::Error::custom(format!("Invalid float value for '{option}': '{s}': {e}"))
+        })?;
+        if !(timeout.is_finite() && timeout > 0.0) {
+            return Err(de::Error::custom(format!(
+                "Invalid timeout for '{option}': '{s}': must be a positive number of seconds"
+            )));
+        }
+        result.push((regex, timeout));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_selection_policy() {
+        use ServiceSelectionPolicy::*;
+
+        assert_eq!("first".parse::<ServiceSelectionPolicy>(), Ok(First));
+        assert_eq!("nearest".parse::<ServiceSelectionPolicy>(), Ok(Nearest));
+        assert_eq!(
+            "round_robin".parse::<ServiceSelectionPolicy>(),
+            Ok(RoundRobin)
+        );
+        assert_eq!(
+            "pinned:robot_1".parse::<ServiceSelectionPolicy>(),
+            Ok(Pinned(OwnedKeyExpr::try_from("robot_1").unwrap()))
+        );
+        assert!("pinned:robot_1/arm"
+            .parse::<ServiceSelectionPolicy>()
+            .is_err());
+        assert!("pinned:".parse::<ServiceSelectionPolicy>().is_err());
+        assert!("fastest".parse::<ServiceSelectionPolicy>().is_err());
+        // the replies of several Servers can't be aggregated in a single reply
+        assert!("all".parse::<ServiceSelectionPolicy>().is_err());
+        assert_eq!(ServiceSelectionPolicy::default(), Nearest);
+
+        for s in ["first", "nearest", "round_robin", "pinned:robot_1"] {
+            assert_eq!(s.parse::<ServiceSelectionPolicy>().unwrap().to_string(), s);
+        }
+
+        let config: Config = serde_json::from_str(
+            r#"{"service_selection": [".*/load_map=round_robin", "robot_1/.*=pinned:robot_1"]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.service_selection.len(), 2);
+        assert!(config.service_selection[0].0.is_match("robot_1/load_map"));
+        assert_eq!(config.service_selection[0].1, RoundRobin);
+        assert_eq!(
+            config.service_selection[1].1,
+            Pinned(OwnedKeyExpr::try_from("robot_1").unwrap())
+        );
+
+        assert!(serde_json::from_str::<Config>(r#"{"service_selection": ["load_map"]}"#).is_err());
+        assert!(
+            serde_json::from_str::<Config>(r#"{"service_selection": [".*=fastest"]}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_max_frequencies() {
+        let config: Config =
+            serde_json::from_str(r#"{"sub_max_frequencies": [".*/laser_scan=5", "tf=0.5"]}"#)
+                .unwrap();
+        assert_eq!(config.sub_max_frequencies.len(), 2);
+        assert!(config.sub_max_frequencies[0]
+            .0
+            .is_match("robot_1/laser_scan"));
+        assert_eq!(config.sub_max_frequencies[0].1, 5.0);
+        assert_eq!(config.sub_max_frequencies[1].1, 0.5);
+
+        // a frequency that can't be converted into a period is rejected
+        for f in ["0", "-1", "NaN", "inf", "1e-30", "x"] {
+            let json = format!(r#"{{"sub_max_frequencies": ["tf={f}"]}}"#);
+            assert!(serde_json::from_str::<Config>(&json).is_err(), "{f}");
+            let json = format!(r#"{{"pub_max_frequencies": ["tf={f}"]}}"#);
+            assert!(serde_json::from_str::<Config>(&json).is_err(), "{f}");
+        }
+    }
+
+    #[test]
+    fn test_qos_overrides() {
+        assert_eq!(
+            "keep_last:10".parse::<HistoryOverride>(),
+            Ok(HistoryOverride::KeepLast(10))
+        );
+        assert_eq!(
+            "keep_all".parse::<HistoryOverride>(),
+            Ok(HistoryOverride::KeepAll)
+        );
+        for s in [
+            "keep_last:0",
+            "keep_last:-1",
+            "keep_last:x",
+            "keep_last",
+            "keep_first",
+        ] {
+            assert!(s.parse::<HistoryOverride>().is_err(), "{s}");
+        }
+
+        let config: Config = serde_json::from_str(
+            r#"{"qos_overrides": [
+                {"topics": "/camera/.*", "reliability": "best_effort", "history": "keep_last:1", "deadline": 0.5},
+                {"topics": ["/map", "/tf_static"], "durability": "transient_local", "history": "keep_all", "lifespan": 10}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.qos_overrides.len(), 2);
+        let rule = &config.qos_overrides[0];
+        assert!(rule.is_matching("/camera/image"));
+        assert!(!rule.is_matching("/my/camera/image"));
+        assert_eq!(rule.reliability, Some(ReliabilityOverride::BestEffort));
+        assert_eq!(rule.durability, None);
+        assert_eq!(rule.history, Some(HistoryOverride::KeepLast(1)));
+        assert_eq!(rule.deadline, Some(Duration::from_millis(500)));
+        assert_eq!(rule.lifespan, None);
+        let rule = &config.qos_overrides[1];
+        assert!(rule.is_matching("/map") && rule.is_matching("/tf_static"));
+        assert_eq!(rule.durability, Some(DurabilityOverride::TransientLocal));
+        assert_eq!(rule.history, Some(HistoryOverride::KeepAll));
+        assert_eq!(rule.lifespan, Some(Duration::from_secs(10)));
+
+        for invalid in [
+            // no QoS to override
+            r#"{"qos_overrides": [{"topics": "/chatter"}]}"#,
+            // no topics
+            r#"{"qos_overrides": [{"reliability": "reliable"}]}"#,
+            // invalid values
+            r#"{"qos_overrides": [{"topics": "/chatter", "reliability": "fast"}]}"#,
+            r#"{"qos_overrides": [{"topics": "/chatter", "history": "keep_last:0"}]}"#,
+            r#"{"qos_overrides": [{"topics": "/chatter", "deadline": -1}]}"#,
+            r#"{"qos_overrides": [{"topics": "/chatter", "lifespan": "10s"}]}"#,
+            // unknown QoS
+            r#"{"qos_overrides": [{"topics": "/chatter", "ownership": "exclusive"}]}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<Config>(invalid).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+}
@@ -16,13 +16,16 @@ use cyclors::qos::{History, HistoryKind, Qos};
 use cyclors::*;
 use flume::Sender;
 use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::ffi::{CStr, CString};
 use std::fmt;
 use std::mem::MaybeUninit;
 use std::os::raw;
 use std::slice;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use zenoh::buffers::ZBuf;
 #[cfg(feature = "dds_shm")]
 use zenoh::buffers::ZSlice;
@@ -32,6 +35,7 @@ use zenoh::Session;
 use zenoh_core::SyncResolve;
 
 use crate::gid::Gid;
+use crate::instance_cache::{InstanceCacheSlot, InstanceKey};
 
 const MAX_SAMPLES: usize = 32;
 
@@ -499,8 +503,309 @@ pub fn run_discovery(dp: dds_entity_t, tx: Sender<DDSDiscoveryEvent>) {
     }
 }
 
+// The statuses reported by the Listener of a DDS entity created by the bridge
+#[derive(Default, Serialize)]
+struct DdsEntityStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    incompatible_qos: Option<IncompatibleQosStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deadline_missed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liveliness_changed: Option<LivelinessChangedStatus>,
+}
+
+#[derive(Serialize)]
+struct IncompatibleQosStatus {
+    total_count: u32,
+    last_policy: &'static str,
+}
+
+#[derive(Serialize)]
+struct LivelinessChangedStatus {
+    alive_count: u32,
+    not_alive_count: u32,
+}
+
+// Records (and logs) the incompatible QoS, deadline missed and liveliness changed statuses
+// reported by a DDS entity created by the bridge. It's serialized in the admin space of the route owning the entity.
+// The route must delete the entity before dropping the recorder (its Listener borrows the recorder).
+#[derive(Clone)]
+pub struct DdsStatusRecorder {
+    inner: Arc<DdsStatusRecorderInner>,
+}
+
+struct DdsStatusRecorderInner {
+    // description of the entity for logs (e.g. "Route Subscriber (Zenoh:xyz -> ROS:/xyz): DDS Writer")
+    entity_descr: String,
+    status: Mutex<DdsEntityStatus>,
+}
+
+impl DdsStatusRecorder {
+    pub fn new(entity_descr: String) -> Self {
+        DdsStatusRecorder {
+            inner: Arc::new(DdsStatusRecorderInner {
+                entity_descr,
+                status: Mutex::new(DdsEntityStatus::default()),
+            }),
+        }
+    }
+
+    fn on_incompatible_qos(&self, kind: &str, total_count: u32, policy_id: dds_qos_policy_id_t) {
+        let last_policy = qos_policy_name(policy_id);
+        log::warn!(
+            "{}: {kind} incompatible QoS with a discovered entity - offending policy: {last_policy} (total count: {total_count})",
+            self.inner.entity_descr
+        );
+        self.status().incompatible_qos = Some(IncompatibleQosStatus {
+            total_count,
+            last_policy,
+        });
+    }
+
+    fn on_deadline_missed(&self, kind: &str, total_count: u32) {
+        log::warn!(
+            "{}: {kind} deadline missed (total count: {total_count})",
+            self.inner.entity_descr
+        );
+        self.status().deadline_missed = Some(total_count);
+    }
+
+    fn on_liveliness_changed(&self, alive_count: u32, not_alive_count: u32) {
+        log::debug!(
+            "{}: liveliness changed (alive: {alive_count}, not alive: {not_alive_count})",
+            self.inner.entity_descr
+        );
+        self.status().liveliness_changed = Some(LivelinessChangedStatus {
+            alive_count,
+            not_alive_count,
+        });
+    }
+
+    fn status(&self) -> MutexGuard<DdsEntityStatus> {
+        self.inner
+            .status
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Set the status callbacks of a DDS Reader's Listener.
+    // The callbacks arg is borrowed from the recorder, that must outlive the Reader.
+    unsafe fn set_reader_listener_callbacks(&self, listener: *mut dds_listener_t) {
+        let arg = Arc::as_ptr(&self.inner) as *mut std::os::raw::c_void;
+        dds_lset_requested_incompatible_qos_arg(
+            listener,
+            Some(on_requested_incompatible_qos),
+            arg,
+            true,
+        );
+        dds_lset_requested_deadline_missed_arg(
+            listener,
+            Some(on_requested_deadline_missed),
+            arg,
+            true,
+        );
+        dds_lset_liveliness_changed_arg(listener, Some(on_liveliness_changed), arg, true);
+    }
+
+    // Set the status callbacks of a DDS Writer's Listener.
+    // The callbacks arg is borrowed from the recorder, that must outlive the Writer.
+    unsafe fn set_writer_listener_callbacks(&self, listener: *mut dds_listener_t) {
+        let arg = Arc::as_ptr(&self.inner) as *mut std::os::raw::c_void;
+        dds_lset_offered_incompatible_qos_arg(
+            listener,
+            Some(on_offered_incompatible_qos),
+            arg,
+            true,
+        );
+        dds_lset_offered_deadline_missed_arg(listener, Some(on_offered_deadline_missed), arg, true);
+    }
+}
+
+impl Serialize for DdsStatusRecorder {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.status().serialize(serializer)
+    }
+}
+
+unsafe fn recorder_from_arg(arg: *mut std::os::raw::c_void) -> DdsStatusRecorder {
+    // increment the strong count, since the arg is borrowed from the recorder (still alive, since the deletion
+    // of the entity, that precedes the drop of the recorder, waits for the running callbacks to complete)
+    Arc::increment_strong_count(arg as *const DdsStatusRecorderInner);
+    DdsStatusRecorder {
+        inner: Arc::from_raw(arg as *const DdsStatusRecorderInner),
+    }
+}
+
+unsafe extern "C" fn on_requested_incompatible_qos(
+    _dr: dds_entity_t,
+    status: dds_requested_incompatible_qos_status_t,
+    arg: *mut std::os::raw::c_void,
+) {
+    recorder_from_arg(arg).on_incompatible_qos(
+        "requested",
+        status.total_count,
+        status.last_policy_id,
+    );
+}
+
+unsafe extern "C" fn on_offered_incompatible_qos(
+    _dw: dds_entity_t,
+    status: dds_offered_incompatible_qos_status_t,
+    arg: *mut std::os::raw::c_void,
+) {
+    recorder_from_arg(arg).on_incompatible_qos(
+        "offered",
+        status.total_count,
+        status.last_policy_id,
+    );
+}
+
+unsafe extern "C" fn on_requested_deadline_missed(
+    _dr: dds_entity_t,
+    status: dds_requested_deadline_missed_status_t,
+    arg: *mut std::os::raw::c_void,
+) {
+    recorder_from_arg(arg).on_deadline_missed("requested", status.total_count);
+}
+
+unsafe extern "C" fn on_offered_deadline_missed(
+    _dw: dds_entity_t,
+    status: dds_offered_deadline_missed_status_t,
+    arg: *mut std::os::raw::c_void,
+) {
+    recorder_from_arg(arg).on_deadline_missed("offered", status.total_count);
+}
+
+unsafe extern "C" fn on_liveliness_changed(
+    _dr: dds_entity_t,
+    status: dds_liveliness_changed_status_t,
+    arg: *mut std::os::raw::c_void,
+) {
+    recorder_from_arg(arg).on_liveliness_changed(status.alive_count, status.not_alive_count);
+}
+
+fn qos_policy_name(policy_id: dds_qos_policy_id_t) -> &'static str {
+    #[allow(non_upper_case_globals)]
+    match policy_id {
+        dds_qos_policy_id_DDS_USERDATA_QOS_POLICY_ID => "UserData",
+        dds_qos_policy_id_DDS_DURABILITY_QOS_POLICY_ID => "Durability",
+        dds_qos_policy_id_DDS_PRESENTATION_QOS_POLICY_ID => "Presentation",
+        dds_qos_policy_id_DDS_DEADLINE_QOS_POLICY_ID => "Deadline",
+        dds_qos_policy_id_DDS_LATENCYBUDGET_QOS_POLICY_ID => "LatencyBudget",
+        dds_qos_policy_id_DDS_OWNERSHIP_QOS_POLICY_ID => "Ownership",
+        dds_qos_policy_id_DDS_OWNERSHIPSTRENGTH_QOS_POLICY_ID => "OwnershipStrength",
+        dds_qos_policy_id_DDS_LIVELINESS_QOS_POLICY_ID => "Liveliness",
+        dds_qos_policy_id_DDS_TIMEBASEDFILTER_QOS_POLICY_ID => "TimeBasedFilter",
+        dds_qos_policy_id_DDS_PARTITION_QOS_POLICY_ID => "Partition",
+        dds_qos_policy_id_DDS_RELIABILITY_QOS_POLICY_ID => "Reliability",
+        dds_qos_policy_id_DDS_DESTINATIONORDER_QOS_POLICY_ID => "DestinationOrder",
+        dds_qos_policy_id_DDS_HISTORY_QOS_POLICY_ID => "History",
+        dds_qos_policy_id_DDS_RESOURCELIMITS_QOS_POLICY_ID => "ResourceLimits",
+        dds_qos_policy_id_DDS_LIFESPAN_QOS_POLICY_ID => "Lifespan",
+        dds_qos_policy_id_DDS_DURABILITYSERVICE_QOS_POLICY_ID => "DurabilityService",
+        dds_qos_policy_id_DDS_TYPE_CONSISTENCY_ENFORCEMENT_QOS_POLICY_ID => {
+            "TypeConsistencyEnforcement"
+        }
+        dds_qos_policy_id_DDS_DATA_REPRESENTATION_QOS_POLICY_ID => "DataRepresentation",
+        _ => "Unknown",
+    }
+}
+
+// A per-instance downsampling of the data forwarded to Zenoh by a DDS Reader, with a period that
+// can be changed at any time (e.g. depending the rates requested by the remote Subscribers)
+#[derive(Default)]
+pub struct DownsamplingFilter {
+    // the minimum separation in nanoseconds between 2 forwarded samples of an instance (0 = no downsampling)
+    period_ns: AtomicU64,
+    // the time of the last forwarded sample for each instance
+    last_forwarded: Mutex<HashMap<dds_instance_handle_t, Instant>>,
+}
+
+impl DownsamplingFilter {
+    pub fn period(&self) -> Option<Duration> {
+        match self.period_ns.load(Ordering::Relaxed) {
+            0 => None,
+            ns => Some(Duration::from_nanos(ns)),
+        }
+    }
+
+    // Set the downsampling period, returning true if it changed
+    pub fn set_period(&self, period: Option<Duration>) -> bool {
+        let ns = period.map_or(0, |p| p.as_nanos().min(u64::MAX as u128) as u64);
+        let changed = self.period_ns.swap(ns, Ordering::Relaxed) != ns;
+        if changed && ns == 0 {
+            self.last_forwarded.lock().unwrap().clear();
+        }
+        changed
+    }
+
+    // Return true if a sample of this instance must be forwarded
+    fn accept(&self, instance: dds_instance_handle_t) -> bool {
+        let period = match self.period() {
+            Some(period) => period,
+            None => return true,
+        };
+        let now = Instant::now();
+        let mut last_forwarded = self.last_forwarded.lock().unwrap();
+        match last_forwarded.get(&instance) {
+            Some(last) if now.duration_since(*last) < period => false,
+            _ => {
+                last_forwarded.insert(instance, now);
+                true
+            }
+        }
+    }
+}
+
+// The argument of the Listener of a DDS Reader, freed once the Reader is deleted (see delete_dds_reader())
+pub struct ListenerArg {
+    ptr: *mut raw::c_void,
+    free: unsafe fn(*mut raw::c_void),
+}
+
+// The argument is only accessed by the Listener, the ListenerArg just owns it (and is Send as the argument)
+unsafe impl Send for ListenerArg {}
+unsafe impl Sync for ListenerArg {}
+
+impl ListenerArg {
+    fn new<T: Send>(arg: Box<T>) -> ListenerArg {
+        unsafe fn free<T>(ptr: *mut raw::c_void) {
+            drop(Box::from_raw(ptr as *mut T));
+        }
+        ListenerArg {
+            ptr: Box::into_raw(arg) as *mut raw::c_void,
+            free: free::<T>,
+        }
+    }
+}
+
+impl Drop for ListenerArg {
+    fn drop(&mut self) {
+        unsafe { (self.free)(self.ptr) }
+    }
+}
+
+// The context of the Listener of a DDS Reader forwarding data to Zenoh
+struct ForwardingReaderContext<'a> {
+    topic_name: String,
+    z_key: KeyExpr<'a>,
+    z: Arc<Session>,
+    // if true, the data are forwarded with CongestionControl::Block, otherwise with CongestionControl::Drop
+    // (can change depending the reliability of the remote Subscribers)
+    congestion_blocking: Arc<AtomicBool>,
+    // if true, the data are not forwarded to Zenoh (e.g. while a remote Publisher has a higher ownership strength)
+    suspended: Arc<AtomicBool>,
+    // the downsampling applied to the data forwarded to Zenoh
+    downsampling: Arc<DownsamplingFilter>,
+    // the cache of the forwarded data per instance (for keyed TRANSIENT_LOCAL topics)
+    instance_cache: InstanceCacheSlot,
+}
+
 unsafe extern "C" fn data_forwarder_listener(dr: dds_entity_t, arg: *mut std::os::raw::c_void) {
-    let pa = arg as *mut (String, KeyExpr, Arc<Session>, CongestionControl);
+    let ctx = &*(arg as *const ForwardingReaderContext);
     let mut zp: *mut ddsi_serdata = std::ptr::null_mut();
     #[allow(clippy::uninit_assumed_init)]
     let mut si = MaybeUninit::<[dds_sample_info_t; 1]>::uninit();
@@ -513,34 +818,94 @@ unsafe extern "C" fn data_forwarder_listener(dr: dds_entity_t, arg: *mut std::os
     ) > 0
     {
         let si = si.assume_init();
-        if si[0].valid_data {
+        if si[0].valid_data
+            && !ctx.suspended.load(Ordering::Relaxed)
+            && ctx.downsampling.accept(si[0].instance_handle)
+        {
             let raw_sample = DDSRawSample::create(zp);
 
             if *crate::LOG_PAYLOAD {
                 log::trace!(
                     "Route Publisher (DDS:{} -> Zenoh:{}) - routing payload: {:02x?}",
-                    &(*pa).0,
-                    &(*pa).1,
+                    ctx.topic_name,
+                    ctx.z_key,
                     raw_sample
                 );
             } else {
                 log::trace!(
                     "Route Publisher (DDS:{} -> Zenoh:{}) - routing {} bytes",
-                    &(*pa).0,
-                    &(*pa).1,
+                    ctx.topic_name,
+                    ctx.z_key,
                     raw_sample.len()
                 );
             }
-            let _ = (*pa)
-                .2
-                .put(&(*pa).1, &raw_sample)
-                .congestion_control((*pa).3)
-                .res_sync();
+            forward_to_zenoh(
+                &ctx.z,
+                &ctx.z_key,
+                &raw_sample,
+                zp,
+                congestion_control(&ctx.congestion_blocking),
+                &ctx.instance_cache,
+            );
+        } else if is_disposed(&si[0]) {
+            remove_cached_instance(&ctx.instance_cache, zp);
         }
         ddsi_serdata_unref(zp);
     }
 }
 
+// Forward a sample to Zenoh, and push it into the route's InstanceCache (if any).
+// Note: the cached sample gets its own timestamp, since the one of the put sample is not known.
+unsafe fn forward_to_zenoh(
+    z: &Session,
+    z_key: &KeyExpr,
+    raw_sample: &DDSRawSample,
+    serdata: *const ddsi_serdata,
+    congestion_ctrl: CongestionControl,
+    instance_cache: &InstanceCacheSlot,
+) {
+    let payload = ZBuf::from(raw_sample);
+    if let Some(cache) = instance_cache.read().unwrap().as_ref() {
+        // the sample is cached with its timestamp once received by the cache's local subscriber
+        cache.push_pending(get_instance_key(serdata), payload.clone());
+    }
+    let _ = z
+        .put(z_key, payload)
+        .congestion_control(congestion_ctrl)
+        .res_sync();
+}
+
+#[inline]
+fn is_disposed(si: &dds_sample_info_t) -> bool {
+    !si.valid_data && si.instance_state == dds_instance_state_DDS_IST_NOT_ALIVE_DISPOSED
+}
+
+unsafe fn remove_cached_instance(instance_cache: &InstanceCacheSlot, serdata: *const ddsi_serdata) {
+    if let Some(cache) = instance_cache.read().unwrap().as_ref() {
+        cache.remove_instance(get_instance_key(serdata));
+    }
+}
+
+// Return the MD5 key hash of a sample's instance. Contrary to the instance handle that is local to
+// the process, it identifies the instance across the bridges restarts (e.g. in the cache files).
+// Note: it's the same for all the samples of a keyless topic (i.e. with a single instance).
+unsafe fn get_instance_key(serdata: *const ddsi_serdata) -> InstanceKey {
+    let mut keyhash = ddsi_keyhash { value: [0; 16] };
+    if let Some(get_keyhash) = (*(*serdata).ops).get_keyhash {
+        get_keyhash(serdata, &mut keyhash, true);
+    }
+    keyhash.value
+}
+
+#[inline]
+fn congestion_control(blocking: &AtomicBool) -> CongestionControl {
+    if blocking.load(Ordering::Relaxed) {
+        CongestionControl::Block
+    } else {
+        CongestionControl::Drop
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn create_forwarding_dds_reader(
     dp: dds_entity_t,
@@ -552,21 +917,35 @@ pub fn create_forwarding_dds_reader(
     z_key: KeyExpr,
     z: Arc<Session>,
     read_period: Option<Duration>,
-    congestion_ctrl: CongestionControl,
-) -> Result<dds_entity_t, String> {
+    congestion_blocking: Arc<AtomicBool>,
+    suspended: Arc<AtomicBool>,
+    downsampling: Arc<DownsamplingFilter>,
+    instance_cache: InstanceCacheSlot,
+    status_recorder: &DdsStatusRecorder,
+) -> Result<(dds_entity_t, Option<ListenerArg>), String> {
     unsafe {
         let t = create_topic(dp, &topic_name, &type_name, type_info, keyless);
 
         match read_period {
             None => {
                 // Use a Listener to route data as soon as it arrives
-                let arg = Box::new((topic_name, z_key, z, congestion_ctrl));
-                let sub_listener =
-                    dds_create_listener(Box::into_raw(arg) as *mut std::os::raw::c_void);
+                let arg = ListenerArg::new(Box::new(ForwardingReaderContext {
+                    topic_name,
+                    z_key,
+                    z,
+                    congestion_blocking,
+                    suspended,
+                    downsampling,
+                    instance_cache,
+                }));
+                let sub_listener = dds_create_listener(arg.ptr);
                 dds_lset_data_available(sub_listener, Some(data_forwarder_listener));
+                status_recorder.set_reader_listener_callbacks(sub_listener);
                 let qos_native = qos.to_qos_native();
                 let reader = dds_create_reader(dp, t, qos_native, sub_listener);
                 Qos::delete_qos_native(qos_native);
+                // the Listener is copied by the Reader
+                dds_delete_listener(sub_listener);
                 if reader >= 0 {
                     let res = dds_reader_wait_for_historical_data(reader, qos::DDS_100MS_DURATION);
                     if res < 0 {
@@ -577,7 +956,7 @@ pub fn create_forwarding_dds_reader(
                                 .unwrap_or("unrecoverable DDS retcode")
                         );
                     }
-                    Ok(reader)
+                    Ok((reader, Some(arg)))
                 } else {
                     Err(format!(
                         "Error creating DDS Reader: {}",
@@ -594,7 +973,11 @@ pub fn create_forwarding_dds_reader(
                     depth: 1,
                 });
                 let qos_native = qos.to_qos_native();
-                let reader = dds_create_reader(dp, t, qos_native, std::ptr::null());
+                let sub_listener = dds_create_listener(std::ptr::null_mut());
+                status_recorder.set_reader_listener_callbacks(sub_listener);
+                let reader = dds_create_reader(dp, t, qos_native, sub_listener);
+                Qos::delete_qos_native(qos_native);
+                dds_delete_listener(sub_listener);
                 let z_key = z_key.into_owned();
                 task::spawn(async move {
                     // loop while reader's instance handle remain the same
@@ -621,7 +1004,10 @@ pub fn create_forwarding_dds_reader(
                         ) > 0
                         {
                             let si = si.assume_init();
-                            if si[0].valid_data {
+                            if si[0].valid_data
+                                && !suspended.load(Ordering::Relaxed)
+                                && downsampling.accept(si[0].instance_handle)
+                            {
                                 log::trace!(
                                     "Route (periodic) data to zenoh resource with rid={}",
                                     z_key
@@ -629,21 +1015,91 @@ pub fn create_forwarding_dds_reader(
 
                                 let raw_sample = DDSRawSample::create(zp);
 
-                                let _ = z
-                                    .put(&z_key, &raw_sample)
-                                    .congestion_control(congestion_ctrl)
-                                    .res_sync();
+                                forward_to_zenoh(
+                                    &z,
+                                    &z_key,
+                                    &raw_sample,
+                                    zp,
+                                    congestion_control(&congestion_blocking),
+                                    &instance_cache,
+                                );
+                            } else if is_disposed(&si[0]) {
+                                remove_cached_instance(&instance_cache, zp);
                             }
                             ddsi_serdata_unref(zp);
                         }
                     }
                 });
-                Ok(reader)
+                Ok((reader, None))
             }
         }
     }
 }
 
+unsafe extern "C" fn listener_to_callback<F>(dr: dds_entity_t, arg: *mut std::os::raw::c_void)
+where
+    F: Fn(&DDSRawSample),
+{
+    let callback = arg as *mut F;
+    let mut zp: *mut ddsi_serdata = std::ptr::null_mut();
+    #[allow(clippy::uninit_assumed_init)]
+    let mut si = MaybeUninit::<[dds_sample_info_t; 1]>::uninit();
+    while dds_takecdr(
+        dr,
+        &mut zp,
+        1,
+        si.as_mut_ptr() as *mut dds_sample_info_t,
+        DDS_ANY_STATE,
+    ) > 0
+    {
+        let si = si.assume_init();
+        if si[0].valid_data {
+            let raw_sample = DDSRawSample::create(zp);
+            (*callback)(&raw_sample);
+        }
+        ddsi_serdata_unref(zp);
+    }
+}
+
+// Create a DDS Reader calling the callback for each received sample,
+// returning it with the callback to be freed once the Reader is deleted
+pub fn create_dds_reader<F>(
+    dp: dds_entity_t,
+    topic_name: String,
+    type_name: String,
+    type_info: &Option<Arc<TypeInfo>>,
+    keyless: bool,
+    qos: Qos,
+    callback: F,
+    status_recorder: &DdsStatusRecorder,
+) -> Result<(dds_entity_t, ListenerArg), String>
+where
+    F: Fn(&DDSRawSample) + std::marker::Send + 'static,
+{
+    unsafe {
+        let t = create_topic(dp, &topic_name, &type_name, type_info, keyless);
+        let arg = ListenerArg::new(Box::new(callback));
+        let sub_listener = dds_create_listener(arg.ptr);
+        dds_lset_data_available(sub_listener, Some(listener_to_callback::<F>));
+        status_recorder.set_reader_listener_callbacks(sub_listener);
+        let qos_native = qos.to_qos_native();
+        let reader = dds_create_reader(dp, t, qos_native, sub_listener);
+        Qos::delete_qos_native(qos_native);
+        // the Listener is copied by the Reader
+        dds_delete_listener(sub_listener);
+        if reader >= 0 {
+            Ok((reader, arg))
+        } else {
+            Err(format!(
+                "Error creating DDS Reader: {}",
+                CStr::from_ptr(dds_strretcode(-reader))
+                    .to_str()
+                    .unwrap_or("unrecoverable DDS retcode")
+            ))
+        }
+    }
+}
+
 unsafe fn create_topic(
     dp: dds_entity_t,
     topic_name: &str,
@@ -683,6 +1139,7 @@ pub fn create_forwarding_dds_writer(
     type_name: String,
     keyless: bool,
     qos: Qos,
+    status_recorder: &DdsStatusRecorder,
 ) -> Result<dds_entity_t, String> {
     let cton = CString::new(topic_name).unwrap().into_raw();
     let ctyn = CString::new(type_name).unwrap().into_raw();
@@ -690,8 +1147,12 @@ pub fn create_forwarding_dds_writer(
     unsafe {
         let t = cdds_create_blob_topic(dp, cton, ctyn, keyless);
         let qos_native = qos.to_qos_native();
-        let writer: i32 = dds_create_writer(dp, t, qos_native, std::ptr::null_mut());
+        let pub_listener = dds_create_listener(std::ptr::null_mut());
+        status_recorder.set_writer_listener_callbacks(pub_listener);
+        let writer: i32 = dds_create_writer(dp, t, qos_native, pub_listener);
         Qos::delete_qos_native(qos_native);
+        // the Listener is copied by the Writer
+        dds_delete_listener(pub_listener);
         if writer >= 0 {
             Ok(writer)
         } else {
@@ -705,6 +1166,72 @@ pub fn create_forwarding_dds_writer(
     }
 }
 
+// Write a serialized sample (CDR header + payload) with a DDS Writer
+pub fn dds_write(data_writer: dds_entity_t, data: Vec<u8>) -> Result<(), String> {
+    unsafe {
+        let size: ddsrt_iov_len_t = data
+            .len()
+            .try_into()
+            .map_err(|_| format!("excessive payload size ({})", data.len()))?;
+        // As per the Vec documentation (see https://doc.rust-lang.org/std/vec/struct.Vec.html#method.into_raw_parts)
+        // the only way to correctly releasing it is to create a vec using from_raw_parts
+        // and then have its destructor do the cleanup.
+        // Thus, while tempting to just pass the raw pointer to cyclone and then free it from C,
+        // that is not necessarily safe or guaranteed to be leak free.
+        // TODO replace when stable https://github.com/rust-lang/rust/issues/65816
+        let (ptr, len, capacity) = crate::vec_into_raw_parts(data);
+        let data_out = ddsrt_iovec_t {
+            iov_base: ptr as *mut std::ffi::c_void,
+            iov_len: size,
+        };
+
+        let mut sertype_ptr: *const ddsi_sertype = std::ptr::null_mut();
+        let ret = dds_get_entity_sertype(data_writer, &mut sertype_ptr);
+        if ret < 0 {
+            drop(Vec::from_raw_parts(ptr, len, capacity));
+            return Err(format!(
+                "sertype lookup failed ({})",
+                CStr::from_ptr(dds_strretcode(ret))
+                    .to_str()
+                    .unwrap_or("unrecoverable DDS retcode")
+            ));
+        }
+
+        let fwdp = ddsi_serdata_from_ser_iov(
+            sertype_ptr,
+            ddsi_serdata_kind_SDK_DATA,
+            1,
+            &data_out,
+            size as usize,
+        );
+
+        let ret = dds_writecdr(data_writer, fwdp);
+        drop(Vec::from_raw_parts(ptr, len, capacity));
+        if ret < 0 {
+            return Err(format!(
+                "DDS write({data_writer}) failed: {}",
+                CStr::from_ptr(dds_strretcode(ret))
+                    .to_str()
+                    .unwrap_or("unrecoverable DDS retcode")
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Delete a DDS Reader and free the argument of its Listener, unless the deletion failed
+// (the Listener might still be called, so its argument is leaked)
+pub fn delete_dds_reader(
+    reader: dds_entity_t,
+    listener_arg: Option<ListenerArg>,
+) -> Result<(), String> {
+    let result = delete_dds_entity(reader);
+    if result.is_err() {
+        std::mem::forget(listener_arg);
+    }
+    result
+}
+
 pub fn delete_dds_entity(entity: dds_entity_t) -> Result<(), String> {
     unsafe {
         let r = dds_delete(entity);
@@ -727,6 +1254,20 @@ pub fn get_guid(entity: &dds_entity_t) -> Result<Gid, String> {
     }
 }
 
+pub fn get_instance_handle(entity: dds_entity_t) -> Result<dds_instance_handle_t, String> {
+    unsafe {
+        let mut handle: dds_instance_handle_t = 0;
+        let r = dds_get_instance_handle(entity, &mut handle);
+        if r == 0 {
+            Ok(handle)
+        } else {
+            Err(format!(
+                "Error getting instance handle of DDS entity - retcode={r}"
+            ))
+        }
+    }
+}
+
 pub fn serialize_entity_guid<S>(entity: &dds_entity_t, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -736,3 +1277,10 @@ where
         Err(_) => s.serialize_str("UNKOWN_GUID"),
     }
 }
+
+pub fn serialize_atomic_entity_guid<S>(entity: &Arc<AtomicI32>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialize_entity_guid(&entity.load(Ordering::Relaxed), s)
+}
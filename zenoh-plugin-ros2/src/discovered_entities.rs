@@ -250,6 +250,15 @@ impl DiscoveredEntities {
         None
     }
 
+    // Return a DiscoveredX event for each complete interface of all the discovered nodes
+    pub fn get_discovered_interfaces(&self) -> Vec<ROS2DiscoveryEvent> {
+        self.nodes_info
+            .values()
+            .flat_map(|nodes| nodes.values())
+            .flat_map(NodeInfo::get_discovered_interfaces)
+            .collect()
+    }
+
     pub fn update_participant_info(
         &mut self,
         ros_info: ParticipantEntitiesInfo,
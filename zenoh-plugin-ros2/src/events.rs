@@ -56,6 +56,22 @@ impl std::fmt::Display for ROS2DiscoveryEvent {
     }
 }
 
+impl ROS2DiscoveryEvent {
+    // Return the UndiscoveredX event corresponding to a DiscoveredX event (an UndiscoveredX event is returned as is)
+    pub fn into_undiscovered(self) -> ROS2DiscoveryEvent {
+        use ROS2DiscoveryEvent::*;
+        match self {
+            DiscoveredMsgPub(node, iface) => UndiscoveredMsgPub(node, iface),
+            DiscoveredMsgSub(node, iface) => UndiscoveredMsgSub(node, iface),
+            DiscoveredServiceSrv(node, iface) => UndiscoveredServiceSrv(node, iface),
+            DiscoveredServiceCli(node, iface) => UndiscoveredServiceCli(node, iface),
+            DiscoveredActionSrv(node, iface) => UndiscoveredActionSrv(node, iface),
+            DiscoveredActionCli(node, iface) => UndiscoveredActionCli(node, iface),
+            evt => evt,
+        }
+    }
+}
+
 /// A (remote) announcement/retirement of a ROS2 interface
 #[derive(Debug)]
 pub enum ROS2AnnouncementEvent {
@@ -0,0 +1,1127 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use cyclors::qos::{HistoryKind, Qos};
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use zenoh::buffers::ZBuf;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::*;
+use zenoh::queryable::{Query, Queryable};
+use zenoh::subscriber::Subscriber;
+use zenoh::time::Timestamp;
+use zenoh_core::SyncResolve;
+
+use crate::cache_file::{CacheFile, CacheRecord, CacheWriter, CACHE_FILE_EXTENSION};
+use crate::qos_helpers::get_history_or_default;
+use crate::{Config, KE_PREFIX_PUB_CACHE};
+
+// The slot where the InstanceCache of a Publisher route is set while the route is active,
+// shared with the route's DDS Reader that pushes the routed samples into the cache
+pub type InstanceCacheSlot = Arc<RwLock<Option<Arc<InstanceCache>>>>;
+
+// The identifier of a DDS instance in an InstanceCache: the key hash of its samples
+pub type InstanceKey = [u8; 16];
+
+// The maximum number of routed samples waiting for their timestamp
+const MAX_PENDING_SAMPLES: usize = 256;
+
+// The number of obsolete records above which a cache file is re-written
+const CACHE_FILE_COMPACTION_THRESHOLD: usize = 64;
+
+// The insertion order of the samples in all the caches, used to evict the oldest samples across caches
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// The limits of an InstanceCache
+#[derive(Clone, Copy, Debug)]
+pub struct CacheLimits {
+    // the number of samples kept per instance (usize::MAX for KEEP_ALL)
+    pub depth: usize,
+    // the number of samples kept for all instances (usize::MAX if no limit)
+    pub max_samples: usize,
+    // the size in bytes of the samples kept for all instances (usize::MAX if no limit)
+    pub max_bytes: usize,
+}
+
+impl CacheLimits {
+    pub fn new(depth: usize, max_samples: usize, max_bytes: usize) -> CacheLimits {
+        CacheLimits {
+            depth: depth.max(1),
+            max_samples: max_samples.max(1),
+            max_bytes,
+        }
+    }
+}
+
+// The memory budget shared by all the caches of the bridge (see "transient_local_cache_max_bytes")
+pub struct CacheBudget {
+    // the maximum size in bytes
+    max_bytes: usize,
+    used_bytes: AtomicUsize,
+    // the caches sharing this budget, to evict samples from any of them when the budget is exceeded
+    caches: Mutex<Vec<Weak<InstanceCache>>>,
+    // if the warning on the budget being reached was logged
+    warned: AtomicBool,
+}
+
+impl CacheBudget {
+    pub fn new(max_bytes: usize) -> CacheBudget {
+        CacheBudget {
+            max_bytes,
+            used_bytes: AtomicUsize::new(0),
+            caches: Mutex::new(Vec::new()),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    fn register(&self, cache: &Arc<InstanceCache>) {
+        let mut caches = self.caches.lock().unwrap();
+        caches.retain(|c| c.strong_count() > 0);
+        caches.push(Arc::downgrade(cache));
+    }
+
+    fn is_exceeded(&self) -> bool {
+        self.used_bytes.load(Ordering::Relaxed) > self.max_bytes
+    }
+
+    // Evict samples while the budget is exceeded, choosing among all the caches the same way as within a cache
+    // (i.e. the oldest sample of the instance with the most samples), but keeping at least the last sample of each cache.
+    // Return true if some samples were evicted.
+    fn evict(&self) -> bool {
+        // upgrade the caches to not hold the lock while evicting (a cache being dropped unregisters itself)
+        let caches: Vec<Arc<InstanceCache>> = self
+            .caches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect();
+        let mut evicted = false;
+        while self.is_exceeded() {
+            let candidate = caches
+                .iter()
+                .filter_map(|cache| cache.eviction_candidate().map(|c| (c, cache)))
+                .max_by_key(|(c, _)| *c);
+            match candidate {
+                Some((_, cache)) if cache.evict() => evicted = true,
+                _ => break,
+            }
+        }
+        evicted
+    }
+
+    fn update(&self, before: usize, after: usize) {
+        if after > before {
+            self.used_bytes.fetch_add(after - before, Ordering::Relaxed);
+        } else {
+            self.used_bytes.fetch_sub(before - after, Ordering::Relaxed);
+        }
+    }
+}
+
+struct CachedSample {
+    // the insertion order in all the caches, used to evict the oldest samples
+    seq: u64,
+    payload: ZBuf,
+    timestamp: Option<Timestamp>,
+}
+
+#[derive(Default)]
+struct CachedInstances {
+    // the cached samples per instance key
+    samples: HashMap<InstanceKey, VecDeque<CachedSample>>,
+    // the total number of cached samples
+    count: usize,
+    // the total size of the cached samples' payloads
+    bytes: usize,
+}
+
+// The priority of a sample for eviction: the number of samples of its instance, then its age
+type EvictionCandidate = (usize, Reverse<u64>);
+
+impl CachedInstances {
+    // Add a sample, evicting samples if over the limits (see `evict`).
+    // Return true if some samples were evicted because of the "max_bytes" limit.
+    fn push(
+        &mut self,
+        instance: InstanceKey,
+        payload: ZBuf,
+        timestamp: Option<Timestamp>,
+        limits: &CacheLimits,
+    ) -> bool {
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        self.count += 1;
+        self.bytes += payload.len();
+        let samples = self.samples.entry(instance).or_default();
+        samples.push_back(CachedSample {
+            seq,
+            payload,
+            timestamp,
+        });
+        if samples.len() > limits.depth {
+            self.remove_oldest(instance);
+        }
+        if self.count > limits.max_samples {
+            self.evict();
+        }
+        // keep at least the new sample, even if bigger than max_bytes
+        let mut evicted = false;
+        while self.bytes > limits.max_bytes && self.count > 1 && self.evict() {
+            evicted = true;
+        }
+        evicted
+    }
+
+    // Return the instance of the next sample to evict: the oldest sample of the instance with the most samples,
+    // so the last sample of the instances rarely published (e.g. a latched map) is evicted last.
+    // If all the instances have the same number of samples, that's the oldest sample of all instances.
+    fn eviction_candidate(&self) -> Option<(EvictionCandidate, InstanceKey)> {
+        self.samples
+            .iter()
+            .filter_map(|(instance, samples)| {
+                samples
+                    .front()
+                    .map(|s| ((samples.len(), Reverse(s.seq)), *instance))
+            })
+            .max()
+    }
+
+    fn evict(&mut self) -> bool {
+        match self.eviction_candidate() {
+            Some((_, instance)) => {
+                self.remove_oldest(instance);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_oldest(&mut self, instance: InstanceKey) {
+        if let Some(samples) = self.samples.get_mut(&instance) {
+            if let Some(s) = samples.pop_front() {
+                self.count -= 1;
+                self.bytes -= s.payload.len();
+            }
+            if samples.is_empty() {
+                self.samples.remove(&instance);
+            }
+        }
+    }
+
+    fn remove_instance(&mut self, instance: InstanceKey) {
+        if let Some(samples) = self.samples.remove(&instance) {
+            self.count -= samples.len();
+            self.bytes -= samples.iter().map(|s| s.payload.len()).sum::<usize>();
+        }
+    }
+
+    // Return the cached samples with their instance, in insertion order
+    fn sorted(&self) -> Vec<(InstanceKey, &CachedSample)> {
+        let mut result: Vec<(InstanceKey, &CachedSample)> = self
+            .samples
+            .iter()
+            .flat_map(|(instance, samples)| samples.iter().map(|s| (*instance, s)))
+            .collect();
+        result.sort_by_key(|(_, s)| s.seq);
+        result
+    }
+}
+
+// A cache of the publications routed to Zenoh by a Publisher route for a TRANSIENT_LOCAL topic,
+// replying to the historical queries from remote Subscriber routes.
+// Contrary to a PublicationCache, it keeps the last `depth` samples of each DDS instance, so the samples
+// of a frequently published instance don't evict the samples of the other instances.
+// The samples are cached with the timestamp set by the Zenoh session on publication (received via a local
+// subscriber), so that a remote Subscriber route can identify the duplicates of the samples received live.
+// When the cache exceeds its "max_samples" limit (for a topic matching the "persistent_caches" option),
+// its "max_bytes" limit or the bridge's CacheBudget, the oldest samples of the instances with the most samples are evicted.
+// For a topic matching the "persistent_caches" option, the samples are also persisted into a CacheFile.
+pub struct InstanceCache {
+    // the Zenoh key expression of the route, used for the replies
+    key_expr: OwnedKeyExpr,
+    limits: CacheLimits,
+    // the memory budget shared with all the caches of the bridge
+    budget: Arc<CacheBudget>,
+    // if the warning on the "max_bytes" limit being reached was logged
+    max_bytes_warned: AtomicBool,
+    // the cached samples with their timestamp
+    instances: Mutex<CachedInstances>,
+    // the samples being published, waiting to be received by the local subscriber with their timestamp
+    pending: Mutex<VecDeque<(InstanceKey, ZBuf)>>,
+    // the file persisting the cached samples (if any)
+    file: Option<Mutex<CacheWriter>>,
+}
+
+impl Drop for InstanceCache {
+    fn drop(&mut self) {
+        // release the memory budget used by this cache
+        let bytes = self.instances.lock().unwrap().bytes;
+        self.budget.update(bytes, 0);
+    }
+}
+
+impl InstanceCache {
+    pub fn new(
+        key_expr: OwnedKeyExpr,
+        limits: CacheLimits,
+        budget: Arc<CacheBudget>,
+    ) -> Arc<InstanceCache> {
+        let cache = Arc::new(InstanceCache {
+            key_expr,
+            limits,
+            budget,
+            max_bytes_warned: AtomicBool::new(false),
+            instances: Mutex::new(CachedInstances::default()),
+            pending: Mutex::new(VecDeque::new()),
+            file: None,
+        });
+        cache.budget.register(&cache);
+        cache
+    }
+
+    // Create a cache persisted into the file at `path`, initialized with the records previously read
+    // from this file (if any). The file is re-written with the retained samples only.
+    pub fn persistent(
+        path: PathBuf,
+        key_expr: OwnedKeyExpr,
+        limits: CacheLimits,
+        budget: Arc<CacheBudget>,
+        records: Vec<CacheRecord>,
+    ) -> Result<Arc<InstanceCache>, String> {
+        let mut instances = CachedInstances::default();
+        for record in records {
+            match record {
+                CacheRecord::Sample {
+                    instance,
+                    payload,
+                    timestamp,
+                } => {
+                    instances.push(instance, payload.into(), timestamp, &limits);
+                }
+                CacheRecord::Dispose { instance } => instances.remove_instance(instance),
+                CacheRecord::Header { .. } => (),
+            }
+        }
+        let file = CacheFile::create(path, &key_expr, limits.depth, &to_records(&instances))?;
+        log::debug!(
+            "Instance cache for {key_expr}: persisted into {} with {} samples",
+            file.path().display(),
+            instances.count
+        );
+        let file = CacheWriter::new(file, key_expr.clone(), limits.depth)?;
+        budget.update(0, instances.bytes);
+        let cache = Arc::new(InstanceCache {
+            key_expr,
+            limits,
+            budget,
+            max_bytes_warned: AtomicBool::new(false),
+            instances: Mutex::new(instances),
+            pending: Mutex::new(VecDeque::new()),
+            file: Some(Mutex::new(file)),
+        });
+        cache.budget.register(&cache);
+        if cache.budget.is_exceeded() {
+            cache.evict_for_budget();
+        }
+        Ok(cache)
+    }
+
+    // Return the number of cached samples and their total size in bytes
+    pub fn size(&self) -> (usize, usize) {
+        let instances = self.instances.lock().unwrap();
+        (instances.count, instances.bytes)
+    }
+
+    // Register a sample about to be published, to be cached when received by the local subscriber
+    pub fn push_pending(&self, instance: InstanceKey, payload: ZBuf) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= MAX_PENDING_SAMPLES {
+            // the publication of the oldest sample failed: cache it without timestamp
+            if let Some((instance, payload)) = pending.pop_front() {
+                self.push(instance, payload, None);
+            }
+        }
+        pending.push_back((instance, payload));
+    }
+
+    fn push(&self, instance: InstanceKey, payload: ZBuf, timestamp: Option<Timestamp>) {
+        let mut instances = self.instances.lock().unwrap();
+        if self.file.is_some() {
+            self.persist(
+                &instances,
+                CacheRecord::Sample {
+                    instance,
+                    payload: payload.contiguous().into_owned(),
+                    timestamp,
+                },
+            );
+        }
+        let before = instances.bytes;
+        let evicted = instances.push(instance, payload, timestamp, &self.limits);
+        self.budget.update(before, instances.bytes);
+        if evicted && !self.max_bytes_warned.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "Instance cache for {}: memory limit reached with {} samples ({} bytes), the oldest samples are evicted \
+                (see 'transient_local_cache_topic_max_bytes' option)",
+                self.key_expr,
+                instances.count,
+                instances.bytes
+            );
+        }
+        // release the lock before evicting from all the caches sharing the budget (including this one)
+        drop(instances);
+        if self.budget.is_exceeded() {
+            self.evict_for_budget();
+        }
+    }
+
+    fn evict_for_budget(&self) {
+        if self.budget.evict() && !self.budget.warned.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "Instance caches: memory limit reached with {} bytes (when caching for {}), the oldest samples \
+                of the instances with the most samples are evicted (see 'transient_local_cache_max_bytes' option)",
+                self.budget.used_bytes.load(Ordering::Relaxed),
+                self.key_expr
+            );
+        }
+    }
+
+    // Return the priority for eviction of the next sample to evict from this cache (if it has more than 1 sample)
+    fn eviction_candidate(&self) -> Option<EvictionCandidate> {
+        let instances = self.instances.lock().unwrap();
+        if instances.count > 1 {
+            instances.eviction_candidate().map(|(c, _)| c)
+        } else {
+            None
+        }
+    }
+
+    fn evict(&self) -> bool {
+        let mut instances = self.instances.lock().unwrap();
+        // the cache might have changed since its eviction_candidate() call
+        if instances.count <= 1 {
+            return false;
+        }
+        let before = instances.bytes;
+        let evicted = instances.evict();
+        self.budget.update(before, instances.bytes);
+        evicted
+    }
+
+    // Remove all the samples of an instance (e.g. when disposed)
+    pub fn remove_instance(&self, instance: InstanceKey) {
+        let mut instances = self.instances.lock().unwrap();
+        if self.file.is_some() {
+            self.persist(&instances, CacheRecord::Dispose { instance });
+        }
+        let before = instances.bytes;
+        instances.remove_instance(instance);
+        self.budget.update(before, instances.bytes);
+    }
+
+    // Append a record to the cache file, or re-write the file if it has too many obsolete records
+    // (the file is written by the CacheWriter's thread)
+    fn persist(&self, instances: &CachedInstances, record: CacheRecord) {
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap();
+            if file.records() > 2 * instances.count + CACHE_FILE_COMPACTION_THRESHOLD {
+                let mut records = to_records(instances);
+                records.push(record);
+                file.rewrite(records);
+            } else {
+                file.append(record);
+            }
+        }
+    }
+
+    // Cache the pending sample matching a sample published by the local session, with its timestamp
+    fn on_local_sample(&self, sample: Sample) {
+        let mut pending = self.pending.lock().unwrap();
+        let payload = sample.value.payload.contiguous();
+        match pending.iter().position(|(_, p)| p.contiguous() == payload) {
+            Some(i) => {
+                // the samples published before were not received: cache them without timestamp
+                for (instance, payload) in pending.drain(..i) {
+                    self.push(instance, payload, None);
+                }
+                if let Some((instance, payload)) = pending.pop_front() {
+                    self.push(instance, payload, sample.timestamp);
+                }
+            }
+            None => log::trace!(
+                "Instance cache for {}: ignore local publication not routed by the bridge",
+                self.key_expr
+            ),
+        }
+    }
+
+    // Declare the local Subscriber receiving the routed samples with their timestamp,
+    // and the Queryable replying to the historical queries with the cached samples
+    pub async fn declare<'a>(
+        self: &Arc<Self>,
+        zsession: &'a Arc<Session>,
+        queryable_ke: OwnedKeyExpr,
+    ) -> Result<(Subscriber<'a, ()>, Queryable<'a, ()>), String> {
+        let cache = self.clone();
+        let subscriber = zsession
+            .declare_subscriber(&self.key_expr)
+            .allowed_origin(Locality::SessionLocal)
+            .callback(move |sample| cache.on_local_sample(sample))
+            .res_async()
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to create local Subscriber on {}: {e}",
+                    self.key_expr
+                )
+            })?;
+        let cache = self.clone();
+        let queryable = zsession
+            .declare_queryable(&queryable_ke)
+            .allowed_origin(Locality::Remote) // Note: don't reply to queries from local QueryingSubscribers
+            .callback(move |query| cache.reply(query))
+            .res_async()
+            .await
+            .map_err(|e| format!("Failed to create Queryable on {queryable_ke}: {e}"))?;
+        Ok((subscriber, queryable))
+    }
+
+    fn reply(&self, query: Query) {
+        // copy the samples to not hold the lock while replying
+        let samples: Vec<Sample> = self
+            .instances
+            .lock()
+            .unwrap()
+            .sorted()
+            .into_iter()
+            .map(|(_, s)| {
+                let sample = Sample::new(self.key_expr.clone(), s.payload.clone());
+                match s.timestamp {
+                    Some(timestamp) => sample.with_timestamp(timestamp),
+                    None => sample,
+                }
+            })
+            .collect();
+        log::trace!(
+            "Instance cache for {}: reply {} samples to query {}",
+            self.key_expr,
+            samples.len(),
+            query.selector()
+        );
+        for sample in samples {
+            if let Err(e) = query.reply(Ok(sample)).res_sync() {
+                log::warn!(
+                    "Instance cache for {}: failed to reply to query {}: {e}",
+                    self.key_expr,
+                    query.selector()
+                );
+            }
+        }
+    }
+}
+
+// The on-disk InstanceCaches of the TRANSIENT_LOCAL publications for the topics matching the "persistent_caches"
+// option, indexed by key expression. They are reloaded at startup from the "persistence_dir" directory,
+// and served to the remote bridges until the bridge stops, even when their route is deactivated or removed.
+pub struct PersistentCaches<'a> {
+    config: Arc<Config>,
+    zsession: &'a Arc<Session>,
+    plugin_id: OwnedKeyExpr,
+    budget: Arc<CacheBudget>,
+    caches: HashMap<OwnedKeyExpr, (Arc<InstanceCache>, Subscriber<'a, ()>, Queryable<'a, ()>)>,
+}
+
+impl<'a> PersistentCaches<'a> {
+    pub fn new(
+        config: Arc<Config>,
+        zsession: &'a Arc<Session>,
+        plugin_id: OwnedKeyExpr,
+        budget: Arc<CacheBudget>,
+    ) -> PersistentCaches<'a> {
+        PersistentCaches {
+            config,
+            zsession,
+            plugin_id,
+            budget,
+            caches: HashMap::new(),
+        }
+    }
+
+    // Reload the caches from the files of the "persistence_dir" directory (if configured)
+    pub async fn load(&mut self) {
+        let dir = match &self.config.persistence_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => return,
+        };
+        if let Err(e) = fs::create_dir_all(&dir) {
+            log::error!(
+                "Failed to create persistence directory {}: {e}",
+                dir.display()
+            );
+            return;
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!(
+                    "Failed to read persistence directory {}: {e}",
+                    dir.display()
+                );
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension() == Some(OsStr::new(CACHE_FILE_EXTENSION)) {
+                if let Err(e) = self.load_file(path).await {
+                    log::warn!("{e}");
+                }
+            }
+        }
+    }
+
+    async fn load_file(&mut self, path: PathBuf) -> Result<(), String> {
+        let (key_expr, depth, records) = CacheFile::read(&path)?;
+        let max_samples =
+            get_persistent_cache_retention(&self.config, &key_expr).ok_or_else(|| {
+                format!(
+                "Ignore cache file {} for {key_expr}: not matching the 'persistent_caches' option",
+                path.display()
+            )
+            })?;
+        let limits = CacheLimits::new(
+            depth,
+            max_samples,
+            get_cache_max_bytes(&self.config, &key_expr),
+        );
+        let cache = InstanceCache::persistent(
+            path,
+            key_expr.clone(),
+            limits,
+            self.budget.clone(),
+            records,
+        )?;
+        log::info!(
+            "Reloaded on-disk cache for {key_expr} with {} samples",
+            cache.instances.lock().unwrap().count
+        );
+        self.insert(key_expr, cache).await?;
+        Ok(())
+    }
+
+    // Return the on-disk cache for a Publisher route if its key expression matches the "persistent_caches" option,
+    // creating it if not yet existing
+    pub async fn get_or_create(
+        &mut self,
+        key_expr: &keyexpr,
+        keyless: bool,
+        reader_qos: &Qos,
+    ) -> Result<Option<Arc<InstanceCache>>, String> {
+        if let Some((cache, _, _)) = self.caches.get(key_expr) {
+            return Ok(Some(cache.clone()));
+        }
+        let (dir, max_samples) = match (
+            &self.config.persistence_dir,
+            get_persistent_cache_retention(&self.config, key_expr),
+        ) {
+            (Some(dir), Some(max_samples)) => (dir, max_samples),
+            _ => return Ok(None),
+        };
+        // for a keyless topic, only the "persistent_caches" limit applies (there might be several Writers)
+        let history_qos = get_history_or_default(reader_qos);
+        let depth = match (keyless, history_qos.kind, history_qos.depth) {
+            (false, HistoryKind::KEEP_LAST, n) => n.max(1) as usize,
+            _ => usize::MAX,
+        };
+        let path = CacheFile::path_for(Path::new(dir), key_expr);
+        let limits = CacheLimits::new(
+            depth,
+            max_samples,
+            get_cache_max_bytes(&self.config, key_expr),
+        );
+        let cache = InstanceCache::persistent(
+            path,
+            key_expr.into(),
+            limits,
+            self.budget.clone(),
+            Vec::new(),
+        )?;
+        self.insert(key_expr.into(), cache).await.map(Some)
+    }
+
+    async fn insert(
+        &mut self,
+        key_expr: OwnedKeyExpr,
+        cache: Arc<InstanceCache>,
+    ) -> Result<Arc<InstanceCache>, String> {
+        let (subscriber, queryable) = cache
+            .declare(
+                self.zsession,
+                *KE_PREFIX_PUB_CACHE / &*self.plugin_id / &key_expr,
+            )
+            .await?;
+        self.caches
+            .insert(key_expr, (cache.clone(), subscriber, queryable));
+        Ok(cache)
+    }
+}
+
+fn to_records(instances: &CachedInstances) -> Vec<CacheRecord> {
+    instances
+        .sorted()
+        .into_iter()
+        .map(|(instance, s)| CacheRecord::Sample {
+            instance,
+            payload: s.payload.contiguous().into_owned(),
+            timestamp: s.timestamp,
+        })
+        .collect()
+}
+
+// Return the maximum number of samples persisted for a Publisher route if a "persistence_dir" is configured
+// and keyexpr matches one of the "persistent_caches" option
+pub fn get_persistent_cache_retention(config: &Config, ke: &keyexpr) -> Option<usize> {
+    config.persistence_dir.as_ref()?;
+    config
+        .persistent_caches
+        .iter()
+        .find(|(re, _)| re.is_match(ke))
+        .map(|(_, max_samples)| *max_samples)
+}
+
+// Return the maximum size in bytes of a cache if keyexpr matches one of the "transient_local_cache_topic_max_bytes" option
+// (usize::MAX otherwise)
+pub fn get_cache_max_bytes(config: &Config, ke: &keyexpr) -> usize {
+    config
+        .transient_local_cache_topic_max_bytes
+        .iter()
+        .find(|(re, _)| re.is_match(ke))
+        .map_or(usize::MAX, |(_, max_bytes)| *max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache_file::temp_dir;
+    use zenoh::time::{new_reception_timestamp, NTP64};
+
+    fn key(i: u8) -> InstanceKey {
+        [i; 16]
+    }
+
+    fn payload(s: &str) -> ZBuf {
+        ZBuf::from(s.as_bytes().to_vec())
+    }
+
+    fn sample(i: u8, s: &str) -> (InstanceKey, String) {
+        (key(i), s.to_string())
+    }
+
+    // the cached samples with their instance, in insertion order
+    fn cached(instances: &CachedInstances) -> Vec<(InstanceKey, String)> {
+        instances
+            .sorted()
+            .into_iter()
+            .map(|(instance, s)| {
+                (
+                    instance,
+                    String::from_utf8(s.payload.contiguous().into_owned()).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn timestamp(time: u64) -> Timestamp {
+        Timestamp::new(NTP64(time), *new_reception_timestamp().get_id())
+    }
+
+    fn stamped(s: &str, time: Option<u64>) -> (String, Option<u64>) {
+        (s.to_string(), time)
+    }
+
+    // the cached samples with the time of their timestamp, in insertion order
+    fn timestamps(cache: &InstanceCache) -> Vec<(String, Option<u64>)> {
+        cache
+            .instances
+            .lock()
+            .unwrap()
+            .sorted()
+            .into_iter()
+            .map(|(_, s)| {
+                (
+                    String::from_utf8(s.payload.contiguous().into_owned()).unwrap(),
+                    s.timestamp.map(|t| t.get_time().as_u64()),
+                )
+            })
+            .collect()
+    }
+
+    // a sample published by the local session, as received by the cache's local subscriber
+    fn local_sample(cache: &InstanceCache, s: &str, time: u64) -> Sample {
+        Sample::new(cache.key_expr.clone(), payload(s)).with_timestamp(timestamp(time))
+    }
+
+    fn instance_cache(limits: CacheLimits) -> Arc<InstanceCache> {
+        InstanceCache::new(
+            OwnedKeyExpr::try_from("chatter").unwrap(),
+            limits,
+            Arc::new(CacheBudget::new(usize::MAX)),
+        )
+    }
+
+    #[test]
+    fn test_cached_instances_depth() {
+        let limits = CacheLimits::new(2, usize::MAX, usize::MAX);
+        let mut instances = CachedInstances::default();
+        assert!(!instances.push(key(1), payload("a1"), None, &limits));
+        assert!(!instances.push(key(2), payload("b1"), None, &limits));
+        assert!(!instances.push(key(1), payload("a2"), None, &limits));
+        assert!(!instances.push(key(1), payload("a3"), None, &limits));
+        // only the oldest sample of the instance over its depth is evicted
+        assert_eq!(
+            cached(&instances),
+            vec![sample(2, "b1"), sample(1, "a2"), sample(1, "a3")]
+        );
+        assert_eq!((instances.count, instances.bytes), (3, 6));
+    }
+
+    #[test]
+    fn test_cached_instances_max_samples() {
+        let limits = CacheLimits::new(2, 3, usize::MAX);
+        let mut instances = CachedInstances::default();
+        instances.push(key(1), payload("a1"), None, &limits);
+        instances.push(key(2), payload("b1"), None, &limits);
+        instances.push(key(1), payload("a2"), None, &limits);
+        // the oldest sample of the instance with the most samples is evicted
+        instances.push(key(3), payload("c1"), None, &limits);
+        assert_eq!(
+            cached(&instances),
+            vec![sample(2, "b1"), sample(1, "a2"), sample(3, "c1")]
+        );
+        // the last sample of the other instances is kept, even if older
+        instances.push(key(1), payload("a3"), None, &limits);
+        assert_eq!(
+            cached(&instances),
+            vec![sample(2, "b1"), sample(3, "c1"), sample(1, "a3")]
+        );
+        // with 1 sample per instance, the oldest sample of all instances is evicted
+        // and an instance left without samples is removed
+        instances.push(key(4), payload("d1"), None, &limits);
+        assert_eq!(
+            cached(&instances),
+            vec![sample(3, "c1"), sample(1, "a3"), sample(4, "d1")]
+        );
+        assert!(!instances.samples.contains_key(&key(2)));
+        assert_eq!((instances.count, instances.bytes), (3, 6));
+    }
+
+    #[test]
+    fn test_cached_instances_max_bytes() {
+        let limits = CacheLimits::new(usize::MAX, usize::MAX, 4);
+        let mut instances = CachedInstances::default();
+        assert!(!instances.push(key(1), payload("aa"), None, &limits));
+        assert!(!instances.push(key(2), payload("bb"), None, &limits));
+        assert!(instances.push(key(1), payload("cc"), None, &limits));
+        assert_eq!(cached(&instances), vec![sample(2, "bb"), sample(1, "cc")]);
+        // the new sample is kept even if bigger than max_bytes
+        assert!(instances.push(key(3), payload("dddddd"), None, &limits));
+        assert_eq!(cached(&instances), vec![sample(3, "dddddd")]);
+        assert_eq!((instances.count, instances.bytes), (1, 6));
+    }
+
+    #[test]
+    fn test_cached_instances_evict() {
+        let limits = CacheLimits::new(usize::MAX, usize::MAX, usize::MAX);
+        let mut instances = CachedInstances::default();
+        assert!(!instances.evict());
+        instances.push(key(1), payload("a1"), None, &limits);
+        instances.push(key(2), payload("b1"), None, &limits);
+        instances.push(key(1), payload("a2"), None, &limits);
+        instances.push(key(1), payload("a3"), None, &limits);
+        assert!(instances.evict());
+        assert_eq!(
+            cached(&instances),
+            vec![sample(2, "b1"), sample(1, "a2"), sample(1, "a3")]
+        );
+        assert!(instances.evict());
+        assert_eq!(cached(&instances), vec![sample(2, "b1"), sample(1, "a3")]);
+        assert!(instances.evict());
+        assert_eq!(cached(&instances), vec![sample(1, "a3")]);
+        assert!(instances.evict());
+        assert!(!instances.evict());
+        assert!(instances.samples.is_empty());
+        assert_eq!((instances.count, instances.bytes), (0, 0));
+    }
+
+    #[test]
+    fn test_dispose() {
+        let cache = instance_cache(CacheLimits::new(2, usize::MAX, usize::MAX));
+        cache.push(key(1), payload("a1"), None);
+        cache.push(key(2), payload("b1"), None);
+        cache.push(key(1), payload("a2"), None);
+        cache.remove_instance(key(1));
+        assert_eq!(
+            cached(&cache.instances.lock().unwrap()),
+            vec![sample(2, "b1")]
+        );
+        assert_eq!(cache.size(), (1, 2));
+        // disposing an instance without samples has no effect
+        cache.remove_instance(key(3));
+        assert_eq!(cache.size(), (1, 2));
+        // a disposed instance can be re-published
+        cache.push(key(1), payload("a3"), None);
+        assert_eq!(
+            cached(&cache.instances.lock().unwrap()),
+            vec![sample(2, "b1"), sample(1, "a3")]
+        );
+    }
+
+    #[test]
+    fn test_on_local_sample() {
+        let cache = instance_cache(CacheLimits::new(usize::MAX, usize::MAX, usize::MAX));
+        cache.push_pending(key(1), payload("a1"));
+        cache.push_pending(key(2), payload("b1"));
+        cache.push_pending(key(1), payload("a2"));
+        assert_eq!(cache.size(), (0, 0));
+
+        // the samples published before the received one are cached without timestamp
+        cache.on_local_sample(local_sample(&cache, "b1", 2));
+        assert_eq!(
+            timestamps(&cache),
+            vec![stamped("a1", None), stamped("b1", Some(2))]
+        );
+        assert_eq!(cache.pending.lock().unwrap().len(), 1);
+
+        // a local publication not routed by the bridge is ignored
+        cache.on_local_sample(local_sample(&cache, "x1", 3));
+        assert_eq!(cache.size(), (2, 4));
+        assert_eq!(cache.pending.lock().unwrap().len(), 1);
+
+        cache.on_local_sample(local_sample(&cache, "a2", 4));
+        assert_eq!(
+            timestamps(&cache),
+            vec![
+                stamped("a1", None),
+                stamped("b1", Some(2)),
+                stamped("a2", Some(4))
+            ]
+        );
+        assert!(cache.pending.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_on_local_sample_same_payloads() {
+        let cache = instance_cache(CacheLimits::new(usize::MAX, usize::MAX, usize::MAX));
+        cache.push_pending(key(1), payload("x"));
+        cache.push_pending(key(2), payload("x"));
+        // the samples with the same payload are matched in publication order
+        cache.on_local_sample(local_sample(&cache, "x", 1));
+        cache.on_local_sample(local_sample(&cache, "x", 2));
+        let instances = cache.instances.lock().unwrap();
+        let cached: Vec<(InstanceKey, Option<u64>)> = instances
+            .sorted()
+            .into_iter()
+            .map(|(instance, s)| (instance, s.timestamp.map(|t| t.get_time().as_u64())))
+            .collect();
+        assert_eq!(cached, vec![(key(1), Some(1)), (key(2), Some(2))]);
+    }
+
+    #[test]
+    fn test_pending_overflow() {
+        let cache = instance_cache(CacheLimits::new(usize::MAX, usize::MAX, usize::MAX));
+        for i in 0..=MAX_PENDING_SAMPLES {
+            cache.push_pending(key(1), payload(&format!("{i:03}")));
+        }
+        // the oldest pending sample is cached without timestamp
+        assert_eq!(timestamps(&cache), vec![stamped("000", None)]);
+        assert_eq!(cache.pending.lock().unwrap().len(), MAX_PENDING_SAMPLES);
+    }
+
+    #[test]
+    fn test_persistent_reload() {
+        let dir = temp_dir("instance-cache-reload");
+        let key_expr = OwnedKeyExpr::try_from("chatter").unwrap();
+        let path = CacheFile::path_for(&dir, &key_expr);
+        let limits = CacheLimits::new(1, usize::MAX, usize::MAX);
+        let budget = Arc::new(CacheBudget::new(usize::MAX));
+        let cache = InstanceCache::persistent(
+            path.clone(),
+            key_expr.clone(),
+            limits,
+            budget.clone(),
+            Vec::new(),
+        )
+        .unwrap();
+        cache.push(key(1), payload("a1"), None);
+        cache.push(key(2), payload("b1"), None);
+        cache.push(key(1), payload("a2"), Some(timestamp(3)));
+        cache.push(key(3), payload("c1"), None);
+        cache.remove_instance(key(2));
+        drop(cache);
+
+        // all the records were appended
+        let (ke, depth, records) = CacheFile::read(&path).unwrap();
+        assert_eq!((ke, depth, records.len()), (key_expr.clone(), 1, 5));
+        // the depth and the dispose are applied on reload
+        let cache =
+            InstanceCache::persistent(path.clone(), key_expr, limits, budget, records).unwrap();
+        assert_eq!(
+            timestamps(&cache),
+            vec![stamped("a2", Some(3)), stamped("c1", None)]
+        );
+        // the file is re-written with the retained samples only
+        assert_eq!(cache.file.as_ref().unwrap().lock().unwrap().records(), 2);
+        assert_eq!(CacheFile::read(&path).unwrap().2.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_persistent_compaction() {
+        let dir = temp_dir("instance-cache-compaction");
+        let key_expr = OwnedKeyExpr::try_from("chatter").unwrap();
+        let path = CacheFile::path_for(&dir, &key_expr);
+        let cache = InstanceCache::persistent(
+            path.clone(),
+            key_expr,
+            CacheLimits::new(1, usize::MAX, usize::MAX),
+            Arc::new(CacheBudget::new(usize::MAX)),
+            Vec::new(),
+        )
+        .unwrap();
+        for i in 0..(4 * CACHE_FILE_COMPACTION_THRESHOLD) {
+            cache.push(key(1), payload(&format!("{i:03}")), None);
+            let records = cache.file.as_ref().unwrap().lock().unwrap().records();
+            assert!(records <= 2 + CACHE_FILE_COMPACTION_THRESHOLD + 1);
+        }
+        let expected = timestamps(&cache);
+        let expected_records = cache.file.as_ref().unwrap().lock().unwrap().records();
+        let (key_expr, limits) = (cache.key_expr.clone(), cache.limits);
+        // wait for the CacheWriter to write all the records
+        drop(cache);
+
+        // the re-written file has the same samples as the cache
+        let (_, _, records) = CacheFile::read(&path).unwrap();
+        assert_eq!(records.len(), expected_records);
+        let reloaded = InstanceCache::persistent(
+            path,
+            key_expr,
+            limits,
+            Arc::new(CacheBudget::new(usize::MAX)),
+            records,
+        )
+        .unwrap();
+        assert_eq!(timestamps(&reloaded), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_budget() {
+        let budget = CacheBudget::new(10);
+        budget.update(0, 6);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 6);
+        budget.update(0, 4);
+        assert!(!budget.is_exceeded());
+        budget.update(4, 5);
+        assert!(budget.is_exceeded());
+        budget.update(6, 2);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 7);
+        assert!(!budget.is_exceeded());
+        budget.update(7, 0);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_cache_budget_eviction() {
+        let budget = Arc::new(CacheBudget::new(8));
+        let limits = CacheLimits::new(usize::MAX, usize::MAX, usize::MAX);
+        let cache1 = InstanceCache::new(
+            OwnedKeyExpr::try_from("chatter1").unwrap(),
+            limits,
+            budget.clone(),
+        );
+        let cache2 = InstanceCache::new(
+            OwnedKeyExpr::try_from("chatter2").unwrap(),
+            limits,
+            budget.clone(),
+        );
+        cache1.push(key(1), payload("a1"), None);
+        cache1.push(key(2), payload("b1"), None);
+        cache1.push(key(3), payload("c1"), None);
+        cache2.push(key(1), payload("x1"), None);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 8);
+        assert!(!budget.warned.load(Ordering::Relaxed));
+
+        // the oldest sample of all caches is evicted, not only from the cache exceeding the budget
+        cache2.push(key(2), payload("y1"), None);
+        assert_eq!(
+            cached(&cache1.instances.lock().unwrap()),
+            vec![sample(2, "b1"), sample(3, "c1")]
+        );
+        assert_eq!(cache2.size(), (2, 4));
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 8);
+        assert!(budget.warned.load(Ordering::Relaxed));
+
+        // the last sample of each cache is kept, even if exceeding the budget
+        cache2.push(key(3), payload("zzzzzzzz"), None);
+        assert_eq!(
+            cached(&cache1.instances.lock().unwrap()),
+            vec![sample(3, "c1")]
+        );
+        assert_eq!(
+            cached(&cache2.instances.lock().unwrap()),
+            vec![sample(3, "zzzzzzzz")]
+        );
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 10);
+
+        // the budget used by a cache is released when dropped
+        drop(cache2);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 2);
+        drop(cache1);
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_cache_budget_eviction_busy_instance() {
+        let budget = Arc::new(CacheBudget::new(8));
+        let limits = CacheLimits::new(usize::MAX, usize::MAX, usize::MAX);
+        let latched = InstanceCache::new(
+            OwnedKeyExpr::try_from("map").unwrap(),
+            limits,
+            budget.clone(),
+        );
+        let busy = InstanceCache::new(
+            OwnedKeyExpr::try_from("chatter").unwrap(),
+            limits,
+            budget.clone(),
+        );
+        latched.push(key(1), payload("m1"), None);
+        latched.push(key(2), payload("n1"), None);
+        busy.push(key(1), payload("a1"), None);
+        busy.push(key(1), payload("a2"), None);
+        // the samples of the busy instance are evicted before the older samples of the latched instances
+        busy.push(key(1), payload("a3"), None);
+        busy.push(key(1), payload("a4"), None);
+        assert_eq!(latched.size(), (2, 4));
+        assert_eq!(
+            cached(&busy.instances.lock().unwrap()),
+            vec![sample(1, "a3"), sample(1, "a4")]
+        );
+        assert_eq!(budget.used_bytes.load(Ordering::Relaxed), 8);
+    }
+}
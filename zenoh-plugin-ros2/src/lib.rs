@@ -16,13 +16,17 @@ use cyclors::*;
 use events::ROS2AnnouncementEvent;
 use flume::{unbounded, Receiver, Sender};
 use futures::select;
+use futures::StreamExt;
 use git_version::git_version;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
 use std::collections::HashMap;
 use std::env;
 use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
 use zenoh::liveliness::LivelinessToken;
 use zenoh::plugins::{Plugin, RunningPluginTrait, Runtime, ZenohPlugin};
 use zenoh::prelude::r#async::AsyncResolve;
@@ -30,34 +34,44 @@ use zenoh::prelude::*;
 use zenoh::queryable::Query;
 use zenoh::Result as ZResult;
 use zenoh::Session;
-use zenoh_core::{bail, zerror};
+use zenoh_core::{zerror, zread};
 use zenoh_ext::SubscriberBuilderExt;
-use zenoh_util::Timed;
+use zenoh_util::{Timed, TimedEvent, Timer};
 
+mod cache_file;
 pub mod config;
 mod dds_discovery;
 mod discovered_entities;
 mod discovery_mgr;
 mod events;
 mod gid;
+mod instance_cache;
 mod liveliness_mgt;
 mod node_info;
 mod qos_helpers;
 mod ros2_utils;
 mod ros_discovery;
+mod route_action_cli;
+mod route_action_srv;
 mod route_publisher;
+mod route_service_cli;
+mod route_service_srv;
 mod route_subscriber;
 mod routes_mgr;
 use config::Config;
 use dds_discovery::*;
 
+use crate::discovered_entities::DiscoveredEntities;
 use crate::discovery_mgr::DiscoveryMgr;
 use crate::events::ROS2DiscoveryEvent;
 use crate::liveliness_mgt::{
-    ke_liveliness_all, ke_liveliness_plugin, parse_ke_liveliness_pub, parse_ke_liveliness_sub,
+    ke_liveliness_all, ke_liveliness_plugin, parse_ke_liveliness_action_cli,
+    parse_ke_liveliness_action_srv, parse_ke_liveliness_pub, parse_ke_liveliness_service_cli,
+    parse_ke_liveliness_service_srv, parse_ke_liveliness_sub,
 };
+use crate::qos_helpers::get_partition_prefix;
 use crate::ros_discovery::RosDiscoveryInfoMgr;
-use crate::routes_mgr::RoutesMgr;
+use crate::routes_mgr::{RoutesMgr, PARTITION_MARKER};
 
 pub const GIT_VERSION: &str = git_version!(prefix = "v", cargo_prefix = "v");
 
@@ -101,6 +115,25 @@ const CYCLONEDDS_CONFIG_ENABLE_SHM: &str = r#"<CycloneDDS><Domain><SharedMemory>
 const ROS_DISCOVERY_INFO_POLL_INTERVAL_MS: u64 = 100;
 const ROS_DISCOVERY_INFO_PUSH_INTERVAL_MS: u64 = 100;
 
+// interval between each check of the retained caches expiry (see "latch_retention")
+const LATCH_RETENTION_CHECK_INTERVAL_MS: u64 = 1000;
+
+// the configuration options that can be changed at runtime (via the zenoh admin space).
+// Note: "qos_overrides" is not part of them, since its rules apply only to the DDS Readers and Writers
+// at their creation (changing it at runtime would require to re-create all the routes).
+const HOT_CONFIG_OPTIONS: [&str; 5] = [
+    "allow",
+    "deny",
+    "pub_max_frequencies",
+    "reliable_routes_blocking",
+    "queries_timeout",
+];
+
+// selector parameter used to address a Service or Action query to a specific bridge (by its plugin id)
+const SELECTOR_PARAM_PLUGIN_ID: &str = "_plugin_id";
+// selector parameter used to pass the timeout (in seconds) of a Service or Action query to the bridge serving it
+const SELECTOR_PARAM_TIMEOUT: &str = "_timeout";
+
 zenoh_plugin_trait::declare_plugin!(ROS2Plugin);
 
 #[allow(clippy::upper_case_acronyms)]
@@ -125,14 +158,23 @@ impl Plugin for ROS2Plugin {
             .ok_or_else(|| zerror!("Plugin `{}`: missing config", name))?;
         let config: Config = serde_json::from_value(plugin_conf.clone())
             .map_err(|e| zerror!("Plugin `{}` configuration error: {}", name, e))?;
-        async_std::task::spawn(run(runtime.clone(), config));
-        Ok(Box::new(ROS2Plugin))
+        let (config_tx, config_rcv) = unbounded();
+        async_std::task::spawn(watch_config(runtime.clone(), name.to_string(), config_tx));
+        async_std::task::spawn(run(runtime.clone(), config, config_rcv));
+        Ok(Box::new(RunningROS2Plugin))
     }
 }
 
-impl RunningPluginTrait for ROS2Plugin {
+// The running plugin, validating the configuration changes
+struct RunningROS2Plugin;
+
+impl RunningPluginTrait for RunningROS2Plugin {
     fn config_checker(&self) -> zenoh::plugins::ValidationFunction {
-        Arc::new(|_, _, _| bail!("ROS2Plugin does not support hot configuration changes."))
+        // Note: the change is applied by the plugin's task once committed by zenoh (see watch_config())
+        Arc::new(|_, current, new| {
+            check_config_change(current, new).map_err(|e| zerror!("ROS2Plugin {}", e))?;
+            Ok(None)
+        })
     }
 
     fn adminspace_getter<'a>(
@@ -152,7 +194,49 @@ impl RunningPluginTrait for ROS2Plugin {
     }
 }
 
-pub async fn run(runtime: Runtime, config: Config) {
+// Check if a configuration change is allowed at runtime: only the HOT_CONFIG_OPTIONS might change,
+// and the new configuration must be valid
+fn check_config_change(
+    current: &serde_json::Map<String, serde_json::Value>,
+    new: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Config, String> {
+    for key in current.keys().chain(new.keys()) {
+        if current.get(key) != new.get(key) && !HOT_CONFIG_OPTIONS.contains(&key.as_str()) {
+            return Err(format!(
+                "does not support hot change of '{key}' configuration (only {HOT_CONFIG_OPTIONS:?} can be changed)"
+            ));
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(new.clone()))
+        .map_err(|e| format!("configuration error: {e}"))
+}
+
+// Pass the plugin's configuration to the plugin's task each time a change is committed by zenoh
+// (i.e. after its validation by the config_checker)
+async fn watch_config(runtime: Runtime, name: String, config_tx: Sender<Config>) {
+    let prefix = format!("plugins/{name}");
+    let notifications = runtime.config.subscribe();
+    while let Ok(key) = notifications.recv_async().await {
+        if *key != *prefix && !key.starts_with(&format!("{prefix}/")) {
+            continue;
+        }
+        let plugin_conf = runtime.config.lock().plugin(&name).cloned();
+        let config = match plugin_conf.map(serde_json::from_value::<Config>) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                log::error!("ROS2Plugin configuration error: {e}");
+                continue;
+            }
+            None => continue,
+        };
+        if config_tx.send(config).is_err() {
+            // the plugin's task stopped
+            break;
+        }
+    }
+}
+
+pub async fn run(runtime: Runtime, config: Config, config_rcv: Receiver<Config>) {
     // Try to initiate login.
     // Required in case of dynamic lib, otherwise no logs.
     // But cannot be done twice in case of static link.
@@ -270,7 +354,7 @@ pub async fn run(runtime: Runtime, config: Config) {
         admin_space: HashMap::<OwnedKeyExpr, AdminRef>::new(),
     };
 
-    ros2_plugin.run().await;
+    ros2_plugin.run(config_rcv).await;
 }
 
 pub struct ROS2PluginRuntime<'a> {
@@ -306,7 +390,7 @@ enum AdminRef {
 }
 
 impl<'a> ROS2PluginRuntime<'a> {
-    async fn run(&mut self) {
+    async fn run(&mut self, config_rcv: Receiver<Config>) {
         // Subscribe to all liveliness info from other ROS2 plugins
         let ke_liveliness_all = zenoh::keformat!(
             ke_liveliness_all::formatter(),
@@ -369,13 +453,26 @@ impl<'a> ROS2PluginRuntime<'a> {
             ros_discovery_mgr,
             admin_prefix.clone(),
         );
+        routes_mgr.load_persistent_caches().await;
+
+        // Timer for periodic check of the retained caches expiry
+        let timer = Timer::default();
+        let (tx, retention_timer_rcv): (Sender<()>, Receiver<()>) = unbounded();
+        let retention_timer_event = TimedEvent::periodic(
+            Duration::from_millis(LATCH_RETENTION_CHECK_INTERVAL_MS),
+            ChannelEvent { tx },
+        );
+        timer.add_async(retention_timer_event).await;
+
+        // the configuration changes committed by zenoh (see watch_config())
+        let mut config_changes = config_rcv.into_stream();
 
         loop {
             select!(
                 evt = discovery_rcv.recv_async() => {
                     match evt {
                         Ok(evt) => {
-                            if self.is_allowed(&evt) {
+                            if is_allowed(&self.config, &evt, &discovery_mgr.discovered_entities) {
                                 log::info!("{evt} - Allowed");
                                 // pass ROS2DiscoveryEvent to RoutesMgr
                                 if let Err(e) = routes_mgr.on_ros_discovery_event(evt).await {
@@ -404,11 +501,16 @@ impl<'a> ROS2PluginRuntime<'a> {
                                     // New remote bridge detected
                                     (None, SampleKind::Put) => {
                                         log::info!("New ROS 2 bridge detected: {}", plugin_id);
+                                        routes_mgr.on_remote_bridge_joined(plugin_id);
                                         // make all routes for a TRANSIENT_LOCAL Subscriber to query historical publications from this new plugin
-                                        routes_mgr.query_historical_all_publications(plugin_id).await;
+                                        routes_mgr.query_historical_all_publications(plugin_id);
                                     }
                                     // New remote bridge left
-                                    (None, SampleKind::Delete) => log::info!("Remote ROS 2 bridge left: {}", plugin_id),
+                                    (None, SampleKind::Delete) => {
+                                        log::info!("Remote ROS 2 bridge left: {}", plugin_id);
+                                        // abort the goals accepted by the Action Servers it was serving
+                                        routes_mgr.on_remote_bridge_left(plugin_id);
+                                    }
                                     // the liveliness token corresponds to a ROS2 announcement
                                     (Some(remaining), _) => {
                                         // parse it and pass ROS2AnnouncementEvent to RoutesMgr
@@ -431,6 +533,16 @@ impl<'a> ROS2PluginRuntime<'a> {
                     }
                 },
 
+                _ = retention_timer_rcv.recv_async() => {
+                    routes_mgr.check_retained_routes();
+                },
+
+                config = config_changes.next() => {
+                    if let Some(config) = config {
+                        self.update_config(config, &discovery_mgr.discovered_entities, &mut routes_mgr).await;
+                    }
+                },
+
                 get_request = admin_queryable.recv_async() => {
                     if let Ok(query) = get_request {
                         self.treat_admin_query(&query).await;
@@ -489,25 +601,90 @@ impl<'a> ROS2PluginRuntime<'a> {
                     plugin_id,
                     zenoh_key_expr,
                 }),
+            ("SS/", SampleKind::Put) => parse_ke_liveliness_service_srv(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(
+                    |(plugin_id, zenoh_key_expr, ros2_type)| AnnouncedServiceSrv {
+                        plugin_id,
+                        zenoh_key_expr,
+                        ros2_type,
+                    },
+                ),
+            ("SS/", SampleKind::Delete) => parse_ke_liveliness_service_srv(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(|(plugin_id, zenoh_key_expr, ..)| RetiredServiceSrv {
+                    plugin_id,
+                    zenoh_key_expr,
+                }),
+            ("SC/", SampleKind::Put) => parse_ke_liveliness_service_cli(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(
+                    |(plugin_id, zenoh_key_expr, ros2_type)| AnnouncedServiceCli {
+                        plugin_id,
+                        zenoh_key_expr,
+                        ros2_type,
+                    },
+                ),
+            ("SC/", SampleKind::Delete) => parse_ke_liveliness_service_cli(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(|(plugin_id, zenoh_key_expr, ..)| RetiredServiceCli {
+                    plugin_id,
+                    zenoh_key_expr,
+                }),
+            ("AS/", SampleKind::Put) => parse_ke_liveliness_action_srv(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(
+                    |(plugin_id, zenoh_key_expr, ros2_type)| AnnouncedActionSrv {
+                        plugin_id,
+                        zenoh_key_expr,
+                        ros2_type,
+                    },
+                ),
+            ("AS/", SampleKind::Delete) => parse_ke_liveliness_action_srv(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(|(plugin_id, zenoh_key_expr, ..)| RetiredActionSrv {
+                    plugin_id,
+                    zenoh_key_expr,
+                }),
+            ("AC/", SampleKind::Put) => parse_ke_liveliness_action_cli(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(
+                    |(plugin_id, zenoh_key_expr, ros2_type)| AnnouncedActionCli {
+                        plugin_id,
+                        zenoh_key_expr,
+                        ros2_type,
+                    },
+                ),
+            ("AC/", SampleKind::Delete) => parse_ke_liveliness_action_cli(liveliness_ke)
+                .map_err(|e| format!("Received invalid liveliness token: {e}"))
+                .map(|(plugin_id, zenoh_key_expr, ..)| RetiredActionCli {
+                    plugin_id,
+                    zenoh_key_expr,
+                }),
             _ => Err(format!("invalid ROS2 interface kind: {iface_kind}")),
         }
     }
 
-    fn is_allowed(&self, evt: &ROS2DiscoveryEvent) -> bool {
-        if let Some(allowance) = &self.config.allowance {
-            use ROS2DiscoveryEvent::*;
-            match evt {
-                DiscoveredMsgPub(_, iface) => allowance.is_publisher_allowed(&iface.name),
-                DiscoveredMsgSub(_, iface) => allowance.is_subscriber_allowed(&iface.name),
-                DiscoveredServiceSrv(_, iface) => allowance.is_service_srv_allowed(&iface.name),
-                DiscoveredServiceCli(_, iface) => allowance.is_service_cli_allowed(&iface.name),
-                DiscoveredActionSrv(_, iface) => allowance.is_action_srv_allowed(&iface.name),
-                DiscoveredActionCli(_, iface) => allowance.is_action_cli_allowed(&iface.name),
-                _ => true, // only Undiscovered events remain - always allow them (in case dynamic change of config is supported)
+    // Apply a configuration change, where only the HOT_CONFIG_OPTIONS might differ: update the routes,
+    // and re-evaluate the allowance of the discovered interfaces, routing the newly allowed ones
+    // and un-routing the newly denied ones
+    async fn update_config(
+        &mut self,
+        config: Config,
+        discovered_entities: &RwLock<DiscoveredEntities>,
+        routes_mgr: &mut RoutesMgr<'_>,
+    ) {
+        log::info!("ROS2 plugin configuration changed: {:?}", config);
+        let interfaces = zread!(discovered_entities).get_discovered_interfaces();
+        let changes = get_allowance_changes(&self.config, &config, interfaces, discovered_entities);
+
+        self.config = Arc::new(config);
+        routes_mgr.update_config(self.config.clone()).await;
+
+        for evt in changes {
+            if let Err(e) = routes_mgr.on_ros_discovery_event(evt).await {
+                log::warn!("Error updating route: {e}");
             }
-        } else {
-            // no allow/deny configured => allow all
-            true
         }
     }
 
@@ -576,3 +753,246 @@ where
 {
     s.serialize_bool(opt.is_some())
 }
+
+pub(crate) fn serialize_atomic_bool<S>(b: &Arc<AtomicBool>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_bool(b.load(Ordering::Relaxed))
+}
+
+// Return the discovery events to apply for the discovered interfaces whose allowance changes with a new configuration:
+// the DiscoveredX events of the newly allowed ones, and the UndiscoveredX events of the newly denied ones
+fn get_allowance_changes(
+    old_config: &Config,
+    new_config: &Config,
+    interfaces: Vec<ROS2DiscoveryEvent>,
+    discovered_entities: &RwLock<DiscoveredEntities>,
+) -> Vec<ROS2DiscoveryEvent> {
+    interfaces
+        .into_iter()
+        .filter_map(|evt| {
+            match (
+                is_allowed(old_config, &evt, discovered_entities),
+                is_allowed(new_config, &evt, discovered_entities),
+            ) {
+                (false, true) => {
+                    log::info!("{evt} - Allowed per new config");
+                    Some(evt)
+                }
+                (true, false) => {
+                    log::info!("{evt} - Denied per new config");
+                    Some(evt.into_undiscovered())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn is_allowed(
+    config: &Config,
+    evt: &ROS2DiscoveryEvent,
+    discovered_entities: &RwLock<DiscoveredEntities>,
+) -> bool {
+    if let Some(allowance) = &config.allowance {
+        use ROS2DiscoveryEvent::*;
+        match evt {
+            // with "partitions_as_key_prefix", the name of a partitioned topic is its route name "%<partition>/<topic>"
+            DiscoveredMsgPub(_, iface) => {
+                let entities = zread!(discovered_entities);
+                let qos = entities.get_writer(&iface.writer).map(|e| &e.qos);
+                allowance.is_publisher_allowed(&get_partitioned_name(config, &iface.name, qos))
+            }
+            DiscoveredMsgSub(_, iface) => {
+                let entities = zread!(discovered_entities);
+                let qos = entities.get_reader(&iface.reader).map(|e| &e.qos);
+                allowance.is_subscriber_allowed(&get_partitioned_name(config, &iface.name, qos))
+            }
+            DiscoveredServiceSrv(_, iface) => allowance.is_service_srv_allowed(&iface.name),
+            DiscoveredServiceCli(_, iface) => allowance.is_service_cli_allowed(&iface.name),
+            DiscoveredActionSrv(_, iface) => allowance.is_action_srv_allowed(&iface.name),
+            DiscoveredActionCli(_, iface) => allowance.is_action_cli_allowed(&iface.name),
+            _ => true, // only Undiscovered events remain - always allow them (the config might have changed since discovery)
+        }
+    } else {
+        // no allow/deny configured => allow all
+        true
+    }
+}
+
+// Return the name of a topic as its route name: prefixed with '%' and its first DDS partition
+// if "partitions_as_key_prefix" is enabled (the other partitions are not used)
+fn get_partitioned_name(config: &Config, ros2_name: &str, qos: Option<&qos::Qos>) -> String {
+    match qos.and_then(|qos| get_partition_prefix(config, qos)) {
+        Some(partition) => format!("{PARTITION_MARKER}{partition}{ros2_name}"),
+        None => ros2_name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gid::Gid;
+    use crate::node_info::{MsgPub, MsgSub, ServiceSrv};
+
+    fn json_map(json: &str) -> serde_json::Map<String, serde_json::Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_check_config_change() {
+        let current = json_map(r#"{"domain": 1, "allow": {"publishers": ["/chatter"]}}"#);
+        // only HOT_CONFIG_OPTIONS change
+        for new in [
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter", "/tf"]}}"#,
+            r#"{"domain": 1, "deny": {"publishers": ["/rosout"]}}"#,
+            r#"{"domain": 1}"#,
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter"]}, "pub_max_frequencies": ["/chatter=10"],
+                "reliable_routes_blocking": false, "queries_timeout": 2.5}"#,
+        ] {
+            assert!(
+                check_config_change(&current, &json_map(new)).is_ok(),
+                "{new}"
+            );
+        }
+        // other options (including "qos_overrides") can't change
+        for new in [
+            r#"{"domain": 2, "allow": {"publishers": ["/chatter"]}}"#,
+            r#"{"allow": {"publishers": ["/chatter"]}}"#,
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter"]}, "namespace": "/robot_1"}"#,
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter"]},
+                "qos_overrides": [{"topics": ["/chatter"], "durability": "transient_local"}]}"#,
+        ] {
+            let e = check_config_change(&current, &json_map(new)).unwrap_err();
+            assert!(e.contains("does not support hot change"), "{new}: {e}");
+        }
+        // the new configuration must be valid
+        for new in [
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter"]}, "queries_timeout": "x"}"#,
+            r#"{"domain": 1, "allow": {"publishers": ["/chatter"]}, "pub_max_frequencies": ["/chatter"]}"#,
+        ] {
+            let e = check_config_change(&current, &json_map(new)).unwrap_err();
+            assert!(e.starts_with("configuration error"), "{new}: {e}");
+        }
+    }
+
+    #[test]
+    fn test_allowance_changes() {
+        let discovered_entities = RwLock::new(DiscoveredEntities::default());
+        let interfaces = || {
+            vec![
+                ROS2DiscoveryEvent::DiscoveredMsgPub(
+                    "/talker".into(),
+                    MsgPub::create(
+                        "/chatter".into(),
+                        "std_msgs/msg/String".into(),
+                        Gid::NOT_DISCOVERED,
+                    )
+                    .unwrap(),
+                ),
+                ROS2DiscoveryEvent::DiscoveredMsgSub(
+                    "/listener".into(),
+                    MsgSub::create(
+                        "/chatter".into(),
+                        "std_msgs/msg/String".into(),
+                        Gid::NOT_DISCOVERED,
+                    )
+                    .unwrap(),
+                ),
+                ROS2DiscoveryEvent::DiscoveredServiceSrv(
+                    "/server".into(),
+                    ServiceSrv::create(
+                        "/add_two_ints".into(),
+                        "example_interfaces/srv/AddTwoInts".into(),
+                    )
+                    .unwrap(),
+                ),
+            ]
+        };
+        let changes = |old: &str, new: &str| -> Vec<String> {
+            get_allowance_changes(
+                &serde_json::from_str(old).unwrap(),
+                &serde_json::from_str(new).unwrap(),
+                interfaces(),
+                &discovered_entities,
+            )
+            .iter()
+            .map(ROS2DiscoveryEvent::to_string)
+            .collect()
+        };
+        const DENY_CHATTER_AND_SERVERS: &str =
+            r#"{"deny": {"publishers": ["/chatter"], "service_servers": [".*"]}}"#;
+
+        // the newly denied interfaces are undiscovered
+        assert_eq!(
+            changes("{}", DENY_CHATTER_AND_SERVERS),
+            vec![
+                "Node /talker undeclares Publisher /chatter: std_msgs/msg/String",
+                "Node /server undeclares Service Server /add_two_ints: example_interfaces/srv/AddTwoInts",
+            ]
+        );
+        // the newly allowed interfaces are discovered again
+        assert_eq!(
+            changes(
+                DENY_CHATTER_AND_SERVERS,
+                r#"{"deny": {"service_servers": [".*"]}}"#
+            ),
+            vec!["Node /talker declares Publisher /chatter: std_msgs/msg/String"]
+        );
+        assert_eq!(
+            changes(
+                DENY_CHATTER_AND_SERVERS,
+                r#"{"allow": {"publishers": ["/chatter"]}}"#
+            ),
+            vec![
+                "Node /talker declares Publisher /chatter: std_msgs/msg/String",
+                "Node /listener undeclares Subscriber /chatter: std_msgs/msg/String",
+            ]
+        );
+        // with an "allow" list, the interfaces not listed are denied
+        assert_eq!(
+            changes("{}", r#"{"allow": {"subscribers": ["/chatter"]}}"#),
+            vec![
+                "Node /talker undeclares Publisher /chatter: std_msgs/msg/String",
+                "Node /server undeclares Service Server /add_two_ints: example_interfaces/srv/AddTwoInts",
+            ]
+        );
+        // no change of allowance
+        assert!(changes(r#"{"deny": {"publishers": ["/tf"]}}"#, "{}").is_empty());
+    }
+
+    #[test]
+    fn test_partitioned_name() {
+        let mut qos = qos::Qos::default();
+        qos.partition = Some(vec!["robot_1".into(), "robots".into()]);
+        let config: Config = serde_json::from_str(r#"{"partitions_as_key_prefix": true}"#).unwrap();
+        // the same name as the route, with the first partition only
+        assert_eq!(
+            get_partitioned_name(&config, "/chatter", Some(&qos)),
+            "%robot_1/chatter"
+        );
+        assert_eq!(get_partitioned_name(&config, "/chatter", None), "/chatter");
+        let allowance: Config = serde_json::from_str(
+            r#"{"partitions_as_key_prefix": true, "allow": {"publishers": ["%robot_1/.*"]}}"#,
+        )
+        .unwrap();
+        let allowance = allowance.allowance.unwrap();
+        assert!(allowance.is_publisher_allowed(&get_partitioned_name(
+            &config,
+            "/chatter",
+            Some(&qos)
+        )));
+        assert!(!allowance.is_publisher_allowed(&get_partitioned_name(
+            &config,
+            "/robot_1/chatter",
+            None
+        )));
+
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            get_partitioned_name(&config, "/chatter", Some(&qos)),
+            "/chatter"
+        );
+    }
+}
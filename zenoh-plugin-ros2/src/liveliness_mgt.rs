@@ -12,22 +12,38 @@
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
 
+use cyclors::dds_duration_t;
 use cyclors::qos::{
-    Durability, DurabilityKind, History, HistoryKind, Qos, Reliability, ReliabilityKind,
-    DDS_100MS_DURATION,
+    Deadline, Durability, DurabilityKind, History, HistoryKind, Lifespan, Liveliness,
+    LivelinessKind, Ownership, OwnershipKind, OwnershipStrength, Qos, Reliability, ReliabilityKind,
+    TimeBasedFilter, DDS_INFINITE_TIME,
 };
+use std::fmt;
 use zenoh::prelude::{keyexpr, OwnedKeyExpr};
 
 const SLASH_REPLACEMSNT_CHAR: &str = "§";
 
+// Version prefixes of the QoS key expression formats (see qos_to_key_expr())
+const QOS_KE_VERSION_1: &str = "v1";
+const QOS_KE_VERSION_2: &str = "v2";
+const QOS_KE_VERSION_3: &str = "v3";
+const QOS_KE_VERSION_4: &str = "v4";
+
 zenoh::kedefine!(
     // Liveliness tokens key expressions
     pub ke_liveliness_all: "@ros2_lv/${plugin_id:*}/${remaining:**}",
     pub ke_liveliness_plugin: "@ros2_lv/${plugin_id:*}",
     pub(crate) ke_liveliness_pub: "@ros2_lv/${plugin_id:*}/MP/${ke:*}/${typ:*}/${qos_ke:*}",
     pub(crate) ke_liveliness_sub: "@ros2_lv/${plugin_id:*}/MS/${ke:*}/${typ:*}/${qos_ke:*}",
+    pub(crate) ke_liveliness_service_srv: "@ros2_lv/${plugin_id:*}/SS/${ke:*}/${typ:*}",
+    pub(crate) ke_liveliness_service_cli: "@ros2_lv/${plugin_id:*}/SC/${ke:*}/${typ:*}",
+    pub(crate) ke_liveliness_action_srv: "@ros2_lv/${plugin_id:*}/AS/${ke:*}/${typ:*}",
+    pub(crate) ke_liveliness_action_cli: "@ros2_lv/${plugin_id:*}/AC/${ke:*}/${typ:*}",
 );
 
+// NOTE: no QoS in Services and Actions liveliness key expressions, since those always use the default QoS
+// (see ros2_service_default_qos(), ros2_action_status_default_qos() and ros2_topic_default_qos())
+
 pub(crate) fn new_ke_liveliness_pub(
     plugin_id: &keyexpr,
     zenoh_key_expr: &keyexpr,
@@ -44,26 +60,26 @@ pub(crate) fn new_ke_liveliness_pub(
 
 pub(crate) fn parse_ke_liveliness_pub(
     ke: &keyexpr,
-) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String, bool, Qos), String> {
-    let parsed = ke_liveliness_pub::parse(ke)
-        .map_err(|e| format!("failed to parse liveliness keyexpr {ke}: {e}"))?;
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String, bool, Qos), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_pub::parse(ke).map_err(|e| invalid(e.to_string()))?;
     let plugin_id = parsed
         .plugin_id()
         .map(ToOwned::to_owned)
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no plugin_id"))?;
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
     let zenoh_key_expr = parsed
         .ke()
         .map(|ke| unescape_slashes(ke))
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no ke"))?;
+        .ok_or_else(|| invalid("no ke".into()))?;
     let ros2_type = parsed
         .typ()
         .map(|ke| unescape_slashes(ke))
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no typ"))?;
-    let (keyless, qos) = parsed
-        .qos_ke()
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no typ"))
-        .and_then(|ke| key_expr_to_qos(ke))
-        .map_err(|e| format!("failed to parse liveliness keyexpr {ke}: {e}"))?;
+        .ok_or_else(|| invalid("no typ".into()))?;
+    let (keyless, qos) =
+        key_expr_to_qos(parsed.qos_ke().ok_or_else(|| invalid("no qos_ke".into()))?)?;
     Ok((
         plugin_id,
         zenoh_key_expr,
@@ -89,26 +105,26 @@ pub(crate) fn new_ke_liveliness_sub(
 
 pub(crate) fn parse_ke_liveliness_sub(
     ke: &keyexpr,
-) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String, bool, Qos), String> {
-    let parsed = ke_liveliness_sub::parse(ke)
-        .map_err(|e| format!("failed to parse liveliness keyexpr {ke}: {e}"))?;
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String, bool, Qos), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_sub::parse(ke).map_err(|e| invalid(e.to_string()))?;
     let plugin_id = parsed
         .plugin_id()
         .map(ToOwned::to_owned)
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no plugin_id"))?;
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
     let zenoh_key_expr = parsed
         .ke()
         .map(|ke| unescape_slashes(ke))
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no ke"))?;
+        .ok_or_else(|| invalid("no ke".into()))?;
     let ros2_type = parsed
         .typ()
         .map(|ke| unescape_slashes(ke))
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no typ"))?;
-    let (keyless, qos) = parsed
-        .qos_ke()
-        .ok_or_else(|| format!("failed to parse liveliness keyexpr {ke}: no typ"))
-        .and_then(|ke| key_expr_to_qos(ke))
-        .map_err(|e| format!("failed to parse liveliness keyexpr {ke}: {e}"))?;
+        .ok_or_else(|| invalid("no typ".into()))?;
+    let (keyless, qos) =
+        key_expr_to_qos(parsed.qos_ke().ok_or_else(|| invalid("no qos_ke".into()))?)?;
     Ok((
         plugin_id,
         zenoh_key_expr,
@@ -118,6 +134,142 @@ pub(crate) fn parse_ke_liveliness_sub(
     ))
 }
 
+pub(crate) fn new_ke_liveliness_service_srv(
+    plugin_id: &keyexpr,
+    zenoh_key_expr: &keyexpr,
+    ros2_type: &str,
+) -> Result<OwnedKeyExpr, String> {
+    let ke = escape_slashes(zenoh_key_expr);
+    let typ = escape_slashes(ros2_type);
+    zenoh::keformat!(ke_liveliness_service_srv::formatter(), plugin_id, ke, typ)
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn parse_ke_liveliness_service_srv(
+    ke: &keyexpr,
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_service_srv::parse(ke).map_err(|e| invalid(e.to_string()))?;
+    let plugin_id = parsed
+        .plugin_id()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
+    let zenoh_key_expr = parsed
+        .ke()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no ke".into()))?;
+    let ros2_type = parsed
+        .typ()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no typ".into()))?;
+    Ok((plugin_id, zenoh_key_expr, ros2_type.to_string()))
+}
+
+pub(crate) fn new_ke_liveliness_service_cli(
+    plugin_id: &keyexpr,
+    zenoh_key_expr: &keyexpr,
+    ros2_type: &str,
+) -> Result<OwnedKeyExpr, String> {
+    let ke = escape_slashes(zenoh_key_expr);
+    let typ = escape_slashes(ros2_type);
+    zenoh::keformat!(ke_liveliness_service_cli::formatter(), plugin_id, ke, typ)
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn parse_ke_liveliness_service_cli(
+    ke: &keyexpr,
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_service_cli::parse(ke).map_err(|e| invalid(e.to_string()))?;
+    let plugin_id = parsed
+        .plugin_id()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
+    let zenoh_key_expr = parsed
+        .ke()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no ke".into()))?;
+    let ros2_type = parsed
+        .typ()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no typ".into()))?;
+    Ok((plugin_id, zenoh_key_expr, ros2_type.to_string()))
+}
+
+pub(crate) fn new_ke_liveliness_action_srv(
+    plugin_id: &keyexpr,
+    zenoh_key_expr: &keyexpr,
+    ros2_type: &str,
+) -> Result<OwnedKeyExpr, String> {
+    let ke = escape_slashes(zenoh_key_expr);
+    let typ = escape_slashes(ros2_type);
+    zenoh::keformat!(ke_liveliness_action_srv::formatter(), plugin_id, ke, typ)
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn parse_ke_liveliness_action_srv(
+    ke: &keyexpr,
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_action_srv::parse(ke).map_err(|e| invalid(e.to_string()))?;
+    let plugin_id = parsed
+        .plugin_id()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
+    let zenoh_key_expr = parsed
+        .ke()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no ke".into()))?;
+    let ros2_type = parsed
+        .typ()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no typ".into()))?;
+    Ok((plugin_id, zenoh_key_expr, ros2_type.to_string()))
+}
+
+pub(crate) fn new_ke_liveliness_action_cli(
+    plugin_id: &keyexpr,
+    zenoh_key_expr: &keyexpr,
+    ros2_type: &str,
+) -> Result<OwnedKeyExpr, String> {
+    let ke = escape_slashes(zenoh_key_expr);
+    let typ = escape_slashes(ros2_type);
+    zenoh::keformat!(ke_liveliness_action_cli::formatter(), plugin_id, ke, typ)
+        .map_err(|e| e.to_string())
+}
+
+pub(crate) fn parse_ke_liveliness_action_cli(
+    ke: &keyexpr,
+) -> Result<(OwnedKeyExpr, OwnedKeyExpr, String), LivelinessParseError> {
+    let invalid = |reason: String| LivelinessParseError::InvalidKeyExpr {
+        ke: ke.to_string(),
+        reason,
+    };
+    let parsed = ke_liveliness_action_cli::parse(ke).map_err(|e| invalid(e.to_string()))?;
+    let plugin_id = parsed
+        .plugin_id()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| invalid("no plugin_id".into()))?;
+    let zenoh_key_expr = parsed
+        .ke()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no ke".into()))?;
+    let ros2_type = parsed
+        .typ()
+        .map(|ke| unescape_slashes(ke))
+        .ok_or_else(|| invalid("no typ".into()))?;
+    Ok((plugin_id, zenoh_key_expr, ros2_type.to_string()))
+}
+
 fn escape_slashes(s: &str) -> OwnedKeyExpr {
     OwnedKeyExpr::try_from(s.replace('/', SLASH_REPLACEMSNT_CHAR)).unwrap()
 }
@@ -126,22 +278,152 @@ fn unescape_slashes(ke: &keyexpr) -> OwnedKeyExpr {
     OwnedKeyExpr::try_from(ke.as_str().replace(SLASH_REPLACEMSNT_CHAR, "/")).unwrap()
 }
 
+// Error returned when parsing a QoS serialized as KeyExpr (see key_expr_to_qos())
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QosParseError {
+    // the version of the format is not supported by this bridge (likely a more recent version)
+    UnsupportedVersion {
+        qos_ke: String,
+        version: String,
+    },
+    // the number of elements separated by ':' is not the one expected for the format version
+    UnexpectedElementsCount {
+        qos_ke: String,
+        expected: usize,
+        found: usize,
+    },
+    // an element failed to be parsed
+    InvalidElement {
+        qos_ke: String,
+        policy: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for QosParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QosParseError::UnsupportedVersion { qos_ke, version } => write!(
+                f,
+                "unexpected QoS expression: '{qos_ke}' - unsupported format version '{version}'"
+            ),
+            QosParseError::UnexpectedElementsCount {
+                qos_ke,
+                expected,
+                found,
+            } => write!(
+                f,
+                "unexpected QoS expression: '{qos_ke}' - {expected} elements between : were expected, found {found}"
+            ),
+            QosParseError::InvalidElement {
+                qos_ke,
+                policy,
+                value,
+            } => write!(
+                f,
+                "unexpected QoS expression: '{qos_ke}' - failed to parse {policy} in '{value}'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QosParseError {}
+
+// Error returned when parsing the liveliness key expression of a route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LivelinessParseError {
+    // the key expression doesn't have the expected format
+    InvalidKeyExpr { ke: String, reason: String },
+    // the QoS part of the key expression failed to be parsed
+    InvalidQos(QosParseError),
+}
+
+impl From<QosParseError> for LivelinessParseError {
+    fn from(e: QosParseError) -> Self {
+        LivelinessParseError::InvalidQos(e)
+    }
+}
+
+impl fmt::Display for LivelinessParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LivelinessParseError::InvalidKeyExpr { ke, reason } => {
+                write!(f, "failed to parse liveliness keyexpr {ke}: {reason}")
+            }
+            LivelinessParseError::InvalidQos(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for LivelinessParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LivelinessParseError::InvalidKeyExpr { .. } => None,
+            LivelinessParseError::InvalidQos(e) => Some(e),
+        }
+    }
+}
+
 // Serialize QoS as a KeyExpr-compatible string (for usage in liveliness keyexpr)
 // NOTE: only significant Qos for ROS2 are serialized
 // See https://docs.ros.org/en/rolling/Concepts/Intermediate/About-Quality-of-Service-Settings.html
 //
-// format: "<keyless>:<ReliabilityKind>:<DurabilityKind>:<HistoryKid>,<HistoryDepth>"
-// where each element is "" if default QoS, or an integer in case of enum, and 'K' for !keyless
+// Five versions of the format exist, where each element is "" if default QoS, or an integer in case of enum,
+// and 'K' for !keyless. The durations are in nanoseconds, or "inf" for infinite duration.
+//  - v0: "<keyless>:<ReliabilityKind>:<DurabilityKind>:<HistoryKid>,<HistoryDepth>"
+//  - v1: "v1:<keyless>:<ReliabilityKind>,<MaxBlockingTime>:<DurabilityKind>:<HistoryKid>,<HistoryDepth>:<DeadlinePeriod>:<LifespanDuration>:<LivelinessKind>,<LeaseDuration>"
+//  - v2: same than v1 with "v2" prefix, and an additional ":<OwnershipKind>,<OwnershipStrength>" element
+//  - v3: same than v2 with "v3" prefix, and an additional ":<Partitions>" element, where the Partitions are separated by ','
+//  - v4: same than v3 with "v4" prefix, and an additional ":<MinimumSeparation>" element (the TimeBasedFilter's duration)
+// The oldest format is still used when sufficient (i.e. v0 for default Deadline, Lifespan, Liveliness and
+// max_blocking_time QoS, v1 for default Ownership QoS, v2 for default Partition QoS, v3 for default TimeBasedFilter QoS),
+// allowing older bridges to parse those liveliness tokens. The default max_blocking_time is the one set by
+// rmw_cyclonedds (see ros2_default_max_blocking_time()), so the tokens of most ROS 2 entities use the v0 format.
 pub fn qos_to_key_expr(keyless: bool, qos: &Qos) -> OwnedKeyExpr {
     use std::io::Write;
     let mut w: Vec<u8> = Vec::new();
 
+    let version = if qos.time_based_filter.is_some() {
+        4
+    } else if qos.partition.is_some() {
+        3
+    } else if qos.ownership.is_some() || qos.ownership_strength.is_some() {
+        2
+    } else if qos.deadline.is_some()
+        || qos.lifespan.is_some()
+        || qos.liveliness.is_some()
+        || qos
+            .reliability
+            .as_ref()
+            .map(|r| r.max_blocking_time != ros2_default_max_blocking_time(r.kind))
+            .unwrap_or(false)
+    {
+        1
+    } else {
+        0
+    };
+    let is_v0 = version == 0;
+
+    match version {
+        1 => write!(w, "{QOS_KE_VERSION_1}:").unwrap(),
+        2 => write!(w, "{QOS_KE_VERSION_2}:").unwrap(),
+        3 => write!(w, "{QOS_KE_VERSION_3}:").unwrap(),
+        4 => write!(w, "{QOS_KE_VERSION_4}:").unwrap(),
+        _ => (),
+    }
     if !keyless {
         write!(w, "K").unwrap();
     }
     write!(w, ":").unwrap();
-    if let Some(Reliability { kind, .. }) = &qos.reliability {
+    if let Some(Reliability {
+        kind,
+        max_blocking_time,
+    }) = &qos.reliability
+    {
         write!(&mut w, "{}", *kind as isize).unwrap();
+        if !is_v0 {
+            write!(&mut w, ",{}", duration_to_str(*max_blocking_time)).unwrap();
+        }
     }
     write!(w, ":").unwrap();
     if let Some(Durability { kind }) = &qos.durability {
@@ -152,50 +434,259 @@ pub fn qos_to_key_expr(keyless: bool, qos: &Qos) -> OwnedKeyExpr {
         write!(&mut w, "{},{}", *kind as isize, depth).unwrap();
     }
 
+    if !is_v0 {
+        write!(w, ":").unwrap();
+        if let Some(Deadline { period }) = &qos.deadline {
+            write!(&mut w, "{}", duration_to_str(*period)).unwrap();
+        }
+        write!(w, ":").unwrap();
+        if let Some(Lifespan { duration }) = &qos.lifespan {
+            write!(&mut w, "{}", duration_to_str(*duration)).unwrap();
+        }
+        write!(w, ":").unwrap();
+        if let Some(Liveliness {
+            kind,
+            lease_duration,
+        }) = &qos.liveliness
+        {
+            write!(
+                &mut w,
+                "{},{}",
+                *kind as isize,
+                duration_to_str(*lease_duration)
+            )
+            .unwrap();
+        }
+    }
+
+    if version >= 2 {
+        write!(w, ":").unwrap();
+        if let Some(Ownership { kind }) = &qos.ownership {
+            write!(&mut w, "{}", *kind as isize).unwrap();
+        }
+        write!(w, ",").unwrap();
+        if let Some(OwnershipStrength { value }) = &qos.ownership_strength {
+            write!(&mut w, "{value}").unwrap();
+        }
+    }
+
+    if version >= 3 {
+        write!(w, ":").unwrap();
+        if let Some(partition) = &qos.partition {
+            write!(&mut w, "{}", partition.join(",")).unwrap();
+        }
+    }
+
+    if version >= 4 {
+        write!(w, ":").unwrap();
+        if let Some(TimeBasedFilter { minimum_separation }) = &qos.time_based_filter {
+            write!(&mut w, "{}", duration_to_str(*minimum_separation)).unwrap();
+        }
+    }
+
     unsafe {
         let s: String = String::from_utf8_unchecked(w);
         OwnedKeyExpr::from_string_unchecked(s)
     }
 }
 
-fn key_expr_to_qos(ke: &keyexpr) -> Result<(bool, Qos), String> {
+fn key_expr_to_qos(ke: &keyexpr) -> Result<(bool, Qos), QosParseError> {
     let elts: Vec<&str> = ke.split(':').collect();
-    if elts.len() != 4 {
-        return Err(format!("Internal Error: unexpected QoS expression: '{ke}' - 4 elements between : were expected"));
+    let (version, elts) = match elts[0] {
+        QOS_KE_VERSION_1 => (1, &elts[1..]),
+        QOS_KE_VERSION_2 => (2, &elts[1..]),
+        QOS_KE_VERSION_3 => (3, &elts[1..]),
+        QOS_KE_VERSION_4 => (4, &elts[1..]),
+        v if v.starts_with('v') => {
+            return Err(QosParseError::UnsupportedVersion {
+                qos_ke: ke.to_string(),
+                version: v.to_string(),
+            })
+        }
+        _ => (0, &elts[..]),
+    };
+    let is_v0 = version == 0;
+    let expected = match version {
+        0 => 4,
+        1 => 7,
+        2 => 8,
+        3 => 9,
+        _ => 10,
+    };
+    if elts.len() != expected {
+        return Err(QosParseError::UnexpectedElementsCount {
+            qos_ke: ke.to_string(),
+            expected,
+            found: elts.len(),
+        });
     }
+    let invalid = |policy: &'static str, value: &str| QosParseError::InvalidElement {
+        qos_ke: ke.to_string(),
+        policy,
+        value: value.to_string(),
+    };
+
     let mut qos = Qos::default();
     let keyless = elts[0].is_empty();
     if !elts[1].is_empty() {
-        match elts[1].parse::<cyclors::dds_reliability_kind_t>() {
-            Ok(i) => qos.reliability = Some(Reliability {kind: ReliabilityKind::from(&i), max_blocking_time: DDS_100MS_DURATION }),
-            Err(_) => return Err(format!("Internal Error: unexpected QoS expression: '{ke}' - failed to parse Reliability in 2nd element")),
+        // in v0 format, max_blocking_time is not serialized: use the ROS 2 default value
+        let (kind, max_blocking_time) = match elts[1].split_once(',') {
+            Some((k, mbt)) if !is_v0 => (k, parse_duration(mbt)),
+            None if is_v0 => (elts[1], None),
+            _ => return Err(invalid("Reliability", elts[1])),
+        };
+        match kind.parse::<cyclors::dds_reliability_kind_t>() {
+            Ok(i) => {
+                let kind = ReliabilityKind::from(&i);
+                let max_blocking_time = match max_blocking_time {
+                    Some(max_blocking_time) => max_blocking_time,
+                    None if is_v0 => ros2_default_max_blocking_time(kind),
+                    None => return Err(invalid("Reliability", elts[1])),
+                };
+                qos.reliability = Some(Reliability {
+                    kind,
+                    max_blocking_time,
+                })
+            }
+            Err(_) => return Err(invalid("Reliability", elts[1])),
         }
     }
     if !elts[2].is_empty() {
         match elts[2].parse::<cyclors::dds_durability_kind_t>() {
-            Ok(i) => qos.durability = Some(Durability {kind: DurabilityKind::from(&i)}),
-            Err(_) => return Err(format!("Internal Error: unexpected QoS expression: '{ke}' - failed to parse Durability in 3d element")),
+            Ok(i) => {
+                qos.durability = Some(Durability {
+                    kind: DurabilityKind::from(&i),
+                })
+            }
+            Err(_) => return Err(invalid("Durability", elts[2])),
         }
     }
     if !elts[3].is_empty() {
-        match elts[3].split_once(',').map(|(s1, s2)|
+        match elts[3]
+            .split_once(',')
+            .map(|(s1, s2)| (s1.parse::<cyclors::dds_history_kind_t>(), s2.parse::<i32>()))
+        {
+            Some((Ok(k), Ok(depth))) => {
+                qos.history = Some(History {
+                    kind: HistoryKind::from(&k),
+                    depth,
+                })
+            }
+            _ => return Err(invalid("History", elts[3])),
+        }
+    }
+    if is_v0 {
+        return Ok((keyless, qos));
+    }
+
+    if !elts[4].is_empty() {
+        match parse_duration(elts[4]) {
+            Some(period) => qos.deadline = Some(Deadline { period }),
+            None => return Err(invalid("Deadline", elts[4])),
+        }
+    }
+    if !elts[5].is_empty() {
+        match parse_duration(elts[5]) {
+            Some(duration) => qos.lifespan = Some(Lifespan { duration }),
+            None => return Err(invalid("Lifespan", elts[5])),
+        }
+    }
+    if !elts[6].is_empty() {
+        match elts[6].split_once(',').map(|(s1, s2)| {
             (
-                s1.parse::<cyclors::dds_history_kind_t>(),
-                s2.parse::<i32>(),
+                s1.parse::<cyclors::dds_liveliness_kind_t>(),
+                parse_duration(s2),
             )
-        ) {
-            Some((Ok(k), Ok(depth))) => qos.history = Some(History {kind: HistoryKind::from(&k), depth }),
-            _ => return Err(format!("Internal Error: unexpected QoS expression: '{ke}' - failed to parse History in 4th element")),
+        }) {
+            Some((Ok(k), Some(lease_duration))) => {
+                qos.liveliness = Some(Liveliness {
+                    kind: LivelinessKind::from(&k),
+                    lease_duration,
+                })
+            }
+            _ => return Err(invalid("Liveliness", elts[6])),
+        }
+    }
+    if version == 1 {
+        return Ok((keyless, qos));
+    }
+
+    match elts[7].split_once(',') {
+        Some((kind, strength)) => {
+            if !kind.is_empty() {
+                match kind.parse::<cyclors::dds_ownership_kind_t>() {
+                    Ok(k) => {
+                        qos.ownership = Some(Ownership {
+                            kind: OwnershipKind::from(&k),
+                        })
+                    }
+                    Err(_) => return Err(invalid("Ownership", elts[7])),
+                }
+            }
+            if !strength.is_empty() {
+                match strength.parse::<i32>() {
+                    Ok(value) => qos.ownership_strength = Some(OwnershipStrength { value }),
+                    Err(_) => return Err(invalid("OwnershipStrength", elts[7])),
+                }
+            }
+        }
+        None => return Err(invalid("Ownership", elts[7])),
+    }
+    if version == 2 {
+        return Ok((keyless, qos));
+    }
+
+    if !elts[8].is_empty() {
+        qos.partition = Some(elts[8].split(',').map(String::from).collect());
+    }
+    if version == 3 {
+        return Ok((keyless, qos));
+    }
+
+    if !elts[9].is_empty() {
+        match parse_duration(elts[9]) {
+            Some(minimum_separation) => {
+                qos.time_based_filter = Some(TimeBasedFilter { minimum_separation })
+            }
+            None => return Err(invalid("TimeBasedFilter", elts[9])),
         }
     }
 
     Ok((keyless, qos))
 }
 
+// The Reliability's max_blocking_time set by rmw_cyclonedds for ROS 2 entities: infinite for RELIABLE,
+// and 0 for BEST_EFFORT (where it's not used)
+fn ros2_default_max_blocking_time(kind: ReliabilityKind) -> dds_duration_t {
+    if kind == ReliabilityKind::RELIABLE {
+        DDS_INFINITE_TIME
+    } else {
+        0
+    }
+}
+
+fn duration_to_str(d: dds_duration_t) -> String {
+    if d == DDS_INFINITE_TIME {
+        "inf".into()
+    } else {
+        d.to_string()
+    }
+}
+
+fn parse_duration(s: &str) -> Option<dds_duration_t> {
+    if s == "inf" {
+        Some(DDS_INFINITE_TIME)
+    } else {
+        s.parse().ok()
+    }
+}
+
 mod tests {
     #[test]
     fn test_qos_key_expr() {
         use super::*;
+        use crate::ke_for_sure;
+        use cyclors::qos::DDS_100MS_DURATION;
 
         let mut q = Qos::default();
         assert_eq!(qos_to_key_expr(true, &q).to_string(), ":::");
@@ -209,9 +700,10 @@ mod tests {
             Ok((false, q.clone()))
         );
 
+        // v0 format with the max_blocking_time set by rmw_cyclonedds
         q.reliability = Some(Reliability {
             kind: ReliabilityKind::RELIABLE,
-            max_blocking_time: DDS_100MS_DURATION,
+            max_blocking_time: DDS_INFINITE_TIME,
         });
         assert_eq!(
             qos_to_key_expr(true, &q).to_string(),
@@ -221,6 +713,14 @@ mod tests {
             key_expr_to_qos(&qos_to_key_expr(true, &q)),
             Ok((true, q.clone()))
         );
+        q.reliability = Some(Reliability {
+            kind: ReliabilityKind::BEST_EFFORT,
+            max_blocking_time: 0,
+        });
+        assert_eq!(
+            qos_to_key_expr(true, &q).to_string(),
+            format!(":{}::", ReliabilityKind::BEST_EFFORT as u8)
+        );
         assert_eq!(
             key_expr_to_qos(&qos_to_key_expr(true, &q)),
             Ok((true, q.clone()))
@@ -253,5 +753,271 @@ mod tests {
             Ok((true, q.clone()))
         );
         q.reliability = None;
+
+        // v1 format, when some QoS are not serializable in v0 format
+        q.history = None;
+        q.reliability = Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: DDS_100MS_DURATION,
+        });
+        assert_eq!(
+            qos_to_key_expr(true, &q).to_string(),
+            format!("v1::{},100000000:::::", ReliabilityKind::RELIABLE as u8)
+        );
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(true, &q)),
+            Ok((true, q.clone()))
+        );
+        q.reliability = Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: DDS_INFINITE_TIME,
+        });
+        q.deadline = Some(Deadline {
+            period: 100_000_000,
+        });
+        q.lifespan = Some(Lifespan {
+            duration: 2_000_000_000,
+        });
+        q.liveliness = Some(Liveliness {
+            kind: LivelinessKind::MANUAL_BY_TOPIC,
+            lease_duration: DDS_INFINITE_TIME,
+        });
+        assert_eq!(
+            qos_to_key_expr(false, &q).to_string(),
+            format!(
+                "v1:K:{},inf:::100000000:2000000000:{},inf",
+                ReliabilityKind::RELIABLE as u8,
+                LivelinessKind::MANUAL_BY_TOPIC as u8
+            )
+        );
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(false, &q)),
+            Ok((false, q.clone()))
+        );
+
+        // v2 format, when Ownership QoS is set
+        q.ownership = Some(Ownership {
+            kind: OwnershipKind::EXCLUSIVE,
+        });
+        q.ownership_strength = Some(OwnershipStrength { value: 42 });
+        assert_eq!(
+            qos_to_key_expr(false, &q).to_string(),
+            format!(
+                "v2:K:{},inf:::100000000:2000000000:{},inf:{},42",
+                ReliabilityKind::RELIABLE as u8,
+                LivelinessKind::MANUAL_BY_TOPIC as u8,
+                OwnershipKind::EXCLUSIVE as u8
+            )
+        );
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(false, &q)),
+            Ok((false, q.clone()))
+        );
+        q = Qos::default();
+        q.ownership_strength = Some(OwnershipStrength { value: -1 });
+        assert_eq!(qos_to_key_expr(true, &q).to_string(), "v2::::::::,-1");
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(true, &q)),
+            Ok((true, q.clone()))
+        );
+
+        // v3 format, when Partition QoS is set
+        q = Qos::default();
+        q.partition = Some(vec!["robot_1".into(), "robots".into()]);
+        assert_eq!(
+            qos_to_key_expr(true, &q).to_string(),
+            "v3::::::::,:robot_1,robots"
+        );
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(true, &q)),
+            Ok((true, q.clone()))
+        );
+
+        // v4 format, when TimeBasedFilter QoS is set
+        q = Qos::default();
+        q.time_based_filter = Some(TimeBasedFilter {
+            minimum_separation: 500_000_000,
+        });
+        assert_eq!(
+            qos_to_key_expr(true, &q).to_string(),
+            "v4::::::::,::500000000"
+        );
+        assert_eq!(
+            key_expr_to_qos(&qos_to_key_expr(true, &q)),
+            Ok((true, q.clone()))
+        );
+
+        // errors
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v9:K:::")),
+            Err(QosParseError::UnsupportedVersion { .. })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v1:K:::")),
+            Err(QosParseError::UnexpectedElementsCount {
+                expected: 7,
+                found: 4,
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v3::::::::,:robot_1:")),
+            Err(QosParseError::UnexpectedElementsCount {
+                expected: 9,
+                found: 10,
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v2::::::::,:robot_1:")),
+            Err(QosParseError::UnexpectedElementsCount {
+                expected: 8,
+                found: 10,
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!(":1,inf::")),
+            Err(QosParseError::InvalidElement {
+                policy: "Reliability",
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v1:::::x::")),
+            Err(QosParseError::InvalidElement {
+                policy: "Deadline",
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v2::::::::1")),
+            Err(QosParseError::InvalidElement {
+                policy: "Ownership",
+                ..
+            })
+        ));
+        assert!(matches!(
+            key_expr_to_qos(ke_for_sure!("v4::::::::,::x")),
+            Err(QosParseError::InvalidElement {
+                policy: "TimeBasedFilter",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_liveliness_key_exprs() {
+        use super::*;
+        use crate::ke_for_sure;
+
+        let plugin_id = ke_for_sure!("my_bridge");
+        let zenoh_key_expr = ke_for_sure!("ns/my_service");
+        let ros2_type = "example_interfaces/srv/AddTwoInts";
+
+        let ke = new_ke_liveliness_service_srv(plugin_id, zenoh_key_expr, ros2_type).unwrap();
+        assert_eq!(
+            ke.as_str(),
+            "@ros2_lv/my_bridge/SS/ns§my_service/example_interfaces§srv§AddTwoInts"
+        );
+        assert_eq!(
+            parse_ke_liveliness_service_srv(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string()
+            ))
+        );
+
+        let ke = new_ke_liveliness_service_cli(plugin_id, zenoh_key_expr, ros2_type).unwrap();
+        assert_eq!(
+            ke.as_str(),
+            "@ros2_lv/my_bridge/SC/ns§my_service/example_interfaces§srv§AddTwoInts"
+        );
+        assert_eq!(
+            parse_ke_liveliness_service_cli(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string()
+            ))
+        );
+        assert!(matches!(
+            parse_ke_liveliness_service_srv(&ke),
+            Err(LivelinessParseError::InvalidKeyExpr { .. })
+        ));
+
+        let zenoh_key_expr = ke_for_sure!("fibonacci");
+        let ros2_type = "example_interfaces/action/Fibonacci";
+
+        let ke = new_ke_liveliness_action_srv(plugin_id, zenoh_key_expr, ros2_type).unwrap();
+        assert_eq!(
+            ke.as_str(),
+            "@ros2_lv/my_bridge/AS/fibonacci/example_interfaces§action§Fibonacci"
+        );
+        assert_eq!(
+            parse_ke_liveliness_action_srv(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string()
+            ))
+        );
+
+        let ke = new_ke_liveliness_action_cli(plugin_id, zenoh_key_expr, ros2_type).unwrap();
+        assert_eq!(
+            ke.as_str(),
+            "@ros2_lv/my_bridge/AC/fibonacci/example_interfaces§action§Fibonacci"
+        );
+        assert_eq!(
+            parse_ke_liveliness_action_cli(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string()
+            ))
+        );
+        assert!(matches!(
+            parse_ke_liveliness_action_srv(&ke),
+            Err(LivelinessParseError::InvalidKeyExpr { .. })
+        ));
+
+        let zenoh_key_expr = ke_for_sure!("chatter");
+        let ros2_type = "std_msgs/msg/String";
+        let qos = Qos::default();
+        let ke = new_ke_liveliness_pub(plugin_id, zenoh_key_expr, ros2_type, true, &qos).unwrap();
+        assert_eq!(
+            parse_ke_liveliness_pub(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string(),
+                true,
+                qos.clone()
+            ))
+        );
+        assert!(matches!(
+            parse_ke_liveliness_sub(&ke),
+            Err(LivelinessParseError::InvalidKeyExpr { .. })
+        ));
+        let ke = new_ke_liveliness_sub(plugin_id, zenoh_key_expr, ros2_type, false, &qos).unwrap();
+        assert_eq!(
+            parse_ke_liveliness_sub(&ke),
+            Ok((
+                plugin_id.to_owned(),
+                zenoh_key_expr.to_owned(),
+                ros2_type.to_string(),
+                false,
+                qos
+            ))
+        );
+        assert!(matches!(
+            parse_ke_liveliness_pub(ke_for_sure!(
+                "@ros2_lv/my_bridge/MP/chatter/std_msgs§msg§String/v9:K:::"
+            )),
+            Err(LivelinessParseError::InvalidQos(
+                QosParseError::UnsupportedVersion { .. }
+            ))
+        ));
     }
 }
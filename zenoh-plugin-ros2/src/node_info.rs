@@ -1709,6 +1709,34 @@ impl NodeInfo {
     }
 
     //
+    // Return a DiscoveredX event for each complete interface of this node
+    pub fn get_discovered_interfaces(&self) -> Vec<ROS2DiscoveryEvent> {
+        use ROS2DiscoveryEvent::*;
+        let node_fullname = self.fullname().to_string();
+        let mut events = Vec::new();
+
+        for v in self.msg_pub.values() {
+            events.push(DiscoveredMsgPub(node_fullname.clone(), v.clone()))
+        }
+        for v in self.msg_sub.values() {
+            events.push(DiscoveredMsgSub(node_fullname.clone(), v.clone()))
+        }
+        for v in self.service_srv.values().filter(|v| v.is_complete()) {
+            events.push(DiscoveredServiceSrv(node_fullname.clone(), v.clone()))
+        }
+        for v in self.service_cli.values().filter(|v| v.is_complete()) {
+            events.push(DiscoveredServiceCli(node_fullname.clone(), v.clone()))
+        }
+        for v in self.action_srv.values().filter(|v| v.is_complete()) {
+            events.push(DiscoveredActionSrv(node_fullname.clone(), v.clone()))
+        }
+        for v in self.action_cli.values().filter(|v| v.is_complete()) {
+            events.push(DiscoveredActionCli(node_fullname.clone(), v.clone()))
+        }
+
+        events
+    }
+
     pub fn remove_all_entities(&mut self) -> Vec<ROS2DiscoveryEvent> {
         use ROS2DiscoveryEvent::*;
         let node_fullname = self.fullname().to_string();
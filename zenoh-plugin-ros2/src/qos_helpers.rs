@@ -11,7 +11,69 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use cyclors::{qos::*, DDS_LENGTH_UNLIMITED};
+use crate::config::{Config, DurabilityOverride, HistoryOverride, ReliabilityOverride};
+use cyclors::{dds_duration_t, qos::*, DDS_LENGTH_UNLIMITED};
+use std::time::Duration;
+
+// Default QoS for ROS2 Services (rmw_qos_profile_services_default), see:
+// https://github.com/ros2/rmw/blob/83445be486deae8c78d275e092eafb4bf380bd49/rmw/include/rmw/qos_profiles.h#L64
+pub fn ros2_service_default_qos() -> Qos {
+    let mut qos = Qos::default();
+    qos.history = Some(History {
+        kind: HistoryKind::KEEP_LAST,
+        depth: 10,
+    });
+    qos.reliability = Some(Reliability {
+        kind: ReliabilityKind::RELIABLE,
+        max_blocking_time: DDS_INFINITE_TIME,
+    });
+    // Don't match with entities of the same participant
+    qos.ignore_local = Some(IgnoreLocal {
+        kind: IgnoreLocalKind::PARTICIPANT,
+    });
+    qos
+}
+
+// Default QoS for ROS2 Action Status topic (rcl_action_qos_profile_status_default), see:
+// https://github.com/ros2/rcl/blob/8f7f4f0804a34ee9d9ecd2d7e75a57ce2b7ced5d/rcl_action/include/rcl_action/default_qos.h#L30
+pub fn ros2_action_status_default_qos() -> Qos {
+    let mut qos = Qos::default();
+    qos.history = Some(History {
+        kind: HistoryKind::KEEP_LAST,
+        depth: 1,
+    });
+    qos.reliability = Some(Reliability {
+        kind: ReliabilityKind::RELIABLE,
+        max_blocking_time: DDS_INFINITE_TIME,
+    });
+    qos.durability = Some(Durability {
+        kind: DurabilityKind::TRANSIENT_LOCAL,
+    });
+    // Don't match with entities of the same participant
+    qos.ignore_local = Some(IgnoreLocal {
+        kind: IgnoreLocalKind::PARTICIPANT,
+    });
+    qos
+}
+
+// Default QoS for ROS2 topics (rmw_qos_profile_default), used for Action Feedback topic, see:
+// https://github.com/ros2/rmw/blob/83445be486deae8c78d275e092eafb4bf380bd49/rmw/include/rmw/qos_profiles.h#L51
+pub fn ros2_topic_default_qos() -> Qos {
+    let mut qos = Qos::default();
+    qos.history = Some(History {
+        kind: HistoryKind::KEEP_LAST,
+        depth: 10,
+    });
+    qos.reliability = Some(Reliability {
+        kind: ReliabilityKind::RELIABLE,
+        max_blocking_time: DDS_INFINITE_TIME,
+    });
+    // Don't match with entities of the same participant
+    qos.ignore_local = Some(IgnoreLocal {
+        kind: IgnoreLocalKind::PARTICIPANT,
+    });
+    qos
+}
 
 pub fn get_history_or_default(qos: &Qos) -> History {
     match &qos.history {
@@ -20,13 +82,6 @@ pub fn get_history_or_default(qos: &Qos) -> History {
     }
 }
 
-pub fn get_durability_service_or_default(qos: &Qos) -> DurabilityService {
-    match &qos.durability_service {
-        None => DurabilityService::default(),
-        Some(durability_service) => durability_service.clone(),
-    }
-}
-
 pub fn partition_is_empty(partition: &Option<Vec<String>>) -> bool {
     partition
         .as_ref()
@@ -39,6 +94,22 @@ pub fn partition_contains(partition: &Option<Vec<String>>, name: &String) -> boo
         .map_or(false, |partition| partition.contains(name))
 }
 
+// If "partitions_as_key_prefix" is enabled, return the DDS partition to be used as key expression prefix
+// for the routing of an entity with this QoS: its first partition if it's usable as a key expression chunk
+pub fn get_partition_prefix<'a>(config: &Config, qos: &'a Qos) -> Option<&'a str> {
+    if !config.partitions_as_key_prefix || partition_is_empty(&qos.partition) {
+        return None;
+    }
+    qos.partition
+        .as_ref()
+        .and_then(|partition| partition.first())
+        .map(String::as_str)
+        .filter(|p| {
+            // also exclude the separators used in the liveliness tokens' QoS (see qos_to_key_expr())
+            !p.is_empty() && !p.contains(['/', '*', '$', '?', '#', ':', ','])
+        })
+}
+
 pub fn is_writer_reliable(reliability: &Option<Reliability>) -> bool {
     reliability.as_ref().map_or(true, |reliability| {
         reliability.kind == ReliabilityKind::RELIABLE
@@ -57,6 +128,216 @@ pub fn is_transient_local(qos: &Qos) -> bool {
     })
 }
 
+// Return the ownership strength of a Writer if its Ownership QoS is EXCLUSIVE, or None if SHARED
+pub fn get_exclusive_ownership_strength(qos: &Qos) -> Option<i32> {
+    match &qos.ownership {
+        Some(Ownership {
+            kind: OwnershipKind::EXCLUSIVE,
+        }) => Some(qos.ownership_strength.as_ref().map_or(0, |s| s.value)),
+        _ => None,
+    }
+}
+
+// Return the reason why a DDS Reader and a DDS Writer can't match because of incompatible
+// Reliability or Durability QoS (i.e. the Reader requests more than the Writer offers), or None if compatible
+pub fn get_qos_mismatch(reader_qos: &Qos, writer_qos: &Qos) -> Option<String> {
+    if is_reader_reliable(&reader_qos.reliability) && !is_writer_reliable(&writer_qos.reliability) {
+        return Some("RELIABLE Reader can't match a BEST_EFFORT Writer".into());
+    }
+    let reader_durability = get_durability_kind_or_default(reader_qos);
+    let writer_durability = get_durability_kind_or_default(writer_qos);
+    if reader_durability as isize > writer_durability as isize {
+        return Some(format!(
+            "{reader_durability:?} Reader can't match a {writer_durability:?} Writer"
+        ));
+    }
+    None
+}
+
+// Copy and adapt a Reader's QoS to match a Writer with lower Reliability or Durability QoS
+pub fn downgrade_reader_qos_for_writer(reader_qos: &Qos, writer_qos: &Qos) -> Qos {
+    let mut qos = reader_qos.clone();
+    if !is_writer_reliable(&writer_qos.reliability) {
+        qos.reliability = Some(Reliability {
+            kind: ReliabilityKind::BEST_EFFORT,
+            max_blocking_time: DDS_100MS_DURATION,
+        });
+    }
+    let writer_durability = get_durability_kind_or_default(writer_qos);
+    if get_durability_kind_or_default(reader_qos) as isize > writer_durability as isize {
+        qos.durability = Some(Durability {
+            kind: writer_durability,
+        });
+    }
+    qos
+}
+
+// Copy and adapt a Writer's QoS to match a Reader with higher Reliability or Durability QoS
+pub fn upgrade_writer_qos_for_reader(writer_qos: &Qos, reader_qos: &Qos) -> Qos {
+    let mut qos = writer_qos.clone();
+    if is_reader_reliable(&reader_qos.reliability) && !is_writer_reliable(&writer_qos.reliability) {
+        qos.reliability = Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: DDS_100MS_DURATION.saturating_add(1),
+        });
+    }
+    let reader_durability = get_durability_kind_or_default(reader_qos);
+    if reader_durability as isize > get_durability_kind_or_default(writer_qos) as isize {
+        qos.durability = Some(Durability {
+            kind: reader_durability,
+        });
+        // as in adapt_reader_qos_for_writer(), configure durability_service QoS for TRANSIENT_LOCAL
+        if qos.durability_service.is_none() {
+            qos.durability_service = Some(durability_service_for_history(&get_history_or_default(
+                reader_qos,
+            )));
+        }
+    }
+    qos
+}
+
+// Return a copy of a Writer's QoS upgraded to be at least as demanding as another QoS regarding
+// Reliability, Durability, History and Ownership (i.e. EXCLUSIVE with highest strength),
+// or None if the Writer's QoS is already as demanding
+pub fn get_most_demanding_writer_qos(writer_qos: &Qos, other_qos: &Qos) -> Option<Qos> {
+    let mut qos = writer_qos.clone();
+    let mut upgraded = false;
+    if is_writer_reliable(&other_qos.reliability) && !is_writer_reliable(&writer_qos.reliability) {
+        qos.reliability = Some(Reliability {
+            kind: ReliabilityKind::RELIABLE,
+            max_blocking_time: DDS_100MS_DURATION.saturating_add(1),
+        });
+        upgraded = true;
+    }
+    let other_durability = get_durability_kind_or_default(other_qos);
+    if other_durability as isize > get_durability_kind_or_default(writer_qos) as isize {
+        qos.durability = Some(Durability {
+            kind: other_durability,
+        });
+        upgraded = true;
+    }
+    let history = get_history_or_default(writer_qos);
+    let other_history = get_history_or_default(other_qos);
+    if history.kind == HistoryKind::KEEP_LAST
+        && (other_history.kind == HistoryKind::KEEP_ALL || other_history.depth > history.depth)
+    {
+        qos.history = Some(other_history);
+        upgraded = true;
+    }
+    if let Some(other_strength) = get_exclusive_ownership_strength(other_qos) {
+        match get_exclusive_ownership_strength(writer_qos) {
+            Some(strength) if strength >= other_strength => (),
+            _ => {
+                qos.ownership = Some(Ownership {
+                    kind: OwnershipKind::EXCLUSIVE,
+                });
+                qos.ownership_strength = Some(OwnershipStrength {
+                    value: other_strength,
+                });
+                upgraded = true;
+            }
+        }
+    }
+    if !upgraded {
+        return None;
+    }
+    // as in adapt_reader_qos_for_writer(), configure durability_service QoS with same history for TRANSIENT_LOCAL
+    if is_transient_local(&qos) {
+        qos.durability_service = Some(durability_service_for_history(&get_history_or_default(
+            &qos,
+        )));
+    }
+    Some(qos)
+}
+
+// Copy and apply to a QoS the first rule of "qos_overrides" that matches the ROS2 topic name (if any)
+pub fn apply_qos_overrides(config: &Config, ros2_name: &str, qos: &Qos) -> Qos {
+    let mut qos = qos.clone();
+    let rule = match config
+        .qos_overrides
+        .iter()
+        .find(|r| r.is_matching(ros2_name))
+    {
+        Some(rule) => rule,
+        None => return qos,
+    };
+    if let Some(reliability) = rule.reliability {
+        qos.reliability = Some(match reliability {
+            ReliabilityOverride::BestEffort => Reliability {
+                kind: ReliabilityKind::BEST_EFFORT,
+                max_blocking_time: DDS_100MS_DURATION,
+            },
+            ReliabilityOverride::Reliable => Reliability {
+                kind: ReliabilityKind::RELIABLE,
+                max_blocking_time: DDS_100MS_DURATION.saturating_add(1),
+            },
+        });
+    }
+    if let Some(history) = rule.history {
+        qos.history = Some(match history {
+            HistoryOverride::KeepLast(depth) => History {
+                kind: HistoryKind::KEEP_LAST,
+                depth,
+            },
+            HistoryOverride::KeepAll => History {
+                kind: HistoryKind::KEEP_ALL,
+                depth: DDS_LENGTH_UNLIMITED,
+            },
+        });
+    }
+    if let Some(durability) = rule.durability {
+        qos.durability = Some(Durability {
+            kind: match durability {
+                DurabilityOverride::Volatile => DurabilityKind::VOLATILE,
+                DurabilityOverride::TransientLocal => DurabilityKind::TRANSIENT_LOCAL,
+            },
+        });
+    }
+    if rule.durability.is_some() || rule.history.is_some() {
+        // keep durability_service consistent with the (possibly overridden) durability and history,
+        // as CycloneDDS is using durability_service.history for transient_local historical data
+        qos.durability_service = None;
+        if is_transient_local(&qos) {
+            qos.durability_service = Some(durability_service_for_history(&get_history_or_default(
+                &qos,
+            )));
+        }
+    }
+    if let Some(deadline) = rule.deadline {
+        qos.deadline = Some(Deadline {
+            period: duration_to_dds(deadline),
+        });
+    }
+    if let Some(lifespan) = rule.lifespan {
+        qos.lifespan = Some(Lifespan {
+            duration: duration_to_dds(lifespan),
+        });
+    }
+    qos
+}
+
+// The durability_service QoS configured for a TRANSIENT_LOCAL Writer with the specified history
+fn durability_service_for_history(history: &History) -> DurabilityService {
+    DurabilityService {
+        service_cleanup_delay: 60 * DDS_1S_DURATION,
+        history_kind: history.kind,
+        history_depth: history.depth,
+        max_samples: DDS_LENGTH_UNLIMITED,
+        max_instances: DDS_LENGTH_UNLIMITED,
+        max_samples_per_instance: DDS_LENGTH_UNLIMITED,
+    }
+}
+
+fn duration_to_dds(d: Duration) -> dds_duration_t {
+    dds_duration_t::try_from(d.as_nanos()).unwrap_or(DDS_INFINITE_TIME)
+}
+
+fn get_durability_kind_or_default(qos: &Qos) -> DurabilityKind {
+    qos.durability
+        .as_ref()
+        .map_or(DurabilityKind::VOLATILE, |durability| durability.kind)
+}
+
 // Copy and adapt Writer's QoS for creation of a matching Reader
 pub fn adapt_writer_qos_for_reader(qos: &Qos) -> Qos {
     let mut reader_qos = qos.clone();
@@ -107,19 +388,8 @@ pub fn adapt_reader_qos_for_writer(qos: &Qos) -> Qos {
     // if Reader is TRANSIENT_LOCAL, configure durability_service QoS with same history as the Reader.
     // This is because CycloneDDS is actually using durability_service.history for transient_local historical data.
     if is_transient_local(qos) {
-        let history = qos
-            .history
-            .as_ref()
-            .map_or(History::default(), |history| history.clone());
-
-        writer_qos.durability_service = Some(DurabilityService {
-            service_cleanup_delay: 60 * DDS_1S_DURATION,
-            history_kind: history.kind,
-            history_depth: history.depth,
-            max_samples: DDS_LENGTH_UNLIMITED,
-            max_instances: DDS_LENGTH_UNLIMITED,
-            max_samples_per_instance: DDS_LENGTH_UNLIMITED,
-        });
+        writer_qos.durability_service =
+            Some(durability_service_for_history(&get_history_or_default(qos)));
     }
     // Workaround for the DDS Writer to correctly match with a FastRTPS Reader
     writer_qos.reliability = match writer_qos.reliability {
@@ -136,3 +406,52 @@ pub fn adapt_reader_qos_for_writer(qos: &Qos) -> Qos {
 
     writer_qos
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_qos_overrides() {
+        let config: Config = serde_json::from_str(
+            r#"{"qos_overrides": [
+                {"topics": "/camera/depth", "history": "keep_all"},
+                {"topics": "/camera/.*", "reliability": "best_effort", "history": "keep_last:1"},
+                {"topics": "/map", "durability": "transient_local", "deadline": 2, "lifespan": 0.5}
+            ]}"#,
+        )
+        .unwrap();
+        let qos = ros2_topic_default_qos();
+
+        // only the first matching rule is applied
+        let depth = apply_qos_overrides(&config, "/camera/depth", &qos);
+        assert_eq!(depth.history.unwrap().kind, HistoryKind::KEEP_ALL);
+        assert_eq!(depth.reliability, qos.reliability);
+        let image = apply_qos_overrides(&config, "/camera/image", &qos);
+        assert_eq!(
+            image.history,
+            Some(History {
+                kind: HistoryKind::KEEP_LAST,
+                depth: 1
+            })
+        );
+        assert_eq!(
+            image.reliability.unwrap().kind,
+            ReliabilityKind::BEST_EFFORT
+        );
+
+        let map = apply_qos_overrides(&config, "/map", &qos);
+        assert!(is_transient_local(&map));
+        assert_eq!(
+            map.durability_service,
+            Some(durability_service_for_history(&get_history_or_default(
+                &qos
+            )))
+        );
+        assert_eq!(map.deadline.unwrap().period, 2 * DDS_1S_DURATION);
+        assert_eq!(map.lifespan.unwrap().duration, DDS_1S_DURATION / 2);
+
+        // no matching rule
+        assert_eq!(apply_qos_overrides(&config, "/chatter", &qos), qos);
+    }
+}
@@ -12,7 +12,26 @@
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
 
-use zenoh::prelude::KeyExpr;
+use zenoh::prelude::{keyexpr, KeyExpr};
+
+use crate::ke_for_sure;
+
+lazy_static::lazy_static!(
+    // Suffixes of the services and topics composing a ROS2 Action
+    pub static ref KE_SUFFIX_ACTION_SEND_GOAL: &'static keyexpr = ke_for_sure!("_action/send_goal");
+    pub static ref KE_SUFFIX_ACTION_CANCEL_GOAL: &'static keyexpr = ke_for_sure!("_action/cancel_goal");
+    pub static ref KE_SUFFIX_ACTION_GET_RESULT: &'static keyexpr = ke_for_sure!("_action/get_result");
+    pub static ref KE_SUFFIX_ACTION_FEEDBACK: &'static keyexpr = ke_for_sure!("_action/feedback");
+    pub static ref KE_SUFFIX_ACTION_STATUS: &'static keyexpr = ke_for_sure!("_action/status");
+);
+
+// ROS2 types of the Action services and topics that are generic (i.e. not depending on the Action type)
+pub const ROS2_ACTION_CANCEL_GOAL_SRV_TYPE: &str = "action_msgs/srv/CancelGoal";
+pub const ROS2_ACTION_STATUS_MSG_TYPE: &str = "action_msgs/msg/GoalStatusArray";
+
+// Values defined in action_msgs/msg/GoalStatus and action_msgs/srv/CancelGoal
+pub const ROS2_ACTION_GOAL_STATUS_ABORTED: u8 = 6;
+pub const ROS2_ACTION_CANCEL_ERROR_REJECTED: u8 = 1;
 
 /// Convert DDS Topic type to ROS2 Message type
 pub fn dds_type_to_ros2_message_type(dds_topic: &str) -> String {
@@ -46,6 +65,16 @@ pub fn dds_type_to_ros2_service_type(dds_topic: &str) -> String {
     )
 }
 
+/// Convert ROS2 Service type to DDS Topic type for the Request
+pub fn ros2_service_type_to_request_dds_type(ros_service: &str) -> String {
+    ros2_message_type_to_dds_type(&format!("{ros_service}_Request"))
+}
+
+/// Convert ROS2 Service type to DDS Topic type for the Reply
+pub fn ros2_service_type_to_reply_dds_type(ros_service: &str) -> String {
+    ros2_message_type_to_dds_type(&format!("{ros_service}_Response"))
+}
+
 /// Convert DDS Topic type for ROS2 Action to ROS2 Action type
 /// Warning: can't work for "rt/.../_action/status", "rq/.../_action/cancel_goalRequest"
 /// or "rr../_action/cancel_goalReply" topic, since their types are generic
@@ -61,6 +90,21 @@ pub fn dds_type_to_ros2_action_type(dds_topic: &str) -> String {
     )
 }
 
+/// Convert ROS2 Action type to ROS2 Service type for the "send_goal" service
+pub fn ros2_action_type_to_send_goal_type(ros_action: &str) -> String {
+    format!("{ros_action}_SendGoal")
+}
+
+/// Convert ROS2 Action type to ROS2 Service type for the "get_result" service
+pub fn ros2_action_type_to_get_result_type(ros_action: &str) -> String {
+    format!("{ros_action}_GetResult")
+}
+
+/// Convert ROS2 Action type to ROS2 Message type for the "feedback" topic
+pub fn ros2_action_type_to_feedback_type(ros_action: &str) -> String {
+    format!("{ros_action}_FeedbackMessage")
+}
+
 // check if name is a ROS name: starting with '/' and useable as a key expression (removing 1st '/')
 #[inline]
 pub fn check_ros_name(name: &str) -> Result<(), String> {
@@ -114,6 +158,15 @@ mod tests {
             "rcl_interfaces/srv/ListParameters"
         );
 
+        assert_eq!(
+            ros2_service_type_to_request_dds_type("example_interfaces/srv/AddTwoInts"),
+            "example_interfaces::srv::dds_::AddTwoInts_Request_"
+        );
+        assert_eq!(
+            ros2_service_type_to_reply_dds_type("example_interfaces/srv/AddTwoInts"),
+            "example_interfaces::srv::dds_::AddTwoInts_Response_"
+        );
+
         assert_eq!(
             dds_type_to_ros2_action_type(
                 "example_interfaces::action::dds_::Fibonacci_SendGoal_Request_"
@@ -144,5 +197,24 @@ mod tests {
             ),
             "example_interfaces/action/Fibonacci"
         );
+
+        assert_eq!(
+            ros2_service_type_to_request_dds_type(&ros2_action_type_to_send_goal_type(
+                "example_interfaces/action/Fibonacci"
+            )),
+            "example_interfaces::action::dds_::Fibonacci_SendGoal_Request_"
+        );
+        assert_eq!(
+            ros2_service_type_to_reply_dds_type(&ros2_action_type_to_get_result_type(
+                "example_interfaces/action/Fibonacci"
+            )),
+            "example_interfaces::action::dds_::Fibonacci_GetResult_Response_"
+        );
+        assert_eq!(
+            ros2_message_type_to_dds_type(&ros2_action_type_to_feedback_type(
+                "example_interfaces/action/Fibonacci"
+            )),
+            "example_interfaces::action::dds_::Fibonacci_FeedbackMessage_"
+        );
     }
 }
@@ -0,0 +1,441 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use cdr::{CdrLe, Infinite};
+use cyclors::dds_entity_t;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zenoh::liveliness::LivelinessToken;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::*;
+
+use crate::config::ServiceSelectionPolicy;
+use crate::gid::Gid;
+use crate::liveliness_mgt::new_ke_liveliness_action_cli;
+use crate::qos_helpers::*;
+use crate::ros2_utils::*;
+use crate::route_service_cli::{
+    get_action_get_result_timeout, get_selection_policy, get_service_timeout, GoalServer,
+    GoalTracking, GoalsMap, RemoteBridgesZid, RouteServiceCli,
+};
+use crate::route_subscriber::RouteSubscriber;
+use crate::Config;
+
+// a route for an Action Client exposed in Zenoh as 3 Queriers (send_goal, cancel_goal and get_result services)
+// and 2 Subscribers (feedback and status topics)
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize)]
+pub struct RouteActionCli<'a> {
+    // the ROS2 Action name
+    ros2_name: String,
+    // the ROS2 type
+    ros2_type: String,
+    // the Zenoh key expression prefix used for routing (each sub-route uses "<prefix>/_action/...")
+    zenoh_key_expr_prefix: OwnedKeyExpr,
+    // the zenoh session
+    #[serde(skip)]
+    zsession: &'a Arc<Session>,
+    // the config
+    #[serde(skip)]
+    config: Arc<Config>,
+    // if the route is active (i.e. serving at least 1 local Action Client)
+    is_active: bool,
+    // the sub-routes composing this Action route
+    route_send_goal: RouteServiceCli<'a>,
+    route_cancel_goal: RouteServiceCli<'a>,
+    route_get_result: RouteServiceCli<'a>,
+    route_feedback: RouteSubscriber<'a>,
+    route_status: RouteSubscriber<'a>,
+    // the remote Action Servers that accepted the goals sent by local Action Clients
+    #[serde(skip)]
+    goals: GoalsMap,
+    // a liveliness token associated to this route, for announcement to other plugins
+    #[serde(skip)]
+    liveliness_token: Option<LivelinessToken<'a>>,
+    // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
+    remote_routes: HashSet<String>,
+    // the list of nodes served by this route
+    local_nodes: HashSet<String>,
+}
+
+impl fmt::Display for RouteActionCli<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Route Action Client (ROS:{} <-> Zenoh:{})",
+            self.ros2_name, self.zenoh_key_expr_prefix
+        )
+    }
+}
+
+impl RouteActionCli<'_> {
+    pub async fn create<'a>(
+        config: Arc<Config>,
+        zsession: &'a Arc<Session>,
+        participant: dds_entity_t,
+        ros2_name: String,
+        ros2_type: String,
+        zenoh_key_expr_prefix: OwnedKeyExpr,
+        remote_bridges_zid: RemoteBridgesZid,
+    ) -> Result<RouteActionCli<'a>, String> {
+        log::debug!(
+            "Route Action Client ({ros2_name} <-> {zenoh_key_expr_prefix}): creation with type {ros2_type}"
+        );
+
+        // A goal must be sent to only 1 remote Action Server: "first" policy is replaced with "nearest".
+        // The cancel_goal and get_result requests are routed to the Action Server that accepted the goal.
+        let selection_policy = match get_selection_policy(&config, &zenoh_key_expr_prefix) {
+            ServiceSelectionPolicy::First => {
+                log::debug!(
+                    "Route Action Client ({ros2_name} <-> {zenoh_key_expr_prefix}): 'nearest' selection policy used instead of 'first'"
+                );
+                ServiceSelectionPolicy::Nearest
+            }
+            policy => policy,
+        };
+        let goals = GoalsMap::new(remote_bridges_zid);
+
+        let route_send_goal = RouteServiceCli::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_SEND_GOAL),
+            ros2_action_type_to_send_goal_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_SEND_GOAL,
+            &None,
+            get_service_timeout(&config, &zenoh_key_expr_prefix),
+            selection_policy.clone(),
+            GoalTracking::Record(goals.clone()),
+        )
+        .await?;
+
+        let route_cancel_goal = RouteServiceCli::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_CANCEL_GOAL),
+            ROS2_ACTION_CANCEL_GOAL_SRV_TYPE.to_string(),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_CANCEL_GOAL,
+            &None,
+            get_service_timeout(&config, &zenoh_key_expr_prefix),
+            selection_policy.clone(),
+            GoalTracking::Follow(goals.clone()),
+        )
+        .await?;
+
+        // the get_result request is replied by the Action Server only when the goal terminates:
+        // use a specific timeout, much longer than for other queries
+        let route_get_result = RouteServiceCli::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_GET_RESULT),
+            ros2_action_type_to_get_result_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_GET_RESULT,
+            &None,
+            get_action_get_result_timeout(&config, &zenoh_key_expr_prefix),
+            selection_policy,
+            GoalTracking::FollowAndRelease(goals.clone()),
+        )
+        .await?;
+
+        let route_feedback = RouteSubscriber::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_FEEDBACK),
+            ros2_action_type_to_feedback_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_FEEDBACK,
+            true,
+            adapt_reader_qos_for_writer(&ros2_topic_default_qos()),
+        )
+        .await?;
+
+        // the status topic is TRANSIENT_LOCAL: the RouteSubscriber will use a FetchingSubscriber,
+        // getting the current goals status from the remote Action Server's route cache
+        let route_status = RouteSubscriber::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_STATUS),
+            ROS2_ACTION_STATUS_MSG_TYPE.to_string(),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_STATUS,
+            true,
+            adapt_reader_qos_for_writer(&ros2_action_status_default_qos()),
+        )
+        .await?;
+
+        Ok(RouteActionCli {
+            ros2_name,
+            ros2_type,
+            zenoh_key_expr_prefix,
+            zsession,
+            config,
+            is_active: false,
+            route_send_goal,
+            route_cancel_goal,
+            route_get_result,
+            route_feedback,
+            route_status,
+            goals,
+            liveliness_token: None,
+            remote_routes: HashSet::new(),
+            local_nodes: HashSet::new(),
+        })
+    }
+
+    async fn activate(&mut self, plugin_id: &keyexpr) -> Result<(), String> {
+        log::debug!("{self} activate");
+        self.route_send_goal.activate(plugin_id, false).await?;
+        self.route_cancel_goal.activate(plugin_id, false).await?;
+        self.route_get_result.activate(plugin_id, false).await?;
+        // Note: the sub-routes don't declare their own LivelinessToken. Only this Action route is announced.
+        self.route_feedback
+            .activate(&self.config, plugin_id, &ros2_topic_default_qos(), false)
+            .await?;
+        self.route_status
+            .activate(
+                &self.config,
+                plugin_id,
+                &ros2_action_status_default_qos(),
+                false,
+            )
+            .await?;
+        self.is_active = true;
+
+        // create associated LivelinessToken
+        let liveliness_ke =
+            new_ke_liveliness_action_cli(plugin_id, &self.zenoh_key_expr_prefix, &self.ros2_type)?;
+        let ros2_name = self.ros2_name.clone();
+        self.liveliness_token = Some(
+            self.zsession
+                .liveliness()
+                .declare_token(liveliness_ke)
+                .res_async()
+                .await
+                .map_err(|e| {
+                    format!(
+                        "Failed create LivelinessToken associated to route for Action Client {ros2_name}: {e}"
+                    )
+                })?,
+        );
+        Ok(())
+    }
+
+    fn deactivate(&mut self) {
+        log::debug!("{self} deactivate");
+        self.route_send_goal.deactivate();
+        self.route_cancel_goal.deactivate();
+        self.route_get_result.deactivate();
+        self.route_feedback.deactivate();
+        self.route_status.deactivate();
+        self.is_active = false;
+        self.liveliness_token = None;
+    }
+
+    // Update the route and its sub-routes with a new config
+    pub fn update_config(&mut self, config: Arc<Config>) {
+        let service_timeout = get_service_timeout(&config, &self.zenoh_key_expr_prefix);
+        self.route_send_goal
+            .update_config(config.clone(), service_timeout);
+        self.route_cancel_goal
+            .update_config(config.clone(), service_timeout);
+        self.route_get_result.update_config(
+            config.clone(),
+            get_action_get_result_timeout(&config, &self.zenoh_key_expr_prefix),
+        );
+        self.route_feedback.update_config(config.clone());
+        self.route_status.update_config(config.clone());
+        self.config = config;
+    }
+
+    /// Query for historical publications of the status topic from the specified plugin
+    pub fn query_historical_status(&mut self, plugin_id: &keyexpr, query_timeout: Duration) {
+        self.route_status
+            .query_historical_publications(plugin_id, query_timeout);
+    }
+
+    /// Abort the goals accepted by the remote Action Servers served by a remote bridge that left:
+    /// publish their STATUS_ABORTED status on the local status topic and fail the pending get_result requests
+    pub fn abort_goals_of_bridge(&mut self, plugin_id: &keyexpr, zid: &Option<ZenohId>) {
+        let aborted: Vec<([u8; 16], GoalServer)> =
+            self.goals.remove_goals_of_bridge(plugin_id, zid);
+        if aborted.is_empty() {
+            return;
+        }
+        log::warn!(
+            "{self}: remote bridge {plugin_id} left - abort {} goal(s) it accepted",
+            aborted.len()
+        );
+
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| Time {
+                sec: d.as_secs() as i32,
+                nanosec: d.subsec_nanos(),
+            })
+            .unwrap_or_default();
+        let status_array = GoalStatusArray {
+            status_list: aborted
+                .iter()
+                .map(|(goal_id, _)| GoalStatus {
+                    goal_info: GoalInfo {
+                        goal_id: *goal_id,
+                        stamp: stamp.clone(),
+                    },
+                    status: ROS2_ACTION_GOAL_STATUS_ABORTED as i8,
+                })
+                .collect(),
+        };
+        match cdr::serialize::<_, _, CdrLe>(&status_array, Infinite) {
+            Ok(buf) => {
+                if let Err(e) = self.route_status.write_to_dds(buf) {
+                    log::warn!("{self}: failed to publish aborted goals status: {e}");
+                }
+            }
+            Err(e) => log::warn!("{self}: error serializing aborted goals status: {e}"),
+        }
+
+        for (_, server) in aborted {
+            server.fail_pending_get_results(&format!("remote bridge {plugin_id} left"));
+        }
+    }
+
+    // Return the GIDs of all the DDS Writers created by this route
+    pub fn dds_writers_guids(&self) -> Result<Vec<Gid>, String> {
+        Ok(vec![
+            self.route_send_goal.dds_writer_guid()?,
+            self.route_cancel_goal.dds_writer_guid()?,
+            self.route_get_result.dds_writer_guid()?,
+            self.route_feedback.dds_writer_guid()?,
+            self.route_status.dds_writer_guid()?,
+        ])
+    }
+
+    // Return the GIDs of all the DDS Readers created by this route
+    pub fn dds_readers_guids(&self) -> Result<Vec<Gid>, String> {
+        Ok(vec![
+            self.route_send_goal.dds_reader_guid()?,
+            self.route_cancel_goal.dds_reader_guid()?,
+            self.route_get_result.dds_reader_guid()?,
+        ])
+    }
+
+    #[inline]
+    pub fn add_remote_route(&mut self, plugin_id: &str, zenoh_key_expr_prefix: &keyexpr) {
+        self.remote_routes
+            .insert(format!("{plugin_id}:{zenoh_key_expr_prefix}"));
+        // the services routes need to know the remote Action Servers for their selection policy
+        self.route_send_goal.add_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_SEND_GOAL),
+        );
+        self.route_cancel_goal.add_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_CANCEL_GOAL),
+        );
+        self.route_get_result.add_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_GET_RESULT),
+        );
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr_prefix: &keyexpr) {
+        self.remote_routes
+            .remove(&format!("{plugin_id}:{zenoh_key_expr_prefix}"));
+        self.route_send_goal.remove_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_SEND_GOAL),
+        );
+        self.route_cancel_goal.remove_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_CANCEL_GOAL),
+        );
+        self.route_get_result.remove_remote_route(
+            plugin_id,
+            &(zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_GET_RESULT),
+        );
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn is_serving_remote_route(&self) -> bool {
+        !self.remote_routes.is_empty()
+    }
+
+    #[inline]
+    pub async fn add_local_node(&mut self, node: String, plugin_id: &keyexpr) {
+        self.local_nodes.insert(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if 1st local node added, activate the route
+        if self.local_nodes.len() == 1 {
+            if let Err(e) = self.activate(plugin_id).await {
+                log::error!("{self} activation failed: {e}");
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove_local_node(&mut self, node: &str) {
+        self.local_nodes.remove(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if last local node removed, deactivate the route
+        if self.local_nodes.is_empty() {
+            self.deactivate();
+        }
+    }
+
+    #[inline]
+    pub fn is_serving_local_node(&self) -> bool {
+        !self.local_nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        !self.is_serving_local_node() && !self.is_serving_remote_route()
+    }
+}
+
+// action_msgs/msg/GoalStatusArray, serialized to publish the status of aborted goals
+#[derive(Serialize)]
+struct GoalStatusArray {
+    status_list: Vec<GoalStatus>,
+}
+
+// action_msgs/msg/GoalStatus
+#[derive(Serialize)]
+struct GoalStatus {
+    goal_info: GoalInfo,
+    status: i8,
+}
+
+// action_msgs/msg/GoalInfo
+#[derive(Serialize)]
+struct GoalInfo {
+    // unique_identifier_msgs/msg/UUID
+    goal_id: [u8; 16],
+    stamp: Time,
+}
+
+// builtin_interfaces/msg/Time
+#[derive(Serialize, Clone, Default)]
+struct Time {
+    sec: i32,
+    nanosec: u32,
+}
@@ -0,0 +1,317 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use cyclors::dds_entity_t;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use zenoh::liveliness::LivelinessToken;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::*;
+
+use crate::gid::Gid;
+use crate::instance_cache::CacheBudget;
+use crate::liveliness_mgt::new_ke_liveliness_action_srv;
+use crate::qos_helpers::*;
+use crate::ros2_utils::*;
+use crate::route_publisher::RoutePublisher;
+use crate::route_service_cli::{get_action_get_result_timeout, get_service_timeout};
+use crate::route_service_srv::RouteServiceSrv;
+use crate::Config;
+
+// a route for an Action Server exposed in Zenoh as 3 Queryables (send_goal, cancel_goal and get_result services)
+// and 2 Publishers (feedback and status topics)
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize)]
+pub struct RouteActionSrv<'a> {
+    // the ROS2 Action name
+    ros2_name: String,
+    // the ROS2 type
+    ros2_type: String,
+    // the Zenoh key expression prefix used for routing (each sub-route uses "<prefix>/_action/...")
+    zenoh_key_expr_prefix: OwnedKeyExpr,
+    // the zenoh session
+    #[serde(skip)]
+    zsession: &'a Arc<Session>,
+    // the config
+    #[serde(skip)]
+    config: Arc<Config>,
+    // if the route is active (i.e. serving at least 1 local Action Server)
+    is_active: bool,
+    // the sub-routes composing this Action route
+    route_send_goal: RouteServiceSrv<'a>,
+    route_cancel_goal: RouteServiceSrv<'a>,
+    route_get_result: RouteServiceSrv<'a>,
+    route_feedback: RoutePublisher<'a>,
+    route_status: RoutePublisher<'a>,
+    // a liveliness token associated to this route, for announcement to other plugins
+    #[serde(skip)]
+    liveliness_token: Option<LivelinessToken<'a>>,
+    // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
+    remote_routes: HashSet<String>,
+    // the list of nodes served by this route
+    local_nodes: HashSet<String>,
+}
+
+impl fmt::Display for RouteActionSrv<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Route Action Server (ROS:{} <-> Zenoh:{})",
+            self.ros2_name, self.zenoh_key_expr_prefix
+        )
+    }
+}
+
+impl RouteActionSrv<'_> {
+    pub async fn create<'a>(
+        config: Arc<Config>,
+        zsession: &'a Arc<Session>,
+        participant: dds_entity_t,
+        ros2_name: String,
+        ros2_type: String,
+        zenoh_key_expr_prefix: OwnedKeyExpr,
+        cache_budget: Arc<CacheBudget>,
+    ) -> Result<RouteActionSrv<'a>, String> {
+        log::debug!(
+            "Route Action Server ({ros2_name} <-> {zenoh_key_expr_prefix}): creation with type {ros2_type}"
+        );
+
+        let route_send_goal = RouteServiceSrv::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_SEND_GOAL),
+            ros2_action_type_to_send_goal_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_SEND_GOAL,
+            &None,
+            get_service_timeout(&config, &zenoh_key_expr_prefix),
+        )
+        .await?;
+
+        let route_cancel_goal = RouteServiceSrv::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_CANCEL_GOAL),
+            ROS2_ACTION_CANCEL_GOAL_SRV_TYPE.to_string(),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_CANCEL_GOAL,
+            &None,
+            get_service_timeout(&config, &zenoh_key_expr_prefix),
+        )
+        .await?;
+
+        let route_get_result = RouteServiceSrv::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_GET_RESULT),
+            ros2_action_type_to_get_result_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_GET_RESULT,
+            &None,
+            get_action_get_result_timeout(&config, &zenoh_key_expr_prefix),
+        )
+        .await?;
+
+        let route_feedback = RoutePublisher::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_FEEDBACK),
+            ros2_action_type_to_feedback_type(&ros2_type),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_FEEDBACK,
+            &None,
+            true,
+            adapt_writer_qos_for_reader(&ros2_topic_default_qos()),
+            None,
+            cache_budget.clone(),
+        )
+        .await?;
+
+        // the status topic is TRANSIENT_LOCAL: the RoutePublisher will use an InstanceCache,
+        // allowing late-joining remote Action Clients to get the current goals status
+        let route_status = RoutePublisher::create(
+            config.clone(),
+            zsession,
+            participant,
+            format!("{ros2_name}/{}", *KE_SUFFIX_ACTION_STATUS),
+            ROS2_ACTION_STATUS_MSG_TYPE.to_string(),
+            &zenoh_key_expr_prefix / *KE_SUFFIX_ACTION_STATUS,
+            &None,
+            true,
+            adapt_writer_qos_for_reader(&ros2_action_status_default_qos()),
+            None,
+            cache_budget,
+        )
+        .await?;
+
+        Ok(RouteActionSrv {
+            ros2_name,
+            ros2_type,
+            zenoh_key_expr_prefix,
+            zsession,
+            config,
+            is_active: false,
+            route_send_goal,
+            route_cancel_goal,
+            route_get_result,
+            route_feedback,
+            route_status,
+            liveliness_token: None,
+            remote_routes: HashSet::new(),
+            local_nodes: HashSet::new(),
+        })
+    }
+
+    async fn activate(&mut self, plugin_id: &keyexpr) -> Result<(), String> {
+        log::debug!("{self} activate");
+        self.route_send_goal.activate(plugin_id, false).await?;
+        self.route_cancel_goal.activate(plugin_id, false).await?;
+        self.route_get_result.activate(plugin_id, false).await?;
+        // Note: the sub-routes don't declare their own LivelinessToken. Only this Action route is announced.
+        self.route_feedback
+            .activate(plugin_id, &ros2_topic_default_qos(), false)
+            .await?;
+        self.route_status
+            .activate(plugin_id, &ros2_action_status_default_qos(), false)
+            .await?;
+        self.is_active = true;
+
+        // create associated LivelinessToken
+        let liveliness_ke =
+            new_ke_liveliness_action_srv(plugin_id, &self.zenoh_key_expr_prefix, &self.ros2_type)?;
+        let ros2_name = self.ros2_name.clone();
+        self.liveliness_token = Some(
+            self.zsession
+                .liveliness()
+                .declare_token(liveliness_ke)
+                .res_async()
+                .await
+                .map_err(|e| {
+                    format!(
+                        "Failed create LivelinessToken associated to route for Action Server {ros2_name}: {e}"
+                    )
+                })?,
+        );
+        Ok(())
+    }
+
+    fn deactivate(&mut self) {
+        log::debug!("{self} deactivate");
+        self.route_send_goal.deactivate();
+        self.route_cancel_goal.deactivate();
+        self.route_get_result.deactivate();
+        self.route_feedback.deactivate();
+        self.route_status.deactivate();
+        self.is_active = false;
+        self.liveliness_token = None;
+    }
+
+    // Update the route and its sub-routes with a new config
+    pub async fn update_config(
+        &mut self,
+        participant: dds_entity_t,
+        config: Arc<Config>,
+    ) -> Result<(), String> {
+        let service_timeout = get_service_timeout(&config, &self.zenoh_key_expr_prefix);
+        self.route_send_goal
+            .update_config(config.clone(), service_timeout);
+        self.route_cancel_goal
+            .update_config(config.clone(), service_timeout);
+        self.route_get_result.update_config(
+            config.clone(),
+            get_action_get_result_timeout(&config, &self.zenoh_key_expr_prefix),
+        );
+        self.route_feedback
+            .update_config(participant, config.clone())
+            .await?;
+        self.route_status
+            .update_config(participant, config.clone())
+            .await?;
+        self.config = config;
+        Ok(())
+    }
+
+    // Return the GIDs of all the DDS Writers created by this route
+    pub fn dds_writers_guids(&self) -> Result<Vec<Gid>, String> {
+        Ok(vec![
+            self.route_send_goal.dds_writer_guid()?,
+            self.route_cancel_goal.dds_writer_guid()?,
+            self.route_get_result.dds_writer_guid()?,
+        ])
+    }
+
+    // Return the GIDs of all the DDS Readers created by this route
+    pub fn dds_readers_guids(&self) -> Result<Vec<Gid>, String> {
+        Ok(vec![
+            self.route_send_goal.dds_reader_guid()?,
+            self.route_cancel_goal.dds_reader_guid()?,
+            self.route_get_result.dds_reader_guid()?,
+            self.route_feedback.dds_reader_guid()?,
+            self.route_status.dds_reader_guid()?,
+        ])
+    }
+
+    #[inline]
+    pub fn add_remote_route(&mut self, plugin_id: &str, zenoh_key_expr_prefix: &keyexpr) {
+        self.remote_routes
+            .insert(format!("{plugin_id}:{zenoh_key_expr_prefix}"));
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr_prefix: &keyexpr) {
+        self.remote_routes
+            .remove(&format!("{plugin_id}:{zenoh_key_expr_prefix}"));
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn is_serving_remote_route(&self) -> bool {
+        !self.remote_routes.is_empty()
+    }
+
+    #[inline]
+    pub async fn add_local_node(&mut self, node: String, plugin_id: &keyexpr) {
+        self.local_nodes.insert(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if 1st local node added, activate the route
+        if self.local_nodes.len() == 1 {
+            if let Err(e) = self.activate(plugin_id).await {
+                log::error!("{self} activation failed: {e}");
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove_local_node(&mut self, node: &str) {
+        self.local_nodes.remove(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if last local node removed, deactivate the route
+        if self.local_nodes.is_empty() {
+            self.deactivate();
+        }
+    }
+
+    #[inline]
+    pub fn is_serving_local_node(&self) -> bool {
+        !self.local_nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        !self.is_serving_local_node() && !self.is_serving_remote_route()
+    }
+}
@@ -12,26 +12,33 @@
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
 
-use cyclors::qos::{HistoryKind, Qos};
-use cyclors::{dds_entity_t, DDS_LENGTH_UNLIMITED};
+use cyclors::dds_entity_t;
+use cyclors::qos::{HistoryKind, OwnershipStrength, Qos};
 use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use std::{collections::HashSet, fmt};
+use std::time::{Duration, Instant};
 use zenoh::liveliness::LivelinessToken;
 use zenoh::prelude::r#async::AsyncResolve;
 use zenoh::prelude::*;
-use zenoh_ext::{PublicationCache, SessionExt};
+use zenoh::queryable::Queryable;
+use zenoh::subscriber::Subscriber;
 
 use crate::gid::Gid;
+use crate::instance_cache::{
+    get_cache_max_bytes, CacheBudget, CacheLimits, InstanceCache, InstanceCacheSlot,
+};
 use crate::liveliness_mgt::new_ke_liveliness_pub;
 use crate::ros2_utils::ros2_message_type_to_dds_type;
+use crate::routes_mgr::RouteStatus;
 use crate::{dds_discovery::*, qos_helpers::*, Config};
-use crate::{serialize_option_as_bool, KE_PREFIX_PUB_CACHE};
+use crate::{serialize_atomic_bool, serialize_option_as_bool, KE_PREFIX_PUB_CACHE};
 
 enum ZPublisher<'a> {
     Publisher(KeyExpr<'a>),
-    PublicationCache(PublicationCache<'a>),
+    InstanceCache(Subscriber<'a, ()>, Queryable<'a, ()>),
 }
 
 // a route from DDS to Zenoh
@@ -54,9 +61,20 @@ pub struct RoutePublisher<'a> {
     // `None` when route is created on a remote announcement and no local ROS2 Subscriber discovered yet
     #[serde(rename = "is_active", serialize_with = "serialize_option_as_bool")]
     zenoh_publisher: Option<ZPublisher<'a>>,
+    // the zenoh publisher with its cache, kept after the last local node left (see "latch_retention")
+    // until the specified time (or indefinitely if None), or until the route is re-activated
+    #[serde(rename = "retained", serialize_with = "serialize_option_as_bool")]
+    retained_publisher: Option<(ZPublisher<'a>, Option<Instant>)>,
     // the local DDS Reader created to serve the route (i.e. re-publish to zenoh data coming from DDS)
     #[serde(serialize_with = "serialize_entity_guid")]
     dds_reader: dds_entity_t,
+    // the argument of the DDS Reader's Listener (if any), freed when the Reader is deleted
+    #[serde(skip)]
+    dds_reader_listener_arg: Option<ListenerArg>,
+    // the QoS of the DDS Reader (after "qos_overrides" rules application)
+    reader_qos: Qos,
+    // the statuses reported by the DDS Reader (incompatible QoS, deadline missed, liveliness changed)
+    dds_reader_status: DdsStatusRecorder,
     // if the Reader is TRANSIENT_LOCAL
     transient_local: bool,
     // if the topic is keyless
@@ -65,15 +83,60 @@ pub struct RoutePublisher<'a> {
     // a liveliness token associated to this route, for announcement to other plugins
     #[serde(skip)]
     liveliness_token: Option<LivelinessToken<'a>>,
+    // the QoS announced with the liveliness token
+    #[serde(skip)]
+    announced_qos: Option<Qos>,
     // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
     remote_routes: HashSet<String>,
+    // the minimum separation between samples requested by the remote routes, indexed as in remote_routes
+    #[serde(skip)]
+    remote_min_separations: HashMap<String, Duration>,
+    // the downsampling of the data routed to Zenoh, with the period of the highest rate required by the remote routes
+    #[serde(
+        rename = "downsampling_period",
+        serialize_with = "serialize_downsampling_period"
+    )]
+    downsampling: Arc<DownsamplingFilter>,
     // the list of nodes served by this route
     local_nodes: HashSet<String>,
+    // the ownership strengths of the served local Writers with EXCLUSIVE Ownership QoS, indexed by node
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    local_ownership_strengths: HashMap<String, i32>,
+    // the ownership strengths of the remote Publishers with EXCLUSIVE Ownership QoS, indexed by plugin id
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    remote_ownership_strengths: HashMap<String, i32>,
+    // if the routing to Zenoh uses CongestionControl::Block: only if "reliable_routes_blocking" is true,
+    // the DDS Reader is RELIABLE, and at least one of the remote routes (if any) has a RELIABLE Reader
+    #[serde(serialize_with = "serialize_atomic_bool")]
+    congestion_blocking: Arc<AtomicBool>,
+    // the remote routes served by this route having a RELIABLE Reader, indexed as in remote_routes
+    #[serde(skip)]
+    remote_reliabilities: HashMap<String, bool>,
+    // the cache of the routed publications per instance, set while the route is active for a TRANSIENT_LOCAL topic
+    // (its size being shown in admin space)
+    #[serde(rename = "cache", serialize_with = "serialize_instance_cache")]
+    instance_cache: InstanceCacheSlot,
+    // the memory budget shared by all the caches of the bridge
+    #[serde(skip)]
+    cache_budget: Arc<CacheBudget>,
+    // the on-disk cache used instead of the in-memory cache for a TRANSIENT_LOCAL topic matching the "persistent_caches" option.
+    // It's owned by the RoutesMgr, to be kept and served to remote bridges when the route is deactivated or removed.
+    #[serde(
+        rename = "persistent_cache",
+        serialize_with = "serialize_option_as_bool"
+    )]
+    persistent_cache: Option<Arc<InstanceCache>>,
+    // if the routing to Zenoh is suspended because a remote Publisher has a higher ownership strength
+    #[serde(serialize_with = "serialize_atomic_bool")]
+    ownership_suspended: Arc<AtomicBool>,
+    // the local nodes served by this route with a Writer QoS conflicting with the DDS Reader's QoS
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    qos_conflicts: HashMap<String, RouteStatus>,
 }
 
 impl Drop for RoutePublisher<'_> {
     fn drop(&mut self) {
-        if let Err(e) = delete_dds_entity(self.dds_reader) {
+        if let Err(e) = delete_dds_reader(self.dds_reader, self.dds_reader_listener_arg.take()) {
             log::warn!("{}: error deleting DDS Reader:  {}", self, e);
         }
     }
@@ -101,68 +164,162 @@ impl RoutePublisher<'_> {
         type_info: &Option<Arc<TypeInfo>>,
         keyless: bool,
         reader_qos: Qos,
+        persistent_cache: Option<Arc<InstanceCache>>,
+        cache_budget: Arc<CacheBudget>,
     ) -> Result<RoutePublisher<'a>, String> {
         let transient_local = is_transient_local(&reader_qos);
         log::debug!(
             "Route Publisher ({ros2_name} -> {zenoh_key_expr}): creation with type {ros2_type}"
         );
 
-        // declare the zenoh key expression (for wire optimization)
-        let declared_ke = zsession
-            .declare_keyexpr(zenoh_key_expr.clone())
-            .res()
-            .await
-            .map_err(|e| {
-                format!("Route Publisher ({ros2_name} -> {zenoh_key_expr}): failed to declare KeyExpr: {e}")
-            })?;
-
-        // CongestionControl to be used when re-publishing over zenoh: Blocking if Writer is RELIABLE (since we don't know what is remote Reader's QoS)
-        let congestion_ctrl = match (
-            config.reliable_routes_blocking,
-            is_reader_reliable(&reader_qos.reliability),
-        ) {
-            (true, true) => CongestionControl::Block,
-            _ => CongestionControl::Drop,
-        };
-
-        let topic_name = format!("rt{ros2_name}");
-        let type_name = ros2_message_type_to_dds_type(&ros2_type);
-        let read_period = get_read_period(&config, &zenoh_key_expr);
-
-        // create matching DDS Reader that forwards data coming from DDS to Zenoh
-        let dds_reader = create_forwarding_dds_reader(
+        let dds_reader_status = DdsStatusRecorder::new(format!(
+            "Route Publisher (ROS:{ros2_name} -> Zenoh:{zenoh_key_expr}): DDS Reader"
+        ));
+        // CongestionControl to be used when re-publishing over zenoh: Blocking if the DDS Reader is RELIABLE
+        // (until the QoS of the remote routes' Readers are known)
+        let congestion_blocking = Arc::new(AtomicBool::new(
+            config.reliable_routes_blocking && is_reader_reliable(&reader_qos.reliability),
+        ));
+        let ownership_suspended = Arc::new(AtomicBool::new(false));
+        let downsampling = Arc::new(DownsamplingFilter::default());
+        let instance_cache = InstanceCacheSlot::default();
+        let (dds_reader, dds_reader_listener_arg) = create_route_dds_reader(
+            &config,
+            zsession,
             participant,
-            topic_name,
-            type_name,
+            &ros2_name,
+            &ros2_type,
+            &zenoh_key_expr,
             type_info,
             keyless,
-            reader_qos.clone(),
-            declared_ke.clone(),
-            zsession.clone(),
-            read_period,
-            congestion_ctrl,
-        )?;
+            &reader_qos,
+            &congestion_blocking,
+            &ownership_suspended,
+            &downsampling,
+            &instance_cache,
+            &dds_reader_status,
+        )
+        .await?;
 
         Ok(RoutePublisher {
             ros2_name,
             ros2_type,
             dds_reader,
+            dds_reader_listener_arg,
+            reader_qos,
+            dds_reader_status,
             zenoh_key_expr,
             zsession,
             config,
             zenoh_publisher: None,
+            retained_publisher: None,
             transient_local,
             keyless,
             liveliness_token: None,
+            announced_qos: None,
             remote_routes: HashSet::new(),
+            remote_min_separations: HashMap::new(),
+            downsampling,
             local_nodes: HashSet::new(),
+            local_ownership_strengths: HashMap::new(),
+            remote_ownership_strengths: HashMap::new(),
+            congestion_blocking,
+            remote_reliabilities: HashMap::new(),
+            instance_cache,
+            cache_budget,
+            persistent_cache,
+            ownership_suspended,
+            qos_conflicts: HashMap::new(),
         })
     }
 
-    async fn activate<'a>(
+    // Re-create the DDS Reader with a new QoS (e.g. to match a new local Writer with conflicting QoS).
+    // Note: the zenoh side of the route is unchanged.
+    pub async fn recreate_dds_reader(
+        &mut self,
+        participant: dds_entity_t,
+        reader_qos: Qos,
+    ) -> Result<(), String> {
+        log::debug!("{self}: re-create DDS Reader with QoS {reader_qos:?}");
+        self.replace_dds_reader(participant, reader_qos).await?;
+        self.qos_conflicts.clear();
+        Ok(())
+    }
+
+    // Update the route with a new config: the CongestionControl might change with "reliable_routes_blocking",
+    // and the DDS Reader is re-created (with the same QoS) if its read period changes with "pub_max_frequencies"
+    pub async fn update_config(
+        &mut self,
+        participant: dds_entity_t,
+        config: Arc<Config>,
+    ) -> Result<(), String> {
+        let read_period_changed = get_read_period(&self.config, &self.zenoh_key_expr)
+            != get_read_period(&config, &self.zenoh_key_expr);
+        self.config = config;
+        self.update_congestion_control();
+        if read_period_changed {
+            log::debug!("{self}: re-create DDS Reader with a new read period");
+            self.replace_dds_reader(participant, self.reader_qos.clone())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn replace_dds_reader(
+        &mut self,
+        participant: dds_entity_t,
+        reader_qos: Qos,
+    ) -> Result<(), String> {
+        let (dds_reader, dds_reader_listener_arg) = create_route_dds_reader(
+            &self.config,
+            self.zsession,
+            participant,
+            &self.ros2_name,
+            &self.ros2_type,
+            &self.zenoh_key_expr,
+            &None,
+            self.keyless,
+            &reader_qos,
+            &self.congestion_blocking,
+            &self.ownership_suspended,
+            &self.downsampling,
+            &self.instance_cache,
+            &self.dds_reader_status,
+        )
+        .await?;
+        if let Err(e) = delete_dds_reader(self.dds_reader, self.dds_reader_listener_arg.take()) {
+            log::warn!("{}: error deleting DDS Reader:  {}", self, e);
+        }
+        self.dds_reader = dds_reader;
+        self.dds_reader_listener_arg = dds_reader_listener_arg;
+        self.reader_qos = reader_qos;
+        self.update_congestion_control();
+        Ok(())
+    }
+
+    #[inline]
+    pub fn ros2_name(&self) -> &str {
+        &self.ros2_name
+    }
+
+    #[inline]
+    pub fn dds_reader_qos(&self) -> &Qos {
+        &self.reader_qos
+    }
+
+    #[inline]
+    pub fn set_qos_conflict(&mut self, node: String, reason: String) {
+        self.qos_conflicts
+            .insert(node, RouteStatus::QoSConflict(reason));
+    }
+
+    // Activate the route. The LivelinessToken announcing this route to other plugins is declared only
+    // if `declare_token` is true (i.e. not for a route that is part of an Action route).
+    pub async fn activate<'a>(
         &'a mut self,
         plugin_id: &keyexpr,
         discovered_writer_qos: &Qos,
+        declare_token: bool,
     ) -> Result<(), String> {
         // For lifetime issue, redeclare the zenoh key expression that can't be stored in Self
         let declared_ke = self
@@ -178,51 +335,50 @@ impl RoutePublisher<'_> {
             })?;
 
         // create the zenoh Publisher
-        // if Reader is TRANSIENT_LOCAL, use a PublicationCache to store historical data
-        self.zenoh_publisher = if self.transient_local {
-            #[allow(non_upper_case_globals)]
+        // if Reader is TRANSIENT_LOCAL, use a cache to store historical data:
+        //  - for a topic matching the "persistent_caches" option, the on-disk cache (already served to remote bridges)
+        //  - otherwise, an InstanceCache storing history.depth samples per instance
+        //    (multiplied by "transient_local_cache_multiplier" for a keyless topic)
+        // if the cache was retained after the last local node left, re-use it with its historical data
+        self.zenoh_publisher = if let Some((publisher, _)) = self.retained_publisher.take() {
+            log::debug!("{self}: re-use the retained cache of TRANSIENT_LOCAL publications");
+            Some(publisher)
+        } else if let Some(cache) = &self.persistent_cache {
+            log::debug!("{self}: caching TRANSIENT_LOCAL publications via an on-disk cache");
+            *self.instance_cache.write().unwrap() = Some(cache.clone());
+            Some(ZPublisher::Publisher(declared_ke.clone()))
+        } else if self.transient_local {
             let history_qos = get_history_or_default(discovered_writer_qos);
-            let durability_service_qos = get_durability_service_or_default(discovered_writer_qos);
-            let mut history = match (history_qos.kind, history_qos.depth) {
-                (HistoryKind::KEEP_LAST, n) => {
-                    if self.keyless {
-                        // only 1 instance => history=n
-                        n as usize
-                    } else if durability_service_qos.max_instances == DDS_LENGTH_UNLIMITED {
-                        // No limit! => history=MAX
-                        usize::MAX
-                    } else if durability_service_qos.max_instances > 0 {
-                        // Compute cache size as history.depth * durability_service.max_instances
-                        // This makes the assumption that the frequency of publication is the same for all instances...
-                        // But as we have no way to have 1 cache per-instance, there is no other choice.
-                        n.saturating_mul(durability_service_qos.max_instances) as usize
-                    } else {
-                        n as usize
-                    }
-                }
+            let mut depth = match (history_qos.kind, history_qos.depth) {
+                (HistoryKind::KEEP_LAST, n) => n.max(1) as usize,
                 (HistoryKind::KEEP_ALL, _) => usize::MAX,
             };
-            // In case there are several Writers served by this route, increase the cache size
-            history = history.saturating_mul(self.config.transient_local_cache_multiplier);
+            // for a keyless topic (only 1 instance), in case there are several Writers served by this route, increase the cache size
+            if self.keyless {
+                depth = depth.saturating_mul(self.config.transient_local_cache_multiplier);
+            }
+            let limits = CacheLimits::new(
+                depth,
+                usize::MAX,
+                get_cache_max_bytes(&self.config, &self.zenoh_key_expr),
+            );
             log::debug!(
-                "{self}: caching TRANSIENT_LOCAL publications via a PublicationCache with history={history} (computed from Reader's QoS: history=({:?},{}), durability_service.max_instances={})",
-                history_qos.kind, history_qos.depth, durability_service_qos.max_instances
+                "{self}: caching TRANSIENT_LOCAL publications via an InstanceCache with {limits:?} (computed from Reader's QoS: history=({:?},{}))",
+                history_qos.kind, history_qos.depth
             );
-            let pub_cache = self
-                .zsession
-                .declare_publication_cache(&declared_ke)
-                .history(history)
-                .queryable_prefix(*KE_PREFIX_PUB_CACHE / plugin_id)
-                .queryable_allowed_origin(Locality::Remote) // Note: don't reply to queries from local QueryingSubscribers
-                .res()
-                .await
-                .map_err(|e| {
-                    format!(
-                        "Failed create PublicationCache for key {} (rid={}): {e}",
-                        self.zenoh_key_expr, declared_ke
-                    )
-                })?;
-            Some(ZPublisher::PublicationCache(pub_cache))
+            let cache = InstanceCache::new(
+                self.zenoh_key_expr.clone(),
+                limits,
+                self.cache_budget.clone(),
+            );
+            let (subscriber, queryable) = cache
+                .declare(
+                    self.zsession,
+                    *KE_PREFIX_PUB_CACHE / plugin_id / &self.zenoh_key_expr,
+                )
+                .await?;
+            *self.instance_cache.write().unwrap() = Some(cache);
+            Some(ZPublisher::InstanceCache(subscriber, queryable))
         } else {
             if let Err(e) = self
                 .zsession
@@ -240,14 +396,29 @@ impl RoutePublisher<'_> {
             Some(ZPublisher::Publisher(declared_ke.clone()))
         };
 
-        // create associated LivelinessToken
+        if !declare_token {
+            return Ok(());
+        }
+        self.declare_liveliness_token(plugin_id, discovered_writer_qos.clone())
+            .await
+    }
+
+    // Declare the LivelinessToken announcing this route to other plugins, with the specified QoS
+    async fn declare_liveliness_token(
+        &mut self,
+        plugin_id: &keyexpr,
+        qos: Qos,
+    ) -> Result<(), String> {
+        // drop the previous token (if any) before declaring a new one
+        self.liveliness_token = None;
         let liveliness_ke = new_ke_liveliness_pub(
             plugin_id,
             &self.zenoh_key_expr,
             &self.ros2_type,
             self.keyless,
-            &discovered_writer_qos,
+            &qos,
         )?;
+        self.announced_qos = Some(qos);
         let ros2_name = self.ros2_name.clone();
         self.liveliness_token = Some(self.zsession
             .liveliness()
@@ -263,12 +434,88 @@ impl RoutePublisher<'_> {
         Ok(())
     }
 
-    fn deactivate(&mut self) {
+    pub fn deactivate(&mut self) {
         log::debug!("{self} deactivate");
         // Drop Zenoh Publisher and Liveliness token
         // The DDS Writer remains to be discovered by local ROS nodes
         self.zenoh_publisher = None;
+        self.retained_publisher = None;
+        *self.instance_cache.write().unwrap() = None;
         self.liveliness_token = None;
+        self.announced_qos = None;
+    }
+
+    // Deactivate the route, but keep its cache queryable by remote bridges for the specified retention
+    // (in seconds, infinite meaning indefinitely). The cache is re-used if the route is re-activated.
+    fn retain(&mut self, retention: f32) {
+        let until = retention_deadline(retention);
+        log::debug!(
+            "{self} deactivate, retaining its cache {}",
+            if until.is_some() {
+                format!("for {retention}s")
+            } else {
+                "indefinitely".into()
+            }
+        );
+        // Drop Liveliness token only. The instance cache (if any) remains set, as no local Writer can push to it
+        self.retained_publisher = self.zenoh_publisher.take().map(|p| (p, until));
+        self.liveliness_token = None;
+        self.announced_qos = None;
+    }
+
+    // If the retention of the cache expired, deactivate the route. Return true if deactivated.
+    pub fn check_retention_expiry(&mut self, now: Instant) -> bool {
+        if let Some((_, Some(until))) = &self.retained_publisher {
+            if *until <= now {
+                log::debug!("{self}: retention of its cache expired");
+                self.deactivate();
+                return true;
+            }
+        }
+        false
+    }
+
+    #[inline]
+    pub fn add_remote_ownership_strength(&mut self, plugin_id: &str, strength: i32) {
+        self.remote_ownership_strengths
+            .insert(plugin_id.to_string(), strength);
+    }
+
+    #[inline]
+    pub fn remove_remote_ownership_strength(&mut self, plugin_id: &str) {
+        self.remote_ownership_strengths.remove(plugin_id);
+    }
+
+    // Update the Ownership arbitration with remote Publishers, after a change of local or remote ownership strengths:
+    //  - the liveliness token is re-declared if the highest strength of the local EXCLUSIVE Writers changed
+    //  - the routing to Zenoh is suspended if a remote Publisher has a higher strength
+    //    (or the same strength and a lower plugin id), so only the strongest source is routed via Zenoh
+    pub async fn update_ownership(&mut self, plugin_id: &keyexpr) -> Result<(), String> {
+        let local_strength = self.local_ownership_strengths.values().max().copied();
+        if let Some(announced_qos) = &self.announced_qos {
+            if local_strength.is_some()
+                && local_strength != get_exclusive_ownership_strength(announced_qos)
+            {
+                let mut qos = announced_qos.clone();
+                qos.ownership_strength = local_strength.map(|value| OwnershipStrength { value });
+                log::debug!("{self}: announce new ownership strength {local_strength:?}");
+                self.declare_liveliness_token(plugin_id, qos).await?;
+            }
+        }
+
+        let suspended = local_strength.map_or(false, |local_strength| {
+            is_outranked(local_strength, plugin_id, &self.remote_ownership_strengths)
+        });
+        if self.ownership_suspended.swap(suspended, Ordering::Relaxed) != suspended {
+            if suspended {
+                log::info!("{self}: routing suspended - a remote Publisher has a higher ownership strength");
+            } else {
+                log::info!(
+                    "{self}: routing resumed - no remote Publisher has a higher ownership strength"
+                );
+            }
+        }
+        Ok(())
     }
 
     #[inline]
@@ -285,9 +532,69 @@ impl RoutePublisher<'_> {
 
     #[inline]
     pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
-        self.remote_routes
-            .remove(&format!("{plugin_id}:{zenoh_key_expr}"));
+        let remote_route = format!("{plugin_id}:{zenoh_key_expr}");
+        self.remote_routes.remove(&remote_route);
+        self.remote_min_separations.remove(&remote_route);
+        self.remote_reliabilities.remove(&remote_route);
         log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+        self.update_downsampling();
+        self.update_congestion_control();
+    }
+
+    // Set if a remote route has a RELIABLE Reader
+    pub fn set_remote_reliability(
+        &mut self,
+        plugin_id: &str,
+        zenoh_key_expr: &keyexpr,
+        reliable: bool,
+    ) {
+        self.remote_reliabilities
+            .insert(format!("{plugin_id}:{zenoh_key_expr}"), reliable);
+        self.update_congestion_control();
+    }
+
+    // Use CongestionControl::Drop if all the remote routes have a BEST_EFFORT Reader,
+    // so a slow BEST_EFFORT remote Reader doesn't block the local RELIABLE Writers
+    fn update_congestion_control(&mut self) {
+        let blocking = self.config.reliable_routes_blocking
+            && is_reader_reliable(&self.reader_qos.reliability)
+            && !are_all_remote_routes_best_effort(&self.remote_routes, &self.remote_reliabilities);
+        if self.congestion_blocking.swap(blocking, Ordering::Relaxed) != blocking {
+            log::debug!(
+                "{self}: route with CongestionControl::{}",
+                if blocking { "Block" } else { "Drop" }
+            );
+        }
+    }
+
+    // Set the minimum separation between samples requested by a remote route (None if it requires all samples)
+    pub fn set_remote_min_separation(
+        &mut self,
+        plugin_id: &str,
+        zenoh_key_expr: &keyexpr,
+        min_separation: Option<Duration>,
+    ) {
+        let remote_route = format!("{plugin_id}:{zenoh_key_expr}");
+        match min_separation {
+            Some(min_separation) => {
+                self.remote_min_separations
+                    .insert(remote_route, min_separation);
+            }
+            None => {
+                self.remote_min_separations.remove(&remote_route);
+            }
+        }
+        self.update_downsampling();
+    }
+
+    fn update_downsampling(&mut self) {
+        let period = get_downsampling_period(&self.remote_routes, &self.remote_min_separations);
+        if self.downsampling.set_period(period) {
+            match period {
+                Some(period) => log::info!("{self}: downsampling with period {period:?}"),
+                None => log::info!("{self}: no more downsampling"),
+            }
+        }
     }
 
     #[inline]
@@ -302,11 +609,15 @@ impl RoutePublisher<'_> {
         plugin_id: &keyexpr,
         discovered_writer_qos: &Qos,
     ) {
+        if let Some(strength) = get_exclusive_ownership_strength(discovered_writer_qos) {
+            self.local_ownership_strengths
+                .insert(node.clone(), strength);
+        }
         self.local_nodes.insert(node);
         log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
         // if 1st local node added, activate the route
         if self.local_nodes.len() == 1 {
-            if let Err(e) = self.activate(plugin_id, discovered_writer_qos).await {
+            if let Err(e) = self.activate(plugin_id, discovered_writer_qos, true).await {
                 log::error!("{self} activation failed: {e}");
             }
         }
@@ -315,10 +626,17 @@ impl RoutePublisher<'_> {
     #[inline]
     pub fn remove_local_node(&mut self, node: &str) {
         self.local_nodes.remove(node);
+        self.local_ownership_strengths.remove(node);
+        self.qos_conflicts.remove(node);
         log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
         // if last local node removed, deactivate the route
+        // (but retain its cache if TRANSIENT_LOCAL and matching the "latch_retention" option)
         if self.local_nodes.is_empty() {
-            self.deactivate();
+            let has_cache = matches!(self.zenoh_publisher, Some(ZPublisher::InstanceCache(_, _)));
+            match get_latch_retention(&self.config, &self.zenoh_key_expr) {
+                Some(retention) if has_cache => self.retain(retention),
+                _ => self.deactivate(),
+            }
         }
     }
 
@@ -329,10 +647,149 @@ impl RoutePublisher<'_> {
 
     #[inline]
     pub fn is_unused(&self) -> bool {
-        !self.is_serving_local_node() && !self.is_serving_remote_route()
+        !self.is_serving_local_node()
+            && !self.is_serving_remote_route()
+            && self.retained_publisher.is_none()
     }
 }
 
+// Create the DDS Reader that forwards data coming from DDS to Zenoh for a route
+#[allow(clippy::too_many_arguments)]
+async fn create_route_dds_reader(
+    config: &Config,
+    zsession: &Arc<Session>,
+    participant: dds_entity_t,
+    ros2_name: &str,
+    ros2_type: &str,
+    zenoh_key_expr: &OwnedKeyExpr,
+    type_info: &Option<Arc<TypeInfo>>,
+    keyless: bool,
+    reader_qos: &Qos,
+    congestion_blocking: &Arc<AtomicBool>,
+    suspended: &Arc<AtomicBool>,
+    downsampling: &Arc<DownsamplingFilter>,
+    instance_cache: &InstanceCacheSlot,
+    status_recorder: &DdsStatusRecorder,
+) -> Result<(dds_entity_t, Option<ListenerArg>), String> {
+    // declare the zenoh key expression (for wire optimization)
+    let declared_ke = zsession
+        .declare_keyexpr(zenoh_key_expr.clone())
+        .res()
+        .await
+        .map_err(|e| {
+            format!(
+                "Route Publisher ({ros2_name} -> {zenoh_key_expr}): failed to declare KeyExpr: {e}"
+            )
+        })?;
+
+    let topic_name = format!("rt{ros2_name}");
+    let type_name = ros2_message_type_to_dds_type(ros2_type);
+    let read_period = get_read_period(config, zenoh_key_expr);
+
+    // create matching DDS Reader that forwards data coming from DDS to Zenoh
+    create_forwarding_dds_reader(
+        participant,
+        topic_name,
+        type_name,
+        type_info,
+        keyless,
+        reader_qos.clone(),
+        declared_ke,
+        zsession.clone(),
+        read_period,
+        congestion_blocking.clone(),
+        suspended.clone(),
+        downsampling.clone(),
+        instance_cache.clone(),
+        status_recorder,
+    )
+}
+
+fn serialize_downsampling_period<S>(d: &Arc<DownsamplingFilter>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match d.period() {
+        Some(period) => s.serialize_some(&period.as_secs_f64()),
+        None => s.serialize_none(),
+    }
+}
+
+fn serialize_instance_cache<S>(slot: &InstanceCacheSlot, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    use serde::ser::SerializeMap;
+    match slot.read().unwrap().as_ref() {
+        Some(cache) => {
+            let (samples, bytes) = cache.size();
+            let mut map = s.serialize_map(Some(2))?;
+            map.serialize_entry("samples", &samples)?;
+            map.serialize_entry("bytes", &bytes)?;
+            map.end()
+        }
+        None => s.serialize_none(),
+    }
+}
+
+// Return true if a remote Publisher has a higher ownership strength than the local one,
+// or the same strength and a lower plugin id (so all the bridges elect the same source)
+fn is_outranked(
+    local_strength: i32,
+    plugin_id: &str,
+    remote_strengths: &HashMap<String, i32>,
+) -> bool {
+    remote_strengths.iter().any(|(remote_id, remote_strength)| {
+        *remote_strength > local_strength
+            || (*remote_strength == local_strength && remote_id.as_str() < plugin_id)
+    })
+}
+
+// Return true if all the remote routes are known to have a BEST_EFFORT Reader (false if there are no remote routes)
+fn are_all_remote_routes_best_effort(
+    remote_routes: &HashSet<String>,
+    remote_reliabilities: &HashMap<String, bool>,
+) -> bool {
+    !remote_routes.is_empty()
+        && remote_routes
+            .iter()
+            .all(|r| remote_reliabilities.get(r) == Some(&false))
+}
+
+// Return the period to downsample the data routed to Zenoh to the highest rate required by the remote routes:
+// only if all of them requested a minimum separation between samples
+fn get_downsampling_period(
+    remote_routes: &HashSet<String>,
+    remote_min_separations: &HashMap<String, Duration>,
+) -> Option<Duration> {
+    if !remote_routes.is_empty()
+        && remote_routes
+            .iter()
+            .all(|r| remote_min_separations.contains_key(r))
+    {
+        remote_min_separations.values().min().copied()
+    } else {
+        None
+    }
+}
+
+// Return the retention in seconds of the cache if keyexpr matches one of the "latch_retention" option
+fn get_latch_retention(config: &Config, ke: &keyexpr) -> Option<f32> {
+    config
+        .latch_retention
+        .iter()
+        .find(|(re, _)| re.is_match(ke))
+        .map(|(_, retention)| *retention)
+}
+
+// Return the instant until which a cache is retained, or None if retained indefinitely
+// (i.e. for an infinite retention, or a retention too large to be represented)
+fn retention_deadline(retention: f32) -> Option<Instant> {
+    Duration::try_from_secs_f32(retention)
+        .ok()
+        .and_then(|d| Instant::now().checked_add(d))
+}
+
 // Return the read period if keyexpr matches one of the "pub_max_frequencies" option
 fn get_read_period(config: &Config, ke: &keyexpr) -> Option<Duration> {
     for (re, freq) in &config.pub_max_frequencies {
@@ -342,3 +799,124 @@ fn get_read_period(config: &Config, ke: &keyexpr) -> Option<Duration> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strengths(strengths: &[(&str, i32)]) -> HashMap<String, i32> {
+        strengths
+            .iter()
+            .map(|(id, strength)| (id.to_string(), *strength))
+            .collect()
+    }
+
+    #[test]
+    fn test_is_outranked() {
+        assert!(!is_outranked(10, "bridge_b", &strengths(&[])));
+        assert!(!is_outranked(
+            10,
+            "bridge_b",
+            &strengths(&[("bridge_a", 5)])
+        ));
+        assert!(is_outranked(
+            10,
+            "bridge_b",
+            &strengths(&[("bridge_a", 5), ("bridge_c", 20)])
+        ));
+        // with the same strength, the lowest plugin id wins
+        assert!(is_outranked(
+            10,
+            "bridge_b",
+            &strengths(&[("bridge_a", 10)])
+        ));
+        assert!(!is_outranked(
+            10,
+            "bridge_b",
+            &strengths(&[("bridge_c", 10)])
+        ));
+        // the remote bridge takes the same decision
+        assert!(!is_outranked(
+            10,
+            "bridge_a",
+            &strengths(&[("bridge_b", 10)])
+        ));
+        assert!(is_outranked(
+            10,
+            "bridge_c",
+            &strengths(&[("bridge_b", 10)])
+        ));
+    }
+
+    fn routes(routes: &[&str]) -> HashSet<String> {
+        routes.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn test_all_remote_routes_best_effort() {
+        let reliabilities: HashMap<String, bool> = [
+            ("bridge_a:chatter".to_string(), false),
+            ("bridge_b:chatter".to_string(), false),
+            ("bridge_c:chatter".to_string(), true),
+        ]
+        .into();
+        assert!(!are_all_remote_routes_best_effort(
+            &routes(&[]),
+            &reliabilities
+        ));
+        assert!(are_all_remote_routes_best_effort(
+            &routes(&["bridge_a:chatter", "bridge_b:chatter"]),
+            &reliabilities
+        ));
+        assert!(!are_all_remote_routes_best_effort(
+            &routes(&["bridge_a:chatter", "bridge_c:chatter"]),
+            &reliabilities
+        ));
+        // a remote route with an unknown reliability is considered as RELIABLE
+        assert!(!are_all_remote_routes_best_effort(
+            &routes(&["bridge_a:chatter", "bridge_d:chatter"]),
+            &reliabilities
+        ));
+    }
+
+    #[test]
+    fn test_downsampling_period() {
+        let separations: HashMap<String, Duration> = [
+            ("bridge_a:chatter".to_string(), Duration::from_millis(100)),
+            ("bridge_b:chatter".to_string(), Duration::from_millis(50)),
+        ]
+        .into();
+        assert_eq!(get_downsampling_period(&routes(&[]), &separations), None);
+        assert_eq!(
+            get_downsampling_period(&routes(&["bridge_a:chatter"]), &HashMap::new()),
+            None
+        );
+        // the highest rate requested by the remote routes
+        assert_eq!(
+            get_downsampling_period(
+                &routes(&["bridge_a:chatter", "bridge_b:chatter"]),
+                &separations
+            ),
+            Some(Duration::from_millis(50))
+        );
+        // no downsampling if a remote route requires all the samples
+        assert_eq!(
+            get_downsampling_period(
+                &routes(&["bridge_a:chatter", "bridge_b:chatter", "bridge_c:chatter"]),
+                &separations
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_retention_deadline() {
+        let now = Instant::now();
+        let deadline = retention_deadline(600.0).unwrap();
+        assert!(deadline >= now + Duration::from_secs(600));
+        assert!(retention_deadline(f32::INFINITY).is_none());
+        // a retention too large for an Instant is indefinite
+        assert!(retention_deadline(1e20).is_none());
+        assert!(retention_deadline(f32::MAX).is_none());
+    }
+}
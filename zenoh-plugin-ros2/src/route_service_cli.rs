@@ -0,0 +1,1110 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use cyclors::dds_entity_t;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant};
+use std::{convert::TryInto, fmt};
+use zenoh::buffers::ZBuf;
+use zenoh::liveliness::LivelinessToken;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::*;
+use zenoh::query::Reply;
+use zenoh_core::{zread, zwrite};
+
+use crate::config::{ServiceSelectionPolicy, DEFAULT_ACTION_GET_RESULT_TIMEOUT};
+use crate::dds_discovery::*;
+use crate::gid::Gid;
+use crate::liveliness_mgt::new_ke_liveliness_service_cli;
+use crate::qos_helpers::ros2_service_default_qos;
+use crate::ros2_utils::{
+    ros2_service_type_to_reply_dds_type, ros2_service_type_to_request_dds_type,
+    ROS2_ACTION_CANCEL_ERROR_REJECTED, ROS2_ACTION_GOAL_STATUS_ABORTED,
+};
+use crate::{
+    ke_for_sure, serialize_atomic_bool, Config, LOG_PAYLOAD, SELECTOR_PARAM_PLUGIN_ID,
+    SELECTOR_PARAM_TIMEOUT,
+};
+
+// The size of the zero-filled payload of the default reply sent to a local Client when no reply is received
+// from Zenoh. It's large enough to be deserialized by most Service and Action types as a reply with default values
+// (i.e. zero numbers, false booleans, empty strings and empty sequences).
+// Limitation: the reply type is unknown to the bridge (the TypeInfo of the DDS entities only contains type hashes),
+// so a reply whose fixed-size fields (e.g. large arrays) exceed this size can't be deserialized by the Client,
+// that will then ignore the default reply and wait forever.
+const DEFAULT_REPLY_PAYLOAD_SIZE: usize = 1024;
+
+// The maximum number of goals tracked by an Action Client route. A goal is forgotten when its result is received,
+// or when this maximum is reached (the oldest one first), e.g. if the local Action Client never requests its result.
+const MAX_TRACKED_GOALS: usize = 1024;
+
+// The remote Action Server that accepted a goal
+#[derive(Debug, Clone)]
+pub(crate) struct GoalServer {
+    // the id of the remote bridge serving the Action Server: the one the send_goal request was addressed to,
+    // or else the one with the replier's zenoh id (if known when the reply was received)
+    pub plugin_id: Option<OwnedKeyExpr>,
+    // the zenoh id of the remote bridge that replied to the send_goal request
+    pub zid: ZenohId,
+    // the order in which the goal was accepted (the lowest is the oldest)
+    accepted_seq: u64,
+    // the get_result requests for this goal that are still waiting for a reply
+    pending_get_results: Vec<Weak<PendingRequest>>,
+}
+
+impl GoalServer {
+    // Return true if the goal was accepted by an Action Server served by the specified remote bridge
+    pub fn is_served_by(&self, plugin_id: &keyexpr, zid: &Option<ZenohId>) -> bool {
+        self.plugin_id.as_deref() == Some(plugin_id) || zid.as_ref() == Some(&self.zid)
+    }
+
+    // Send a default reply (i.e. with STATUS_ABORTED) to the get_result requests still waiting for a reply
+    pub fn fail_pending_get_results(&self, reason: &str) {
+        for pending in self.pending_get_results.iter().filter_map(Weak::upgrade) {
+            pending.fail(reason);
+        }
+    }
+}
+
+// The zenoh id of each remote bridge, indexed by plugin_id
+pub(crate) type RemoteBridgesZid = Arc<RwLock<HashMap<OwnedKeyExpr, ZenohId>>>;
+
+// The remote Action Servers that accepted the goals sent by the local Action Clients of an Action Client route
+#[derive(Clone)]
+pub(crate) struct GoalsMap {
+    // the remote Action Server that accepted each goal, indexed by goal_id
+    servers: Arc<RwLock<HashMap<[u8; 16], GoalServer>>>,
+    // the zenoh ids of the remote bridges, to find the bridge serving an Action Server from the replier's zenoh id
+    bridges_zid: RemoteBridgesZid,
+    // the sequence number of the next accepted goal
+    next_seq: Arc<AtomicU64>,
+}
+
+impl GoalsMap {
+    pub fn new(bridges_zid: RemoteBridgesZid) -> GoalsMap {
+        GoalsMap {
+            servers: Arc::new(RwLock::new(HashMap::new())),
+            bridges_zid,
+            next_seq: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Record the remote Action Server that accepted a goal, forgetting the oldest goal if too many are tracked
+    fn record(&self, goal_id: [u8; 16], plugin_id: Option<OwnedKeyExpr>, zid: ZenohId) {
+        let plugin_id = plugin_id.or_else(|| self.find_bridge(&zid));
+        let mut servers = zwrite!(self.servers);
+        if servers.len() >= MAX_TRACKED_GOALS && !servers.contains_key(&goal_id) {
+            if let Some(oldest) = servers
+                .iter()
+                .min_by_key(|(_, server)| server.accepted_seq)
+                .map(|(id, _)| *id)
+            {
+                log::debug!(
+                    "More than {MAX_TRACKED_GOALS} goals tracked - forget the oldest: {}",
+                    hex::encode(oldest)
+                );
+                servers.remove(&oldest);
+            }
+        }
+        servers.insert(
+            goal_id,
+            GoalServer {
+                plugin_id,
+                zid,
+                accepted_seq: self.next_seq.fetch_add(1, Ordering::Relaxed),
+                pending_get_results: Vec::new(),
+            },
+        );
+    }
+
+    // Return the id of the remote bridge with the specified zenoh id (if known)
+    fn find_bridge(&self, zid: &ZenohId) -> Option<OwnedKeyExpr> {
+        zread!(self.bridges_zid)
+            .iter()
+            .find(|(_, bridge_zid)| *bridge_zid == zid)
+            .map(|(plugin_id, _)| plugin_id.clone())
+    }
+
+    // Return the target of a request for a goal: the bridge serving the Action Server that accepted it.
+    // None if the goal is unknown (not accepted, or forgotten).
+    fn target(&self, goal_id: &[u8; 16]) -> Option<RequestTarget> {
+        let servers = zread!(self.servers);
+        let server = servers.get(goal_id)?;
+        match server
+            .plugin_id
+            .clone()
+            .or_else(|| self.find_bridge(&server.zid))
+        {
+            Some(plugin_id) => Some(RequestTarget::Bridge(plugin_id)),
+            None => {
+                log::warn!(
+                    "Unknown bridge with zenoh id {} serving the Action Server that accepted goal {} - route the request to the best matching Action Server",
+                    server.zid,
+                    hex::encode(goal_id)
+                );
+                Some(RequestTarget::BestMatching)
+            }
+        }
+    }
+
+    fn remove(&self, goal_id: &[u8; 16]) {
+        zwrite!(self.servers).remove(goal_id);
+    }
+
+    fn add_pending_get_result(&self, goal_id: &[u8; 16], pending: &Arc<PendingRequest>) {
+        if let Some(server) = zwrite!(self.servers).get_mut(goal_id) {
+            server.pending_get_results.retain(|p| p.strong_count() > 0);
+            server.pending_get_results.push(Arc::downgrade(pending));
+        }
+    }
+
+    // Remove and return the goals accepted by the Action Servers served by the specified remote bridge
+    pub fn remove_goals_of_bridge(
+        &self,
+        plugin_id: &keyexpr,
+        zid: &Option<ZenohId>,
+    ) -> Vec<([u8; 16], GoalServer)> {
+        let mut removed = Vec::new();
+        zwrite!(self.servers).retain(|goal_id, server| {
+            if server.is_served_by(plugin_id, zid) {
+                removed.push((*goal_id, server.clone()));
+                false
+            } else {
+                true
+            }
+        });
+        removed
+    }
+}
+
+// For the routes that are part of an Action Client route: how the goal_id in requests is used
+// to select the remote Action Server
+#[derive(Clone)]
+pub(crate) enum GoalTracking {
+    // not part of an Action Client route
+    None,
+    // "send_goal" route: record the remote Action Server that accepted the goal
+    Record(GoalsMap),
+    // "cancel_goal" route: route the request to the remote Action Server that accepted the goal
+    Follow(GoalsMap),
+    // "get_result" route: same than Follow, and forget the goal once the result is received
+    FollowAndRelease(GoalsMap),
+}
+
+impl GoalTracking {
+    // Return the goal_id of the request, if tracked. It's a UUID following the CDR header and the request header.
+    fn goal_id(&self, dds_req_buf: &[u8]) -> Option<[u8; 16]> {
+        match self {
+            GoalTracking::None => None,
+            _ => dds_req_buf.get(20..36).map(|b| b.try_into().unwrap()),
+        }
+    }
+
+    fn on_reply(
+        &self,
+        goal_id: &[u8; 16],
+        zenoh_rep_buf: &[u8],
+        plugin_id: &Option<OwnedKeyExpr>,
+        replier_id: ZenohId,
+    ) {
+        match self {
+            // the SendGoal response starts with the "accepted" boolean, following the CDR header
+            GoalTracking::Record(goals) if zenoh_rep_buf.get(4) == Some(&1) => {
+                goals.record(*goal_id, plugin_id.clone(), replier_id);
+            }
+            GoalTracking::FollowAndRelease(goals) => goals.remove(goal_id),
+            _ => (),
+        }
+    }
+
+    fn on_request(&self, goal_id: &[u8; 16], pending: &Arc<PendingRequest>) {
+        if let GoalTracking::FollowAndRelease(goals) = self {
+            goals.add_pending_get_result(goal_id, pending);
+        }
+    }
+
+    fn on_no_reply(&self, goal_id: &[u8; 16]) {
+        if let GoalTracking::FollowAndRelease(goals) = self {
+            goals.remove(goal_id);
+        }
+    }
+
+    // Return the payload (without CDR header) of the default reply sent to a local Client when no reply is
+    // received from Zenoh. For an Action, the first field of the response reports the failure.
+    // For a Service, all the fields have their default value (e.g. "success" false for the usual Services).
+    fn default_reply_payload(&self) -> Vec<u8> {
+        let mut payload = vec![0u8; DEFAULT_REPLY_PAYLOAD_SIZE];
+        match self {
+            GoalTracking::None => (),
+            // SendGoal response: "accepted" is false
+            GoalTracking::Record(_) => (),
+            // CancelGoal response: "return_code" is ERROR_REJECTED
+            GoalTracking::Follow(_) => payload[0] = ROS2_ACTION_CANCEL_ERROR_REJECTED,
+            // GetResult response: "status" is STATUS_ABORTED
+            GoalTracking::FollowAndRelease(_) => payload[0] = ROS2_ACTION_GOAL_STATUS_ABORTED,
+        }
+        payload
+    }
+}
+
+// A request routed to Zenoh, owned by the reply callback of the query.
+// When dropped (i.e. the query is complete or timed out) and no reply was routed to the local Client,
+// a default reply reporting the failure is sent to the Client, so it doesn't wait forever.
+struct PendingRequest {
+    request_id: [u8; 16],
+    // the CDR header of the request, re-used for the default reply
+    cdr_header: [u8; 4],
+    goal_id: Option<[u8; 16]>,
+    goal_tracking: GoalTracking,
+    rep_writer: dds_entity_t,
+    route_id: String,
+    query_timeout: Duration,
+    replied: AtomicBool,
+    // the bridge selected by the nearest policy, whose latency is measured with this request
+    measured_bridge: Option<OwnedKeyExpr>,
+    latencies: ServerLatencies,
+    sent: Instant,
+}
+
+impl PendingRequest {
+    // Handle the failure of the request, if no reply was routed to the local Client yet:
+    // send it a default reply
+    fn fail(&self, reason: &str) {
+        if self.replied.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        if let Some(goal_id) = &self.goal_id {
+            self.goal_tracking.on_no_reply(goal_id);
+        }
+        let payload = self.goal_tracking.default_reply_payload();
+        log::warn!(
+            "{}: {reason} for request {} - send a default reply to the Client",
+            self.route_id,
+            hex::encode(self.request_id)
+        );
+        let mut dds_rep_buf: Vec<u8> = Vec::with_capacity(payload.len() + 20);
+        dds_rep_buf.extend_from_slice(&self.cdr_header);
+        dds_rep_buf.extend_from_slice(&self.request_id);
+        dds_rep_buf.extend_from_slice(&payload);
+        if let Err(e) = dds_write(self.rep_writer, dds_rep_buf) {
+            log::warn!(
+                "{}: sending default reply for request {} to DDS failed: {e}",
+                self.route_id,
+                hex::encode(self.request_id)
+            );
+        }
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if !self.replied.load(Ordering::Relaxed) {
+            // the bridge selected by the nearest policy didn't reply: account the timeout as its latency
+            if let Some(plugin_id) = &self.measured_bridge {
+                self.latencies.record(plugin_id, self.query_timeout);
+            }
+            self.fail(&format!(
+                "no reply received from Zenoh (timeout: {:?})",
+                self.query_timeout
+            ));
+        }
+    }
+}
+
+// The target of a request routed to Zenoh
+#[derive(Debug, PartialEq)]
+enum RequestTarget {
+    // the best matching remote Server per Zenoh routing (i.e. when no remote bridge is known to serve the route)
+    BestMatching,
+    // the remote Server served by the bridge with the lowest latency
+    Nearest(OwnedKeyExpr),
+    // all the remote Servers, routing only the first reply if `first_only` is true
+    All { first_only: bool },
+    // the remote Server served by the bridge with the specified id
+    Bridge(OwnedKeyExpr),
+}
+
+// The selection of the remote Server(s) to which each request is routed, according to the selection policy
+#[derive(Clone)]
+struct ServerSelector {
+    policy: ServiceSelectionPolicy,
+    // the ids of the remote bridges serving this route (i.e. that announced a corresponding Server route)
+    remote_servers: Arc<RwLock<Vec<OwnedKeyExpr>>>,
+    // the index of the next remote bridge to select, for round_robin policy
+    next_index: Arc<AtomicUsize>,
+    // the latencies of the remote bridges, for nearest policy
+    latencies: ServerLatencies,
+    goal_tracking: GoalTracking,
+}
+
+impl ServerSelector {
+    // Select the target of a request. A request for a goal (cancel_goal or get_result) is routed to
+    // the Action Server that accepted the goal, whatever the policy.
+    fn select(&self, goal_id: &Option<[u8; 16]>) -> RequestTarget {
+        if let (
+            GoalTracking::Follow(goals) | GoalTracking::FollowAndRelease(goals),
+            Some(goal_id),
+        ) = (&self.goal_tracking, goal_id)
+        {
+            if goal_id == &[0u8; 16] {
+                // a cancel request for all goals: route it to all remote Action Servers
+                return RequestTarget::All { first_only: false };
+            }
+            if let Some(target) = goals.target(goal_id) {
+                return target;
+            }
+        }
+
+        match &self.policy {
+            ServiceSelectionPolicy::First => RequestTarget::All { first_only: true },
+            ServiceSelectionPolicy::Nearest => {
+                match self.latencies.nearest(&zread!(self.remote_servers)) {
+                    Some(plugin_id) => RequestTarget::Nearest(plugin_id),
+                    None => RequestTarget::BestMatching,
+                }
+            }
+            ServiceSelectionPolicy::Pinned(plugin_id) => RequestTarget::Bridge(plugin_id.clone()),
+            ServiceSelectionPolicy::RoundRobin => {
+                let remote_servers = zread!(self.remote_servers);
+                if remote_servers.is_empty() {
+                    RequestTarget::BestMatching
+                } else {
+                    let i = self.next_index.fetch_add(1, Ordering::Relaxed) % remote_servers.len();
+                    RequestTarget::Bridge(remote_servers[i].clone())
+                }
+            }
+        }
+    }
+}
+
+// The latencies of the remote bridges serving a Client route, measured from the requests routed
+// with the nearest policy (i.e. the time until the reply, or the timeout if no reply)
+#[derive(Clone, Default)]
+struct ServerLatencies(Arc<RwLock<HashMap<OwnedKeyExpr, Duration>>>);
+
+impl ServerLatencies {
+    // Record a latency, smoothed with the previous ones (as TCP does for its round-trip time)
+    fn record(&self, plugin_id: &OwnedKeyExpr, latency: Duration) {
+        let mut latencies = zwrite!(self.0);
+        match latencies.get_mut(plugin_id) {
+            Some(smoothed) => *smoothed = (*smoothed * 7 + latency) / 8,
+            None => {
+                latencies.insert(plugin_id.clone(), latency);
+            }
+        }
+    }
+
+    fn remove(&self, plugin_id: &str) {
+        zwrite!(self.0).retain(|id, _| id.as_str() != plugin_id);
+    }
+
+    // Return the remote bridge with the lowest latency, a bridge with no latency measured yet being selected first
+    fn nearest(&self, remote_servers: &[OwnedKeyExpr]) -> Option<OwnedKeyExpr> {
+        let latencies = zread!(self.0);
+        remote_servers
+            .iter()
+            .min_by_key(|id| latencies.get(*id).copied().unwrap_or_default())
+            .cloned()
+    }
+}
+
+// Return the selection policy if keyexpr matches one of the "service_selection" option (default: nearest)
+pub(crate) fn get_selection_policy(config: &Config, ke: &keyexpr) -> ServiceSelectionPolicy {
+    for (re, policy) in &config.service_selection {
+        if re.is_match(ke) {
+            return policy.clone();
+        }
+    }
+    ServiceSelectionPolicy::default()
+}
+
+// Return the timeout for Service calls if keyexpr matches one of the "service_timeouts" option (default: queries_timeout)
+pub(crate) fn get_service_timeout(config: &Config, ke: &keyexpr) -> Duration {
+    for (re, timeout) in &config.service_timeouts {
+        if re.is_match(ke) {
+            return Duration::from_secs_f32(*timeout);
+        }
+    }
+    config.queries_timeout
+}
+
+// Return the timeout for Action get_result calls if keyexpr matches one of the "action_get_result_timeouts" option
+pub(crate) fn get_action_get_result_timeout(config: &Config, ke: &keyexpr) -> Duration {
+    for (re, timeout) in &config.action_get_result_timeouts {
+        if re.is_match(ke) {
+            return Duration::from_secs_f32(*timeout);
+        }
+    }
+    Duration::from_secs_f32(DEFAULT_ACTION_GET_RESULT_TIMEOUT)
+}
+
+// a route for a Service Client exposed in Zenoh as a Querier
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize)]
+pub struct RouteServiceCli<'a> {
+    // the ROS2 Service name
+    ros2_name: String,
+    // the ROS2 type
+    ros2_type: String,
+    // the Zenoh key expression used for routing
+    zenoh_key_expr: OwnedKeyExpr,
+    // the zenoh session
+    #[serde(skip)]
+    zsession: &'a Arc<Session>,
+    // the config
+    #[serde(skip)]
+    config: Arc<Config>,
+    // if the route is active (i.e. serving at least 1 local Service Client).
+    // The requests are routed to Zenoh only while the route is active.
+    #[serde(serialize_with = "serialize_atomic_bool")]
+    is_active: Arc<AtomicBool>,
+    // the local DDS Reader created to receive Requests from the local Service Clients
+    #[serde(serialize_with = "serialize_entity_guid")]
+    req_reader: dds_entity_t,
+    // the argument of the DDS Reader's Listener, freed when the Reader is deleted
+    #[serde(skip)]
+    req_reader_listener_arg: Option<ListenerArg>,
+    // the local DDS Writer created to send Replies to the local Service Clients
+    #[serde(serialize_with = "serialize_entity_guid")]
+    rep_writer: dds_entity_t,
+    // the statuses reported by the DDS Reader and Writer (incompatible QoS, deadline missed, liveliness changed)
+    req_reader_status: DdsStatusRecorder,
+    rep_writer_status: DdsStatusRecorder,
+    // the timeout of the queries routing the requests to Zenoh (updatable on a configuration change)
+    #[serde(skip)]
+    query_timeout: Arc<RwLock<Duration>>,
+    // the policy used to select the remote Service Server(s) for each request
+    selection_policy: ServiceSelectionPolicy,
+    #[serde(skip)]
+    server_selector: ServerSelector,
+    // a liveliness token associated to this route, for announcement to other plugins
+    #[serde(skip)]
+    liveliness_token: Option<LivelinessToken<'a>>,
+    // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
+    remote_routes: HashSet<String>,
+    // the list of nodes served by this route
+    local_nodes: HashSet<String>,
+}
+
+impl Drop for RouteServiceCli<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = delete_dds_reader(self.req_reader, self.req_reader_listener_arg.take()) {
+            log::warn!("{}: error deleting DDS Reader:  {}", self, e);
+        }
+        if let Err(e) = delete_dds_entity(self.rep_writer) {
+            log::warn!("{}: error deleting DDS Writer:  {}", self, e);
+        }
+    }
+}
+
+impl fmt::Display for RouteServiceCli<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Route Service Client (ROS:{} <-> Zenoh:{})",
+            self.ros2_name, self.zenoh_key_expr
+        )
+    }
+}
+
+impl RouteServiceCli<'_> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create<'a>(
+        config: Arc<Config>,
+        zsession: &'a Arc<Session>,
+        participant: dds_entity_t,
+        ros2_name: String,
+        ros2_type: String,
+        zenoh_key_expr: OwnedKeyExpr,
+        type_info: &Option<Arc<TypeInfo>>,
+        query_timeout: Duration,
+        selection_policy: ServiceSelectionPolicy,
+        goal_tracking: GoalTracking,
+    ) -> Result<RouteServiceCli<'a>, String> {
+        log::debug!(
+            "Route Service Client ({ros2_name} <-> {zenoh_key_expr}): creation with type {ros2_type} (selection policy: {selection_policy})"
+        );
+
+        // Default Service QoS
+        let qos = ros2_service_default_qos();
+
+        // create DDS Writer to send replies coming from Zenoh to the Client
+        let rep_topic_name = format!("rr{ros2_name}Reply");
+        let rep_type_name = ros2_service_type_to_reply_dds_type(&ros2_type);
+        let rep_writer_status = DdsStatusRecorder::new(format!(
+            "Route Service Client (ROS:{ros2_name} <-> Zenoh:{zenoh_key_expr}): replies DDS Writer"
+        ));
+        let rep_writer = create_forwarding_dds_writer(
+            participant,
+            rep_topic_name,
+            rep_type_name,
+            true,
+            qos.clone(),
+            &rep_writer_status,
+        )?;
+
+        // create DDS Reader to receive requests and route them to Zenoh
+        let req_topic_name = format!("rq{ros2_name}Request");
+        let req_type_name = ros2_service_type_to_request_dds_type(&ros2_type);
+        let zenoh_key_expr2 = zenoh_key_expr.clone();
+        let zsession2 = zsession.clone();
+        let server_selector = ServerSelector {
+            policy: selection_policy.clone(),
+            remote_servers: Arc::new(RwLock::new(Vec::new())),
+            next_index: Arc::new(AtomicUsize::default()),
+            latencies: ServerLatencies::default(),
+            goal_tracking,
+        };
+        let server_selector2 = server_selector.clone();
+        let query_timeout = Arc::new(RwLock::new(query_timeout));
+        let query_timeout2 = query_timeout.clone();
+        let is_active = Arc::new(AtomicBool::new(false));
+        let is_active2 = is_active.clone();
+        let route_id = format!("Route Service Client (ROS:{ros2_name} <-> Zenoh:{zenoh_key_expr})");
+        let req_reader_status = DdsStatusRecorder::new(format!("{route_id}: requests DDS Reader"));
+        let (req_reader, req_reader_listener_arg) = match create_dds_reader(
+            participant,
+            req_topic_name,
+            req_type_name,
+            type_info,
+            true,
+            qos,
+            move |sample: &DDSRawSample| {
+                if !is_active2.load(Ordering::Relaxed) {
+                    log::warn!("{route_id}: route not active (no local Client discovered yet) - request dropped");
+                    return;
+                }
+                route_dds_request_to_zenoh(
+                    sample,
+                    &zenoh_key_expr2,
+                    &zsession2,
+                    *zread!(query_timeout2),
+                    &server_selector2,
+                    rep_writer,
+                    &route_id,
+                );
+            },
+            &req_reader_status,
+        ) {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = delete_dds_entity(rep_writer);
+                return Err(e);
+            }
+        };
+
+        Ok(RouteServiceCli {
+            ros2_name,
+            ros2_type,
+            zenoh_key_expr,
+            zsession,
+            config,
+            is_active,
+            req_reader,
+            req_reader_listener_arg: Some(req_reader_listener_arg),
+            rep_writer,
+            req_reader_status,
+            rep_writer_status,
+            query_timeout,
+            selection_policy,
+            server_selector,
+            liveliness_token: None,
+            remote_routes: HashSet::new(),
+            local_nodes: HashSet::new(),
+        })
+    }
+
+    // Activate the route. The LivelinessToken announcing this route to other plugins is declared only
+    // if `declare_token` is true (i.e. not for a route that is part of an Action route).
+    pub async fn activate(
+        &mut self,
+        plugin_id: &keyexpr,
+        declare_token: bool,
+    ) -> Result<(), String> {
+        log::debug!("{self} activate");
+        self.is_active.store(true, Ordering::Relaxed);
+        if declare_token {
+            // create associated LivelinessToken
+            let liveliness_ke =
+                new_ke_liveliness_service_cli(plugin_id, &self.zenoh_key_expr, &self.ros2_type)?;
+            let ros2_name = self.ros2_name.clone();
+            self.liveliness_token = Some(
+                self.zsession
+                    .liveliness()
+                    .declare_token(liveliness_ke)
+                    .res_async()
+                    .await
+                    .map_err(|e| {
+                        format!(
+                            "Failed create LivelinessToken associated to route for Service Client {ros2_name}: {e}"
+                        )
+                    })?,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn deactivate(&mut self) {
+        log::debug!("{self} deactivate");
+        // The DDS Reader and Writer remain to be discovered by local ROS nodes
+        self.is_active.store(false, Ordering::Relaxed);
+        self.liveliness_token = None;
+    }
+
+    // Update the route with a new config and the timeout of the queries routing the requests
+    pub fn update_config(&mut self, config: Arc<Config>, query_timeout: Duration) {
+        self.config = config;
+        *zwrite!(self.query_timeout) = query_timeout;
+    }
+
+    #[inline]
+    pub fn dds_reader_guid(&self) -> Result<Gid, String> {
+        get_guid(&self.req_reader)
+    }
+
+    #[inline]
+    pub fn dds_writer_guid(&self) -> Result<Gid, String> {
+        get_guid(&self.rep_writer)
+    }
+
+    #[inline]
+    pub fn add_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
+        self.remote_routes
+            .insert(format!("{plugin_id}:{zenoh_key_expr}"));
+        let mut remote_servers = zwrite!(self.server_selector.remote_servers);
+        if !remote_servers.iter().any(|id| id.as_str() == plugin_id) {
+            remote_servers.push(ke_for_sure!(plugin_id).to_owned());
+        }
+        drop(remote_servers);
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
+        self.remote_routes
+            .remove(&format!("{plugin_id}:{zenoh_key_expr}"));
+        zwrite!(self.server_selector.remote_servers).retain(|id| id.as_str() != plugin_id);
+        self.server_selector.latencies.remove(plugin_id);
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn is_serving_remote_route(&self) -> bool {
+        !self.remote_routes.is_empty()
+    }
+
+    #[inline]
+    pub async fn add_local_node(&mut self, node: String, plugin_id: &keyexpr) {
+        self.local_nodes.insert(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if 1st local node added, activate the route
+        if self.local_nodes.len() == 1 {
+            if let Err(e) = self.activate(plugin_id, true).await {
+                log::error!("{self} activation failed: {e}");
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove_local_node(&mut self, node: &str) {
+        self.local_nodes.remove(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if last local node removed, deactivate the route
+        if self.local_nodes.is_empty() {
+            self.deactivate();
+        }
+    }
+
+    #[inline]
+    pub fn is_serving_local_node(&self) -> bool {
+        !self.local_nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        !self.is_serving_local_node() && !self.is_serving_remote_route()
+    }
+}
+
+fn route_dds_request_to_zenoh(
+    sample: &DDSRawSample,
+    zenoh_key_expr: &OwnedKeyExpr,
+    zsession: &Arc<Session>,
+    query_timeout: Duration,
+    server_selector: &ServerSelector,
+    rep_writer: dds_entity_t,
+    route_id: &str,
+) {
+    use zenoh_core::SyncResolve;
+
+    // request payload is expected to be a CDR header (4 bytes) + the request header (16 bytes) + the serialized request
+    let zbuf: ZBuf = sample.into();
+    let dds_req_buf = zbuf.contiguous();
+    if dds_req_buf.len() < 20 {
+        log::warn!(
+            "{route_id}: received invalid request from DDS: {:02x?}",
+            dds_req_buf
+        );
+        return;
+    }
+
+    // the request header (client_guid and sequence_number) is kept to be re-used in the reply
+    let request_id: [u8; 16] = dds_req_buf[4..20].try_into().unwrap();
+
+    // route the request without the request header
+    let mut zenoh_req_buf: Vec<u8> = Vec::with_capacity(dds_req_buf.len() - 16);
+    zenoh_req_buf.extend_from_slice(&dds_req_buf[..4]);
+    zenoh_req_buf.extend_from_slice(&dds_req_buf[20..]);
+
+    if *LOG_PAYLOAD {
+        log::trace!(
+            "{route_id}: routing request {} from DDS to Zenoh - payload: {:02x?}",
+            hex::encode(request_id),
+            zenoh_req_buf
+        );
+    } else {
+        log::trace!(
+            "{route_id}: routing request {} from DDS to Zenoh - {} bytes",
+            hex::encode(request_id),
+            zenoh_req_buf.len()
+        );
+    }
+
+    // select the remote Server(s) to route the request to
+    let goal_id = server_selector.goal_tracking.goal_id(&dds_req_buf);
+    let target = server_selector.select(&goal_id);
+    log::trace!(
+        "{route_id}: request {} routed to {target:?}",
+        hex::encode(request_id)
+    );
+    // pass the query timeout via a selector parameter, for the Server route to wait the reply as long as this route
+    let selector = format!(
+        "{zenoh_key_expr}?{SELECTOR_PARAM_TIMEOUT}={}",
+        query_timeout.as_secs_f64()
+    );
+    let measured_bridge = match &target {
+        RequestTarget::Nearest(plugin_id) => Some(plugin_id.clone()),
+        _ => None,
+    };
+    let (selector, query_target, first_only, target_bridge) = match target {
+        RequestTarget::BestMatching => (selector, QueryTarget::BestMatching, false, None),
+        RequestTarget::All { first_only } => (selector, QueryTarget::All, first_only, None),
+        // address the query to the specified bridge via a selector parameter
+        // (the Server routes of other bridges will ignore it)
+        RequestTarget::Nearest(plugin_id) | RequestTarget::Bridge(plugin_id) => (
+            format!("{selector}&{SELECTOR_PARAM_PLUGIN_ID}={plugin_id}"),
+            QueryTarget::All,
+            false,
+            Some(plugin_id),
+        ),
+    };
+
+    let route_id2 = route_id.to_string();
+    let goal_tracking = server_selector.goal_tracking.clone();
+    let pending = Arc::new(PendingRequest {
+        request_id,
+        cdr_header: [dds_req_buf[0], dds_req_buf[1], 0, 0],
+        goal_id,
+        goal_tracking: goal_tracking.clone(),
+        rep_writer,
+        route_id: route_id.to_string(),
+        query_timeout,
+        replied: AtomicBool::new(false),
+        measured_bridge,
+        latencies: server_selector.latencies.clone(),
+        sent: Instant::now(),
+    });
+    if let Some(goal_id) = &goal_id {
+        goal_tracking.on_request(goal_id, &pending);
+    }
+    if let Err(e) = zsession
+        .get(selector.as_str())
+        .with_value(zenoh_req_buf)
+        .target(query_target)
+        .consolidation(ConsolidationMode::None)
+        .timeout(query_timeout)
+        .callback(move |reply| {
+            if let (Ok(_), Some(plugin_id)) = (&reply.sample, &pending.measured_bridge) {
+                pending.latencies.record(plugin_id, pending.sent.elapsed());
+            }
+            if reply.sample.is_ok() && pending.replied.swap(true, Ordering::Relaxed) && first_only {
+                log::trace!(
+                    "{route_id2}: ignoring reply for request {} from {} - only the first reply is routed",
+                    hex::encode(request_id),
+                    reply.replier_id
+                );
+                return;
+            }
+            route_zenoh_reply_to_dds(
+                reply,
+                &request_id,
+                &goal_id,
+                &goal_tracking,
+                &target_bridge,
+                rep_writer,
+                &route_id2,
+            )
+        })
+        .res_sync()
+    {
+        log::warn!(
+            "{route_id}: routing request {} from DDS to Zenoh failed: {e}",
+            hex::encode(request_id)
+        );
+    }
+}
+
+fn route_zenoh_reply_to_dds(
+    reply: Reply,
+    request_id: &[u8; 16],
+    goal_id: &Option<[u8; 16]>,
+    goal_tracking: &GoalTracking,
+    target_bridge: &Option<OwnedKeyExpr>,
+    rep_writer: dds_entity_t,
+    route_id: &str,
+) {
+    let replier_id = reply.replier_id;
+    match reply.sample {
+        Ok(sample) => {
+            // reply payload is expected to be a CDR header (4 bytes) followed by the serialized reply
+            let zenoh_rep_buf = sample.value.payload.contiguous();
+            if zenoh_rep_buf.len() < 4 {
+                log::warn!(
+                    "{route_id}: received invalid reply from Zenoh for request {}: {:02x?}",
+                    hex::encode(request_id),
+                    zenoh_rep_buf
+                );
+                return;
+            }
+
+            // route the reply with the original request header inserted after the CDR header
+            let mut dds_rep_buf: Vec<u8> = Vec::with_capacity(zenoh_rep_buf.len() + 16);
+            dds_rep_buf.extend_from_slice(&zenoh_rep_buf[..4]);
+            dds_rep_buf.extend_from_slice(request_id);
+            dds_rep_buf.extend_from_slice(&zenoh_rep_buf[4..]);
+
+            if *LOG_PAYLOAD {
+                log::trace!(
+                    "{route_id}: routing reply for request {} from Zenoh to DDS - payload: {:02x?}",
+                    hex::encode(request_id),
+                    dds_rep_buf
+                );
+            } else {
+                log::trace!(
+                    "{route_id}: routing reply for request {} from Zenoh to DDS - {} bytes",
+                    hex::encode(request_id),
+                    dds_rep_buf.len()
+                );
+            }
+
+            if let Err(e) = dds_write(rep_writer, dds_rep_buf) {
+                log::warn!(
+                    "{route_id}: routing reply for request {} from Zenoh to DDS failed: {e}",
+                    hex::encode(request_id)
+                );
+            }
+
+            if let Some(goal_id) = goal_id {
+                goal_tracking.on_reply(goal_id, &zenoh_rep_buf, target_bridge, replier_id);
+            }
+        }
+        Err(val) => log::warn!(
+            "{route_id}: received error as reply for request {}: {}",
+            hex::encode(request_id),
+            String::try_from(&val).unwrap_or_else(|_| format!("{val:?}"))
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge(id: &str) -> OwnedKeyExpr {
+        OwnedKeyExpr::try_from(id).unwrap()
+    }
+
+    fn selector(policy: ServiceSelectionPolicy, goal_tracking: GoalTracking) -> ServerSelector {
+        ServerSelector {
+            policy,
+            remote_servers: Arc::new(RwLock::new(vec![bridge("robot_1"), bridge("robot_2")])),
+            next_index: Arc::new(AtomicUsize::new(0)),
+            latencies: ServerLatencies::default(),
+            goal_tracking,
+        }
+    }
+
+    // the SendGoal response with "accepted" true
+    const GOAL_ACCEPTED: [u8; 5] = [0, 1, 0, 0, 1];
+
+    #[test]
+    fn test_select_policy() {
+        let s = selector(ServiceSelectionPolicy::Nearest, GoalTracking::None);
+        assert_eq!(s.select(&None), RequestTarget::Nearest(bridge("robot_1")));
+        let s = selector(ServiceSelectionPolicy::First, GoalTracking::None);
+        assert_eq!(s.select(&None), RequestTarget::All { first_only: true });
+        let s = selector(
+            ServiceSelectionPolicy::Pinned(bridge("robot_2")),
+            GoalTracking::None,
+        );
+        assert_eq!(s.select(&None), RequestTarget::Bridge(bridge("robot_2")));
+
+        let s = selector(ServiceSelectionPolicy::RoundRobin, GoalTracking::None);
+        assert_eq!(s.select(&None), RequestTarget::Bridge(bridge("robot_1")));
+        assert_eq!(s.select(&None), RequestTarget::Bridge(bridge("robot_2")));
+        assert_eq!(s.select(&None), RequestTarget::Bridge(bridge("robot_1")));
+        zwrite!(s.remote_servers).clear();
+        assert_eq!(s.select(&None), RequestTarget::BestMatching);
+    }
+
+    #[test]
+    fn test_select_nearest() {
+        let s = selector(ServiceSelectionPolicy::Nearest, GoalTracking::None);
+        let nearest = |id: &str| RequestTarget::Nearest(bridge(id));
+
+        // the bridges with no latency measured yet are selected first
+        assert_eq!(s.select(&None), nearest("robot_1"));
+        s.latencies
+            .record(&bridge("robot_1"), Duration::from_millis(10));
+        assert_eq!(s.select(&None), nearest("robot_2"));
+        s.latencies
+            .record(&bridge("robot_2"), Duration::from_millis(5));
+        assert_eq!(s.select(&None), nearest("robot_2"));
+
+        // a timeout (i.e. no reply) is accounted as latency, smoothed with the previous ones
+        s.latencies
+            .record(&bridge("robot_2"), Duration::from_secs(1));
+        assert_eq!(
+            zread!(s.latencies.0).get(&bridge("robot_2")),
+            Some(&((Duration::from_millis(5) * 7 + Duration::from_secs(1)) / 8))
+        );
+        assert_eq!(s.select(&None), nearest("robot_1"));
+
+        // the latency of a bridge that left is forgotten
+        s.latencies.remove("robot_2");
+        assert_eq!(s.select(&None), nearest("robot_2"));
+        zwrite!(s.remote_servers).clear();
+        assert_eq!(s.select(&None), RequestTarget::BestMatching);
+    }
+
+    #[test]
+    fn test_select_goal_server() {
+        let zid_1 = ZenohId::rand();
+        let zid_2 = ZenohId::rand();
+        let bridges_zid: RemoteBridgesZid = Arc::new(RwLock::new(HashMap::from([
+            (bridge("robot_1"), zid_1),
+            (bridge("robot_2"), zid_2),
+        ])));
+        let goals = GoalsMap::new(bridges_zid.clone());
+        let send_goal = GoalTracking::Record(goals.clone());
+        let cancel_goal = selector(
+            ServiceSelectionPolicy::Nearest,
+            GoalTracking::Follow(goals.clone()),
+        );
+        let get_result = selector(
+            ServiceSelectionPolicy::RoundRobin,
+            GoalTracking::FollowAndRelease(goals.clone()),
+        );
+
+        // goal 1 sent to the nearest Action Server, accepted by robot_2's one
+        send_goal.on_reply(&[1u8; 16], &GOAL_ACCEPTED, &None, zid_2);
+        // goal 2 sent to robot_1's Action Server, accepted
+        send_goal.on_reply(&[2u8; 16], &GOAL_ACCEPTED, &Some(bridge("robot_1")), zid_1);
+        // goal 3 rejected
+        send_goal.on_reply(&[3u8; 16], &[0, 1, 0, 0, 0], &None, zid_1);
+
+        // the requests for a goal are routed to the Action Server that accepted it, whatever the policy
+        for _ in 0..2 {
+            assert_eq!(
+                cancel_goal.select(&Some([1u8; 16])),
+                RequestTarget::Bridge(bridge("robot_2"))
+            );
+            assert_eq!(
+                get_result.select(&Some([1u8; 16])),
+                RequestTarget::Bridge(bridge("robot_2"))
+            );
+            assert_eq!(
+                cancel_goal.select(&Some([2u8; 16])),
+                RequestTarget::Bridge(bridge("robot_1"))
+            );
+            assert_eq!(
+                get_result.select(&Some([2u8; 16])),
+                RequestTarget::Bridge(bridge("robot_1"))
+            );
+        }
+        // unknown goal: routed according to the policy
+        assert_eq!(
+            cancel_goal.select(&Some([3u8; 16])),
+            RequestTarget::Nearest(bridge("robot_1"))
+        );
+        // cancel of all goals: routed to all Action Servers
+        assert_eq!(
+            cancel_goal.select(&Some([0u8; 16])),
+            RequestTarget::All { first_only: false }
+        );
+
+        // the goal is forgotten once its result is received
+        get_result
+            .goal_tracking
+            .on_reply(&[2u8; 16], &[0, 1, 0, 0], &None, zid_1);
+        assert_eq!(
+            cancel_goal.select(&Some([2u8; 16])),
+            RequestTarget::Nearest(bridge("robot_1"))
+        );
+
+        // the goals of a bridge that left are removed
+        let removed = goals.remove_goals_of_bridge(&bridge("robot_2"), &Some(zid_2));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, [1u8; 16]);
+        assert_eq!(
+            cancel_goal.select(&Some([1u8; 16])),
+            RequestTarget::Nearest(bridge("robot_1"))
+        );
+    }
+
+    #[test]
+    fn test_default_reply_payload() {
+        let goals = GoalsMap::new(Arc::new(RwLock::new(HashMap::new())));
+        // default values for a Service
+        let payload = GoalTracking::None.default_reply_payload();
+        assert_eq!(payload, vec![0u8; DEFAULT_REPLY_PAYLOAD_SIZE]);
+        // "accepted" false for send_goal
+        let payload = GoalTracking::Record(goals.clone()).default_reply_payload();
+        assert_eq!(payload[0], 0);
+        // "return_code" ERROR_REJECTED for cancel_goal
+        let payload = GoalTracking::Follow(goals.clone()).default_reply_payload();
+        assert_eq!(payload[0], ROS2_ACTION_CANCEL_ERROR_REJECTED);
+        // "status" STATUS_ABORTED for get_result
+        let payload = GoalTracking::FollowAndRelease(goals).default_reply_payload();
+        assert_eq!(payload[0], ROS2_ACTION_GOAL_STATUS_ABORTED);
+    }
+
+    #[test]
+    fn test_goals_bound() {
+        let goals = GoalsMap::new(Arc::new(RwLock::new(HashMap::new())));
+        let zid = ZenohId::rand();
+        for i in 0..=MAX_TRACKED_GOALS {
+            let mut goal_id = [0u8; 16];
+            goal_id[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            goals.record(goal_id, Some(bridge("robot_1")), zid);
+        }
+        let servers = zread!(goals.servers);
+        assert_eq!(servers.len(), MAX_TRACKED_GOALS);
+        // the oldest goal was forgotten
+        assert!(!servers.contains_key(&[0u8; 16]));
+    }
+}
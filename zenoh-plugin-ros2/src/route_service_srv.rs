@@ -0,0 +1,571 @@
+//
+// Copyright (c) 2022 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+
+use cyclors::dds_entity_t;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use std::{convert::TryInto, fmt};
+use zenoh::buffers::ZBuf;
+use zenoh::liveliness::LivelinessToken;
+use zenoh::prelude::r#async::AsyncResolve;
+use zenoh::prelude::*;
+use zenoh::queryable::{Query, Queryable};
+use zenoh_core::{zread, zwrite};
+
+use crate::dds_discovery::*;
+use crate::gid::Gid;
+use crate::liveliness_mgt::new_ke_liveliness_service_srv;
+use crate::qos_helpers::ros2_service_default_qos;
+use crate::ros2_utils::{
+    ros2_service_type_to_reply_dds_type, ros2_service_type_to_request_dds_type,
+};
+use crate::{
+    serialize_option_as_bool, Config, LOG_PAYLOAD, SELECTOR_PARAM_PLUGIN_ID, SELECTOR_PARAM_TIMEOUT,
+};
+
+// a route for a Service Server exposed in Zenoh as a Queryable
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize)]
+pub struct RouteServiceSrv<'a> {
+    // the ROS2 Service name
+    ros2_name: String,
+    // the ROS2 type
+    ros2_type: String,
+    // the Zenoh key expression used for routing
+    zenoh_key_expr: OwnedKeyExpr,
+    // the zenoh session
+    #[serde(skip)]
+    zsession: &'a Arc<Session>,
+    // the config
+    #[serde(skip)]
+    config: Arc<Config>,
+    // the zenoh queryable used to expose the service server in zenoh.
+    // `None` when route is created on a remote announcement and no local ServiceSrv discovered yet
+    #[serde(rename = "is_active", serialize_with = "serialize_option_as_bool")]
+    zenoh_queryable: Option<Queryable<'a, ()>>,
+    // the local DDS Writer created to send Requests to the local Service Server
+    #[serde(serialize_with = "serialize_entity_guid")]
+    req_writer: dds_entity_t,
+    // the local DDS Reader created to receive Replies from the local Service Server
+    #[serde(serialize_with = "serialize_entity_guid")]
+    rep_reader: dds_entity_t,
+    // the argument of the DDS Reader's Listener, freed when the Reader is deleted
+    #[serde(skip)]
+    rep_reader_listener_arg: Option<ListenerArg>,
+    // the statuses reported by the DDS Writer and Reader (incompatible QoS, deadline missed, liveliness changed)
+    req_writer_status: DdsStatusRecorder,
+    rep_reader_status: DdsStatusRecorder,
+    // the client GUID used in each request
+    #[serde(skip)]
+    client_guid: u64,
+    // the sequence number used for the next request
+    #[serde(skip)]
+    sequence_number: Arc<AtomicU64>,
+    // the Zenoh queries waiting for a reply, indexed by request sequence number
+    #[serde(skip)]
+    queries_in_progress: Arc<RwLock<HashMap<u64, Query>>>,
+    // the time after which a query not replied by the local Service Server is dropped, if the remote Client route
+    // didn't pass its own timeout with the query. Shared with the queryable's callback to be updatable at runtime
+    #[serde(skip)]
+    query_timeout: Arc<RwLock<Duration>>,
+    // a liveliness token associated to this route, for announcement to other plugins
+    #[serde(skip)]
+    liveliness_token: Option<LivelinessToken<'a>>,
+    // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
+    remote_routes: HashSet<String>,
+    // the list of nodes served by this route
+    local_nodes: HashSet<String>,
+}
+
+impl Drop for RouteServiceSrv<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = delete_dds_entity(self.req_writer) {
+            log::warn!("{}: error deleting DDS Writer:  {}", self, e);
+        }
+        if let Err(e) = delete_dds_reader(self.rep_reader, self.rep_reader_listener_arg.take()) {
+            log::warn!("{}: error deleting DDS Reader:  {}", self, e);
+        }
+    }
+}
+
+impl fmt::Display for RouteServiceSrv<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Route Service Server (ROS:{} <-> Zenoh:{})",
+            self.ros2_name, self.zenoh_key_expr
+        )
+    }
+}
+
+impl RouteServiceSrv<'_> {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create<'a>(
+        config: Arc<Config>,
+        zsession: &'a Arc<Session>,
+        participant: dds_entity_t,
+        ros2_name: String,
+        ros2_type: String,
+        zenoh_key_expr: OwnedKeyExpr,
+        type_info: &Option<Arc<TypeInfo>>,
+        query_timeout: Duration,
+    ) -> Result<RouteServiceSrv<'a>, String> {
+        log::debug!(
+            "Route Service Server ({ros2_name} <-> {zenoh_key_expr}): creation with type {ros2_type}"
+        );
+
+        // Default Service QoS
+        let qos = ros2_service_default_qos();
+
+        // create DDS Writer to send requests coming from Zenoh to the Service
+        let req_topic_name = format!("rq{ros2_name}Request");
+        let req_type_name = ros2_service_type_to_request_dds_type(&ros2_type);
+        let req_writer_status = DdsStatusRecorder::new(format!(
+            "Route Service Server (ROS:{ros2_name} <-> Zenoh:{zenoh_key_expr}): requests DDS Writer"
+        ));
+        let req_writer = create_forwarding_dds_writer(
+            participant,
+            req_topic_name,
+            req_type_name,
+            true,
+            qos.clone(),
+            &req_writer_status,
+        )?;
+
+        // client_guid used in requests: use the instance handle of the requests Writer, as rmw_cyclonedds does here:
+        // https://github.com/ros2/rmw_cyclonedds/blob/2263814fab142ac19dd3395971fb1f358d22a653/rmw_cyclonedds_cpp/src/rmw_node.cpp#L4848
+        let client_guid = match get_instance_handle(req_writer) {
+            Ok(guid) => guid,
+            Err(e) => {
+                let _ = delete_dds_entity(req_writer);
+                return Err(e);
+            }
+        };
+        log::debug!(
+            "Route Service Server ({ros2_name} <-> {zenoh_key_expr}): client_guid={client_guid:02x}"
+        );
+
+        let queries_in_progress: Arc<RwLock<HashMap<u64, Query>>> =
+            Arc::new(RwLock::new(HashMap::new()));
+
+        // create DDS Reader to receive replies and route them to Zenoh
+        let rep_topic_name = format!("rr{ros2_name}Reply");
+        let rep_type_name = ros2_service_type_to_reply_dds_type(&ros2_type);
+        let zenoh_key_expr2 = zenoh_key_expr.clone();
+        let queries_in_progress2 = queries_in_progress.clone();
+        let route_id = format!("Route Service Server (ROS:{ros2_name} <-> Zenoh:{zenoh_key_expr})");
+        let rep_reader_status = DdsStatusRecorder::new(format!("{route_id}: replies DDS Reader"));
+        let (rep_reader, rep_reader_listener_arg) = match create_dds_reader(
+            participant,
+            rep_topic_name,
+            rep_type_name,
+            type_info,
+            true,
+            qos,
+            move |sample: &DDSRawSample| {
+                route_dds_reply_to_zenoh(
+                    sample,
+                    &zenoh_key_expr2,
+                    client_guid,
+                    &queries_in_progress2,
+                    &route_id,
+                );
+            },
+            &rep_reader_status,
+        ) {
+            Ok(reader) => reader,
+            Err(e) => {
+                let _ = delete_dds_entity(req_writer);
+                return Err(e);
+            }
+        };
+
+        Ok(RouteServiceSrv {
+            ros2_name,
+            ros2_type,
+            zenoh_key_expr,
+            zsession,
+            config,
+            zenoh_queryable: None,
+            req_writer,
+            rep_reader,
+            rep_reader_listener_arg: Some(rep_reader_listener_arg),
+            req_writer_status,
+            rep_reader_status,
+            client_guid,
+            sequence_number: Arc::new(AtomicU64::default()),
+            queries_in_progress,
+            query_timeout: Arc::new(RwLock::new(query_timeout)),
+            liveliness_token: None,
+            remote_routes: HashSet::new(),
+            local_nodes: HashSet::new(),
+        })
+    }
+
+    // Activate the route. The LivelinessToken announcing this route to other plugins is declared only
+    // if `declare_token` is true (i.e. not for a route that is part of an Action route).
+    pub async fn activate(
+        &mut self,
+        plugin_id: &keyexpr,
+        declare_token: bool,
+    ) -> Result<(), String> {
+        log::debug!("{self} activate");
+        // Callback routing request received by Zenoh queryable to DDS Writer
+        let route_id = self.to_string();
+        let client_guid = self.client_guid;
+        let sequence_number = self.sequence_number.clone();
+        let queries_in_progress = self.queries_in_progress.clone();
+        let query_timeout = self.query_timeout.clone();
+        let req_writer = self.req_writer;
+        let plugin_id2 = plugin_id.to_owned();
+        self.zenoh_queryable = Some(
+            self.zsession
+                .declare_queryable(&self.zenoh_key_expr)
+                .complete(true)
+                .allowed_origin(Locality::Remote) // Allow only remote queries, local clients are directly served via DDS
+                .callback(move |query| {
+                    route_zenoh_request_to_dds(
+                        query,
+                        &route_id,
+                        &plugin_id2,
+                        client_guid,
+                        &sequence_number,
+                        &queries_in_progress,
+                        *zread!(query_timeout),
+                        req_writer,
+                    )
+                })
+                .res_async()
+                .await
+                .map_err(|e| format!("{self}: failed to create Queryable: {e}"))?,
+        );
+        if declare_token {
+            // create associated LivelinessToken
+            let liveliness_ke =
+                new_ke_liveliness_service_srv(plugin_id, &self.zenoh_key_expr, &self.ros2_type)?;
+            let ros2_name = self.ros2_name.clone();
+            self.liveliness_token = Some(
+                self.zsession
+                    .liveliness()
+                    .declare_token(liveliness_ke)
+                    .res_async()
+                    .await
+                    .map_err(|e| {
+                        format!(
+                            "Failed create LivelinessToken associated to route for Service Server {ros2_name}: {e}"
+                        )
+                    })?,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn deactivate(&mut self) {
+        log::debug!("{self} deactivate");
+        // Drop Zenoh Queryable and Liveliness token
+        // The DDS Writer and Reader remain to be discovered by local ROS nodes
+        self.zenoh_queryable = None;
+        self.liveliness_token = None;
+    }
+
+    // Update the route with a new config and the timeout after which the queries not replied are dropped
+    pub fn update_config(&mut self, config: Arc<Config>, query_timeout: Duration) {
+        self.config = config;
+        *zwrite!(self.query_timeout) = query_timeout;
+    }
+
+    #[inline]
+    pub fn dds_writer_guid(&self) -> Result<Gid, String> {
+        get_guid(&self.req_writer)
+    }
+
+    #[inline]
+    pub fn dds_reader_guid(&self) -> Result<Gid, String> {
+        get_guid(&self.rep_reader)
+    }
+
+    #[inline]
+    pub fn add_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
+        self.remote_routes
+            .insert(format!("{plugin_id}:{zenoh_key_expr}"));
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
+        self.remote_routes
+            .remove(&format!("{plugin_id}:{zenoh_key_expr}"));
+        log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
+    }
+
+    #[inline]
+    pub fn is_serving_remote_route(&self) -> bool {
+        !self.remote_routes.is_empty()
+    }
+
+    #[inline]
+    pub async fn add_local_node(&mut self, node: String, plugin_id: &keyexpr) {
+        self.local_nodes.insert(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if 1st local node added, activate the route
+        if self.local_nodes.len() == 1 {
+            if let Err(e) = self.activate(plugin_id, true).await {
+                log::error!("{self} activation failed: {e}");
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove_local_node(&mut self, node: &str) {
+        self.local_nodes.remove(node);
+        log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
+        // if last local node removed, deactivate the route
+        if self.local_nodes.is_empty() {
+            self.deactivate();
+        }
+    }
+
+    #[inline]
+    pub fn is_serving_local_node(&self) -> bool {
+        !self.local_nodes.is_empty()
+    }
+
+    #[inline]
+    pub fn is_unused(&self) -> bool {
+        !self.is_serving_local_node() && !self.is_serving_remote_route()
+    }
+}
+
+fn route_zenoh_request_to_dds(
+    query: Query,
+    route_id: &str,
+    plugin_id: &keyexpr,
+    client_guid: u64,
+    sequence_number: &AtomicU64,
+    queries_in_progress: &Arc<RwLock<HashMap<u64, Query>>>,
+    query_timeout: Duration,
+    req_writer: dds_entity_t,
+) {
+    use zenoh_core::SyncResolve;
+
+    // If the query is addressed to a specific bridge (via the selection policy of the remote Client route),
+    // ignore it if it's not this one
+    if let Some(target_id) = get_selector_param(query.parameters(), SELECTOR_PARAM_PLUGIN_ID) {
+        if target_id != plugin_id.as_str() {
+            log::trace!(
+                "{route_id}: ignoring query addressed to another bridge: {}",
+                query.selector()
+            );
+            return;
+        }
+    }
+
+    // Wait for the reply as long as the remote Client route (if it passed its timeout with the query)
+    let query_timeout = get_query_timeout(query.parameters()).unwrap_or(query_timeout);
+
+    // Get expected sequence number and increment it for next request
+    let n = sequence_number.fetch_add(1, Ordering::Relaxed);
+
+    // The request payload is expected to be a CDR header (4 bytes) followed by the serialized request.
+    // Insert the request header expected by rmw_cyclonedds (client_guid and sequence_number)
+    // between the CDR header and the payload, as per
+    // https://github.com/ros2/rmw_cyclonedds/blob/2263814fab142ac19dd3395971fb1f358d22a653/rmw_cyclonedds_cpp/src/serdata.hpp#L73
+    // Note: the header is encoded with the endianness specified in the CDR header
+    let dds_req_buf = match query.value() {
+        Some(value) => {
+            let zenoh_req_buf = value.payload.contiguous();
+            if zenoh_req_buf.len() < 4 {
+                log::warn!(
+                    "{route_id}: received invalid query with no CDR payload: {}",
+                    query.selector()
+                );
+                return;
+            }
+            let is_little_endian = zenoh_req_buf[1] & 1 != 0;
+            let mut buf: Vec<u8> = Vec::with_capacity(zenoh_req_buf.len() + 16);
+            buf.extend_from_slice(&zenoh_req_buf[..4]);
+            if is_little_endian {
+                buf.extend_from_slice(&client_guid.to_le_bytes());
+                buf.extend_from_slice(&n.to_le_bytes());
+            } else {
+                buf.extend_from_slice(&client_guid.to_be_bytes());
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+            buf.extend_from_slice(&zenoh_req_buf[4..]);
+            buf
+        }
+        None => {
+            log::warn!(
+                "{route_id}: received invalid query with no CDR payload: {}",
+                query.selector()
+            );
+            return;
+        }
+    };
+
+    if *LOG_PAYLOAD {
+        log::trace!(
+            "{route_id}: routing request #{n} from Zenoh to DDS - payload: {:02x?}",
+            dds_req_buf
+        );
+    } else {
+        log::trace!(
+            "{route_id}: routing request #{n} from Zenoh to DDS - {} bytes",
+            dds_req_buf.len()
+        );
+    }
+
+    // Store the query, to reply to it when the DDS reply arrives.
+    // If the Server doesn't reply before the timeout, drop it (the remote Client route no longer waits for it)
+    zwrite!(queries_in_progress).insert(n, query);
+    let queries_in_progress2 = queries_in_progress.clone();
+    let route_id2 = route_id.to_string();
+    async_std::task::spawn(async move {
+        async_std::task::sleep(query_timeout).await;
+        if zwrite!(queries_in_progress2).remove(&n).is_some() {
+            log::debug!(
+                "{route_id2}: no reply from DDS to request #{n} after {query_timeout:?} - query dropped"
+            );
+        }
+    });
+
+    if let Err(e) = dds_write(req_writer, dds_req_buf) {
+        log::warn!("{route_id}: routing request #{n} from Zenoh to DDS failed: {e}");
+        if let Some(query) = zwrite!(queries_in_progress).remove(&n) {
+            if let Err(e) = query
+                .reply(Err(format!("Failed to route request to DDS: {e}").into()))
+                .res_sync()
+            {
+                log::warn!("{route_id}: failed to send error reply to query: {e}");
+            }
+        }
+    }
+}
+
+// Return the value of a parameter of a query's selector
+fn get_selector_param<'a>(parameters: &'a str, name: &str) -> Option<&'a str> {
+    parameters
+        .split('&')
+        .find_map(|param| param.strip_prefix(name).and_then(|s| s.strip_prefix('=')))
+}
+
+// Return the timeout passed by the remote Client route with a query, if any and valid
+fn get_query_timeout(parameters: &str) -> Option<Duration> {
+    get_selector_param(parameters, SELECTOR_PARAM_TIMEOUT)
+        .and_then(|s| s.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+fn route_dds_reply_to_zenoh(
+    sample: &DDSRawSample,
+    zenoh_key_expr: &OwnedKeyExpr,
+    client_guid: u64,
+    queries_in_progress: &RwLock<HashMap<u64, Query>>,
+    route_id: &str,
+) {
+    use zenoh_core::SyncResolve;
+
+    // reply payload is expected to be a CDR header (4 bytes) + the request header (16 bytes) + the serialized reply
+    let zbuf: ZBuf = sample.into();
+    let dds_rep_buf = zbuf.contiguous();
+    if dds_rep_buf.len() < 20 {
+        log::warn!(
+            "{route_id}: received invalid reply from DDS: {:02x?}",
+            dds_rep_buf
+        );
+        return;
+    }
+
+    let is_little_endian = dds_rep_buf[1] & 1 != 0;
+    let (guid_bytes, seq_bytes): ([u8; 8], [u8; 8]) = (
+        dds_rep_buf[4..12].try_into().unwrap(),
+        dds_rep_buf[12..20].try_into().unwrap(),
+    );
+    let (guid, seq_num) = if is_little_endian {
+        (
+            u64::from_le_bytes(guid_bytes),
+            u64::from_le_bytes(seq_bytes),
+        )
+    } else {
+        (
+            u64::from_be_bytes(guid_bytes),
+            u64::from_be_bytes(seq_bytes),
+        )
+    };
+
+    if guid != client_guid {
+        log::trace!(
+            "{route_id}: ignoring reply from DDS for another client: {guid:02x} (not {client_guid:02x})"
+        );
+        return;
+    }
+
+    // route the reply without the request header
+    let mut zenoh_rep_buf: Vec<u8> = Vec::with_capacity(dds_rep_buf.len() - 16);
+    zenoh_rep_buf.extend_from_slice(&dds_rep_buf[..4]);
+    zenoh_rep_buf.extend_from_slice(&dds_rep_buf[20..]);
+
+    match zwrite!(queries_in_progress).remove(&seq_num) {
+        Some(query) => {
+            if *LOG_PAYLOAD {
+                log::trace!(
+                    "{route_id}: routing reply #{seq_num} from DDS to Zenoh - payload: {:02x?}",
+                    zenoh_rep_buf
+                );
+            } else {
+                log::trace!(
+                    "{route_id}: routing reply #{seq_num} from DDS to Zenoh - {} bytes",
+                    zenoh_rep_buf.len()
+                );
+            }
+            if let Err(e) = query
+                .reply(Ok(Sample::new(zenoh_key_expr.clone(), zenoh_rep_buf)))
+                .res_sync()
+            {
+                log::warn!("{route_id}: failed to route reply #{seq_num} to Zenoh: {e}");
+            }
+        }
+        None => log::warn!(
+            "{route_id}: received reply from DDS for an unknown query (#{seq_num}) - possibly already timed out"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_params() {
+        let params = "_timeout=3600&_plugin_id=robot_1";
+        assert_eq!(
+            get_selector_param(params, SELECTOR_PARAM_PLUGIN_ID),
+            Some("robot_1")
+        );
+        assert_eq!(get_selector_param("", SELECTOR_PARAM_PLUGIN_ID), None);
+        assert_eq!(get_query_timeout(params), Some(Duration::from_secs(3600)));
+        assert_eq!(
+            get_query_timeout("_timeout=0.5"),
+            Some(Duration::from_millis(500))
+        );
+        // no timeout, or an invalid one: the route's timeout is used
+        assert_eq!(get_query_timeout("_plugin_id=robot_1"), None);
+        assert_eq!(get_query_timeout("_timeout=-1"), None);
+        assert_eq!(get_query_timeout("_timeout=NaN"), None);
+        assert_eq!(get_query_timeout("_timeout=1e20"), None);
+    }
+}
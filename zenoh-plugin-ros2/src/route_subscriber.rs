@@ -12,27 +12,31 @@
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
 
-use cyclors::{
-    dds_entity_t, dds_get_entity_sertype, dds_strretcode, dds_writecdr, ddsi_serdata_from_ser_iov,
-    ddsi_serdata_kind_SDK_DATA, ddsi_sertype, ddsrt_iov_len_t, ddsrt_iovec_t,
-};
+use async_std::task;
+use cyclors::{dds_duration_t, dds_entity_t};
 use serde::Serialize;
-use std::collections::HashSet;
-use std::convert::TryInto;
-use std::sync::Arc;
-use std::{ffi::CStr, fmt, time::Duration};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use zenoh::liveliness::LivelinessToken;
 use zenoh::prelude::*;
 use zenoh::query::ReplyKeyExpr;
+use zenoh::subscriber::Reliability;
+use zenoh::time::Timestamp;
 use zenoh::{prelude::r#async::AsyncResolve, subscriber::Subscriber};
 use zenoh_ext::{FetchingSubscriber, SubscriberBuilderExt};
 
 use crate::gid::Gid;
 use crate::liveliness_mgt::new_ke_liveliness_sub;
-use crate::qos_helpers::is_transient_local;
+use crate::qos_helpers::{is_reader_reliable, is_transient_local};
 use crate::ros2_utils::ros2_message_type_to_dds_type;
+use crate::routes_mgr::RouteStatus;
 use crate::{
-    dds_discovery::*, qos::Qos, vec_into_raw_parts, Config, KE_ANY_1_SEGMENT, LOG_PAYLOAD,
+    dds_discovery::*,
+    qos::{Qos, TimeBasedFilter, DDS_INFINITE_TIME},
+    Config, KE_ANY_1_SEGMENT, LOG_PAYLOAD,
 };
 use crate::{serialize_option_as_bool, KE_PREFIX_PUB_CACHE};
 
@@ -50,6 +54,117 @@ impl ZSubscriber<'_> {
     }
 }
 
+// Drops the samples received from Zenoh that are older than a maximum age (if any),
+// counting the dropped samples
+#[derive(Default)]
+struct SampleAgeFilter {
+    // the maximum age in nanoseconds (0 = no maximum)
+    max_age_ns: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SampleAgeFilter {
+    fn max_age(&self) -> Option<Duration> {
+        match self.max_age_ns.load(Ordering::Relaxed) {
+            0 => None,
+            ns => Some(Duration::from_nanos(ns)),
+        }
+    }
+
+    fn set_max_age(&self, max_age: Option<Duration>) {
+        self.max_age_ns.store(
+            max_age.map_or(0, |d| d.as_nanos().min(u64::MAX as u128) as u64),
+            Ordering::Relaxed,
+        );
+    }
+
+    // Return true if the sample is older than the maximum age (and count it as dropped).
+    // Note: the sample's age is computed from its Zenoh timestamp, assuming the hosts' clocks are synchronized.
+    fn is_too_old(&self, s: &Sample) -> bool {
+        let (max_age, timestamp) = match (self.max_age(), &s.timestamp) {
+            (Some(max_age), Some(timestamp)) => (max_age, timestamp),
+            _ => return false,
+        };
+        let age = SystemTime::now()
+            .duration_since(timestamp.get_time().to_system_time())
+            .unwrap_or_default();
+        if age > max_age {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// The maximum number of timestamps remembered by RoutedTimestamps
+const MAX_ROUTED_TIMESTAMPS: usize = 4096;
+
+// The timestamps of the last samples routed by a route for a TRANSIENT_LOCAL topic.
+// The same historical sample can be received several times: from several bridges caching it
+// (e.g. a relay bridge and the origin bridge), from several fetches (at activation and when a new
+// remote bridge is discovered), or as a live sample before being fetched.
+// As a Zenoh timestamp includes the identity of its source (the HLC id), 2 samples with the same timestamp
+// are the same sample, and only the first one is routed.
+#[derive(Default)]
+struct RoutedTimestamps {
+    timestamps: HashSet<Timestamp>,
+    // the insertion order, to forget the oldest timestamps
+    order: VecDeque<Timestamp>,
+}
+
+impl RoutedTimestamps {
+    // Record the timestamp, returning false if it was already routed
+    fn insert(&mut self, timestamp: Timestamp) -> bool {
+        if !self.timestamps.insert(timestamp) {
+            return false;
+        }
+        self.order.push_back(timestamp);
+        if self.order.len() > MAX_ROUTED_TIMESTAMPS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.timestamps.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+// The plugin ids of the bridges from which the historical publications were fetched since the route's activation,
+// so the announcement of a remote Publisher triggers a query only to a bridge not fetched yet.
+// Shared with the tasks sending the queries, that record a bridge once its query is sent.
+#[derive(Clone, Default)]
+struct FetchedBridges(Arc<Mutex<HashSet<String>>>);
+
+impl FetchedBridges {
+    // Set the bridges serving the remote routes ("<plugin_id>:<zenoh_key_expr>") as fetched
+    // (i.e. queried by a new FetchingSubscriber)
+    fn set_from_remote_routes(&self, remote_routes: &HashSet<String>) {
+        *self.0.lock().unwrap() = remote_routes
+            .iter()
+            .filter_map(|r| {
+                r.split_once(':')
+                    .map(|(plugin_id, _)| plugin_id.to_string())
+            })
+            .collect();
+    }
+
+    fn contains(&self, plugin_id: &str) -> bool {
+        self.0.lock().unwrap().contains(plugin_id)
+    }
+
+    fn insert(&self, plugin_id: String) {
+        self.0.lock().unwrap().insert(plugin_id);
+    }
+
+    fn remove(&self, plugin_id: &str) {
+        self.0.lock().unwrap().remove(plugin_id);
+    }
+
+    fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
 // a route from Zenoh to DDS
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize)]
@@ -70,26 +185,54 @@ pub struct RouteSubscriber<'a> {
     // `None` when route is created on a remote announcement and no local ROS2 Subscriber discovered yet
     #[serde(rename = "is_active", serialize_with = "serialize_option_as_bool")]
     zenoh_subscriber: Option<ZSubscriber<'a>>,
+    // if the zenoh subscriber is reliable: only if the Writer is TRANSIENT_LOCAL or any local Reader is RELIABLE
+    zenoh_reliable: bool,
     // the local DDS Writer created to serve the route (i.e. re-publish to DDS data coming from zenoh)
-    #[serde(serialize_with = "serialize_entity_guid")]
-    dds_writer: dds_entity_t,
+    // shared with the zenoh subscriber's callback, allowing to swap it when re-created with another QoS
+    #[serde(serialize_with = "serialize_atomic_entity_guid")]
+    dds_writer: Arc<AtomicI32>,
+    // the QoS of the DDS Writer (after "qos_overrides" rules application)
+    writer_qos: Qos,
+    // the statuses reported by the DDS Writer (incompatible QoS, deadline missed)
+    dds_writer_status: DdsStatusRecorder,
+    // the drop of the samples older than the Writer's Lifespan QoS or the "max_sample_ages" option
+    #[serde(serialize_with = "serialize_sample_age_filter")]
+    sample_age_filter: Arc<SampleAgeFilter>,
     // if the Writer is TRANSIENT_LOCAL
     transient_local: bool,
+    // the timestamps of the routed samples, to drop the duplicates if the Writer is TRANSIENT_LOCAL
+    #[serde(skip)]
+    routed_timestamps: Arc<Mutex<RoutedTimestamps>>,
     // if the topic is keyless
     #[serde(skip)]
     keyless: bool,
     // a liveliness token associated to this route, for announcement to other plugins
     #[serde(skip)]
     liveliness_token: Option<LivelinessToken<'a>>,
+    // the QoS announced with the liveliness token
+    #[serde(skip)]
+    announced_qos: Option<Qos>,
     // the list of remote routes served by this route ("<plugin_id>:<zenoh_key_expr>"")
     remote_routes: HashSet<String>,
+    // the bridges from which the historical publications were fetched since the route's activation
+    #[serde(skip)]
+    fetched_bridges: FetchedBridges,
     // the list of nodes served by this route
     local_nodes: HashSet<String>,
+    // the minimum separation between samples requested by each local node via its TimeBasedFilter QoS (0 if none)
+    #[serde(skip)]
+    local_min_separations: HashMap<String, dds_duration_t>,
+    // if the Reader of each local node is RELIABLE
+    #[serde(skip)]
+    local_reliabilities: HashMap<String, bool>,
+    // the local nodes served by this route with a Reader QoS conflicting with the DDS Writer's QoS
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    qos_conflicts: HashMap<String, RouteStatus>,
 }
 
 impl Drop for RouteSubscriber<'_> {
     fn drop(&mut self) {
-        if let Err(e) = delete_dds_entity(self.dds_writer) {
+        if let Err(e) = delete_dds_entity(self.dds_writer.load(Ordering::Relaxed)) {
             log::warn!("{}: error deleting DDS Reader:  {}", self, e);
         }
     }
@@ -123,8 +266,20 @@ impl RouteSubscriber<'_> {
         let topic_name = format!("rt{ros2_name}");
         let type_name = ros2_message_type_to_dds_type(&ros2_type);
 
-        let dds_writer =
-            create_forwarding_dds_writer(participant, topic_name, type_name, keyless, writer_qos)?;
+        let dds_writer_status = DdsStatusRecorder::new(format!(
+            "Route Subscriber (Zenoh:{zenoh_key_expr} -> ROS:{ros2_name}): DDS Writer"
+        ));
+        let dds_writer = create_forwarding_dds_writer(
+            participant,
+            topic_name,
+            type_name,
+            keyless,
+            writer_qos.clone(),
+            &dds_writer_status,
+        )?;
+
+        let sample_age_filter = Arc::new(SampleAgeFilter::default());
+        sample_age_filter.set_max_age(get_max_sample_age(&config, &zenoh_key_expr, &writer_qos));
 
         Ok(RouteSubscriber {
             ros2_name,
@@ -133,98 +288,130 @@ impl RouteSubscriber<'_> {
             zsession,
             config,
             zenoh_subscriber: None,
-            dds_writer,
+            zenoh_reliable: true,
+            dds_writer: Arc::new(AtomicI32::new(dds_writer)),
+            writer_qos,
+            dds_writer_status,
+            sample_age_filter,
             transient_local,
+            routed_timestamps: Arc::new(Mutex::new(RoutedTimestamps::default())),
             keyless,
             liveliness_token: None,
+            announced_qos: None,
             remote_routes: HashSet::new(),
+            fetched_bridges: FetchedBridges::default(),
             local_nodes: HashSet::new(),
+            local_min_separations: HashMap::new(),
+            local_reliabilities: HashMap::new(),
+            qos_conflicts: HashMap::new(),
         })
     }
 
-    async fn activate(
+    // Re-create the DDS Writer with a new QoS (e.g. to match a new local Reader with conflicting QoS,
+    // or a remote Publisher with a more demanding QoS). The new Writer transparently replaces the old one
+    // in the Zenoh subscriber's callback. If the route is active and the Writer becomes TRANSIENT_LOCAL,
+    // the Zenoh subscriber is re-created as a FetchingSubscriber to get the remote historical data.
+    pub async fn recreate_dds_writer(
+        &mut self,
+        participant: dds_entity_t,
+        writer_qos: Qos,
+    ) -> Result<(), String> {
+        log::debug!("{self}: re-create DDS Writer with QoS {writer_qos:?}");
+        let dds_writer = create_forwarding_dds_writer(
+            participant,
+            format!("rt{}", self.ros2_name),
+            ros2_message_type_to_dds_type(&self.ros2_type),
+            self.keyless,
+            writer_qos.clone(),
+            &self.dds_writer_status,
+        )?;
+        let old_dds_writer = self.dds_writer.swap(dds_writer, Ordering::Relaxed);
+        if let Err(e) = delete_dds_entity(old_dds_writer) {
+            log::warn!("{}: error deleting DDS Writer:  {}", self, e);
+        }
+        self.sample_age_filter.set_max_age(get_max_sample_age(
+            &self.config,
+            &self.zenoh_key_expr,
+            &writer_qos,
+        ));
+        let transient_local = is_transient_local(&writer_qos);
+        let fetching_required = transient_local && !self.transient_local;
+        self.transient_local = transient_local;
+        self.writer_qos = writer_qos;
+        self.qos_conflicts.clear();
+        if fetching_required && self.zenoh_subscriber.is_some() {
+            // drop the previous subscriber before creating the new one
+            self.zenoh_subscriber = None;
+            self.zenoh_reliable = self.requires_reliable_subscription();
+            let config = self.config.clone();
+            self.declare_zenoh_subscriber(&config).await?;
+        }
+        Ok(())
+    }
+
+    // Update the route with a new config (e.g. the "queries_timeout" of the next FetchingSubscriber)
+    #[inline]
+    pub fn update_config(&mut self, config: Arc<Config>) {
+        self.config = config;
+    }
+
+    #[inline]
+    pub fn ros2_name(&self) -> &str {
+        &self.ros2_name
+    }
+
+    #[inline]
+    pub fn dds_writer_qos(&self) -> &Qos {
+        &self.writer_qos
+    }
+
+    #[inline]
+    pub fn set_qos_conflict(&mut self, node: String, reason: String) {
+        self.qos_conflicts
+            .insert(node, RouteStatus::QoSConflict(reason));
+    }
+
+    // Activate the route. The LivelinessToken announcing this route to other plugins is declared only
+    // if `declare_token` is true (i.e. not for a route that is part of an Action route).
+    pub async fn activate(
         &mut self,
         config: &Config,
         plugin_id: &keyexpr,
         discovered_reader_qos: &Qos,
+        declare_token: bool,
     ) -> Result<(), String> {
         log::debug!("{self} activate");
-        // Callback routing data received by Zenoh subscriber to DDS Writer (if set)
-        let ros2_name = self.ros2_name.clone();
-        let dds_writer = self.dds_writer;
-        let subscriber_callback = move |s: Sample| {
-            do_route_data(s, &ros2_name, dds_writer);
-        };
+        self.zenoh_reliable = self.requires_reliable_subscription();
+        self.declare_zenoh_subscriber(config).await?;
 
-        // create zenoh subscriber
-        // if Writer is TRANSIENT_LOCAL, use a QueryingSubscriber to fetch remote historical data to write
-        self.zenoh_subscriber = if self.transient_local {
-            // query all PublicationCaches on "<KE_PREFIX_PUB_CACHE>/*/<routing_keyexpr>"
-            let query_selector: Selector =
-                (*KE_PREFIX_PUB_CACHE / *KE_ANY_1_SEGMENT / &self.zenoh_key_expr).into();
-            log::error!("{self}: query historical data from everybody for TRANSIENT_LOCAL Reader on {query_selector}");
-            {
-                use zenoh_core::SyncResolve;
-                //
-                println!("********* QUERY FROM {query_selector}");
-                let rep = self
-                    .zsession
-                    .get(&query_selector)
-                    .target(QueryTarget::All)
-                    .consolidation(ConsolidationMode::None)
-                    .accept_replies(ReplyKeyExpr::Any)
-                    .res_sync()
-                    .unwrap();
-                while let Ok(reply) = rep.recv() {
-                    match reply.sample {
-                        Ok(sample) => println!(
-                            ">>>>>> Received ('{}': '{:02x?}')",
-                            sample.key_expr.as_str(),
-                            sample.value.payload.contiguous(),
-                        ),
-                        Err(err) => {
-                            println!(">> Received (ERROR: '{}')", String::try_from(&err).unwrap())
-                        }
-                    }
-                }
-                //
-            }
+        if !declare_token {
+            return Ok(());
+        }
 
-            let sub = self
-                .zsession
-                .declare_subscriber(&self.zenoh_key_expr)
-                .callback(subscriber_callback)
-                .allowed_origin(Locality::Remote) // Allow only remote publications to avoid loops
-                .reliable()
-                .querying()
-                .query_timeout(config.queries_timeout)
-                .query_selector(query_selector)
-                .query_accept_replies(ReplyKeyExpr::Any)
-                .res()
-                .await
-                .map_err(|e| format!("{self}: failed to create FetchingSubscriber: {e}",))?;
-            Some(ZSubscriber::FetchingSubscriber(sub))
-        } else {
-            let sub = self
-                .zsession
-                .declare_subscriber(&self.zenoh_key_expr)
-                .callback(subscriber_callback)
-                .allowed_origin(Locality::Remote) // Allow only remote publications to avoid loops
-                .reliable()
-                .res()
-                .await
-                .map_err(|e| format!("{self}: failed to create Subscriber: {e}"))?;
-            Some(ZSubscriber::Subscriber(sub))
-        };
+        // announce the minimum separation requested for this route, for the remote Publishers to downsample
+        let mut qos = discovered_reader_qos.clone();
+        qos.time_based_filter = self
+            .get_requested_min_separation()
+            .map(|minimum_separation| TimeBasedFilter { minimum_separation });
+        self.declare_liveliness_token(plugin_id, qos).await
+    }
 
-        // create associated LivelinessToken
+    // Declare the LivelinessToken announcing this route to other plugins, with the specified QoS
+    async fn declare_liveliness_token(
+        &mut self,
+        plugin_id: &keyexpr,
+        qos: Qos,
+    ) -> Result<(), String> {
+        // drop the previous token (if any) before declaring a new one
+        self.liveliness_token = None;
         let liveliness_ke = new_ke_liveliness_sub(
             plugin_id,
             &self.zenoh_key_expr,
             &self.ros2_type,
             self.keyless,
-            &discovered_reader_qos,
+            &qos,
         )?;
+        self.announced_qos = Some(qos);
         let ros2_name = self.ros2_name.clone();
         self.liveliness_token = Some(
             self.zsession
@@ -241,88 +428,169 @@ impl RouteSubscriber<'_> {
         Ok(())
     }
 
-    fn deactivate(&mut self) {
+    pub fn deactivate(&mut self) {
         log::debug!("{self} deactivate");
         // Drop Zenoh Subscriber and Liveliness token
         // The DDS Writer remains to be discovered by local ROS nodes
         self.zenoh_subscriber = None;
+        self.fetched_bridges.clear();
         self.liveliness_token = None;
+        self.announced_qos = None;
     }
 
-    /// If this route uses a FetchingSubscriber, query for historical publications
-    /// using the specified Selector. Otherwise, do nothing.
-    pub async fn query_historical_publications<'a>(
+    // Return the minimum separation between samples requested to the remote Publishers: the period of the
+    // matching "sub_max_frequencies" option if any, or else the lowest TimeBasedFilter of the local Readers
+    // (none if any of them has no TimeBasedFilter)
+    fn get_requested_min_separation(&self) -> Option<dds_duration_t> {
+        if let Some(period) = get_requested_period(&self.config, &self.zenoh_key_expr) {
+            return Some(period.as_nanos().min(dds_duration_t::MAX as u128) as dds_duration_t);
+        }
+        self.local_min_separations
+            .values()
+            .min()
+            .copied()
+            .filter(|s| *s > 0)
+    }
+
+    // Update the route after a change of local Readers: the requested minimum separation
+    // and the reliability of the zenoh subscriber might change
+    pub async fn update_for_local_nodes(&mut self, plugin_id: &keyexpr) -> Result<(), String> {
+        self.update_requested_min_separation(plugin_id).await?;
+        self.update_zenoh_reliability().await
+    }
+
+    // Re-declare the liveliness token if the minimum separation requested to the remote Publishers changed
+    async fn update_requested_min_separation(&mut self, plugin_id: &keyexpr) -> Result<(), String> {
+        if let Some(announced_qos) = &self.announced_qos {
+            let requested = self.get_requested_min_separation();
+            if requested
+                != announced_qos
+                    .time_based_filter
+                    .as_ref()
+                    .map(|f| f.minimum_separation)
+            {
+                let mut qos = announced_qos.clone();
+                qos.time_based_filter =
+                    requested.map(|minimum_separation| TimeBasedFilter { minimum_separation });
+                log::debug!("{self}: announce new requested minimum separation {requested:?}");
+                self.declare_liveliness_token(plugin_id, qos).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // The zenoh subscriber is best effort only if all the local Readers are BEST_EFFORT,
+    // so the remote Publishers don't get blocked by a slow BEST_EFFORT Reader.
+    // A FetchingSubscriber (for a TRANSIENT_LOCAL Writer) is always reliable.
+    fn requires_reliable_subscription(&self) -> bool {
+        self.transient_local
+            || self.local_reliabilities.is_empty()
+            || self.local_reliabilities.values().any(|reliable| *reliable)
+    }
+
+    // Re-create the zenoh subscriber if its reliability must change
+    async fn update_zenoh_reliability(&mut self) -> Result<(), String> {
+        let reliable = self.requires_reliable_subscription();
+        if self.zenoh_subscriber.is_some() && reliable != self.zenoh_reliable {
+            log::debug!(
+                "{self}: re-create the zenoh subscriber as {}",
+                if reliable { "reliable" } else { "best effort" }
+            );
+            // drop the previous subscriber before creating the new one
+            self.zenoh_subscriber = None;
+            self.zenoh_reliable = reliable;
+            let config = self.config.clone();
+            self.declare_zenoh_subscriber(&config).await?;
+        }
+        Ok(())
+    }
+
+    // (Re-)create the zenoh subscriber. A FetchingSubscriber queries the historical publications
+    // of all the bridges, including those serving the remote routes known so far.
+    async fn declare_zenoh_subscriber(&mut self, config: &Config) -> Result<(), String> {
+        self.zenoh_subscriber = Some(self.create_zenoh_subscriber(config).await?);
+        if self.transient_local {
+            self.fetched_bridges
+                .set_from_remote_routes(&self.remote_routes);
+        } else {
+            self.fetched_bridges.clear();
+        }
+        Ok(())
+    }
+
+    // On announcement of a remote Publisher, query its historical publications if not already fetched
+    // from its bridge (i.e. at the route's activation or when the bridge joined)
+    pub fn fetch_historical_publications_from(
         &mut self,
         plugin_id: &keyexpr,
         query_timeout: Duration,
     ) {
+        if !self.fetched_bridges.contains(plugin_id) {
+            self.query_historical_publications(plugin_id, query_timeout);
+        }
+    }
+
+    /// If this route uses a FetchingSubscriber, query for historical publications
+    /// using the specified Selector. Otherwise, do nothing.
+    /// The query is sent by a task, not to block the caller, and the bridge is recorded
+    /// as fetched only once the query is sent.
+    pub fn query_historical_publications(&mut self, plugin_id: &keyexpr, query_timeout: Duration) {
+        use zenoh_core::SyncResolve;
+        let route = self.to_string();
         if let Some(ZSubscriber::FetchingSubscriber(sub)) = &mut self.zenoh_subscriber {
             // query all PublicationCaches on "<KE_PREFIX_PUB_CACHE>/<plugin_id>/<routing_keyexpr>"
-            let query_selector: Selector =
+            let query_selector: Selector<'static> =
                 (*KE_PREFIX_PUB_CACHE / plugin_id / &self.zenoh_key_expr).into();
-            log::error!("Route Subscriber (Zenoh:{} -> ROS:{}): query historical data from {plugin_id} for TRANSIENT_LOCAL Reader on {query_selector}",
+            log::debug!("Route Subscriber (Zenoh:{} -> ROS:{}): query historical data from {plugin_id} for TRANSIENT_LOCAL Reader on {query_selector}",
                 self.zenoh_key_expr, self.ros2_name
             );
 
             if let Err(e) = sub
                 .fetch({
-                    let session = &self.zsession;
+                    let session = self.zsession.clone();
+                    let fetched_bridges = self.fetched_bridges.clone();
+                    let plugin_id = plugin_id.to_string();
+                    let route = route.clone();
                     let query_selector = query_selector.clone();
-                    {
-                        use zenoh_core::SyncResolve;
-                        //
-                        println!("********* FETCH FROM {query_selector}");
-                        let rep = session
-                            .get(&query_selector)
-                            .target(QueryTarget::All)
-                            .consolidation(ConsolidationMode::None)
-                            .accept_replies(ReplyKeyExpr::Any)
-                            .res_sync()
-                            .unwrap();
-                        while let Ok(reply) = rep.recv() {
-                            match reply.sample {
-                                Ok(sample) => println!(
-                                    ">>>>>> Received ('{}': '{:02x?}')",
-                                    sample.key_expr.as_str(),
-                                    sample.value.payload.contiguous(),
-                                ),
-                                Err(err) => println!(
-                                    ">> Received (ERROR: '{}')",
-                                    String::try_from(&err).unwrap()
+                    move |cb| {
+                        task::spawn(async move {
+                            match session
+                                .get(&query_selector)
+                                .target(QueryTarget::All)
+                                .consolidation(ConsolidationMode::None)
+                                .accept_replies(ReplyKeyExpr::Any)
+                                .timeout(query_timeout)
+                                .callback(cb)
+                                .res_async()
+                                .await
+                            {
+                                Ok(()) => {
+                                    fetched_bridges.insert(plugin_id);
+                                }
+                                Err(e) => log::warn!(
+                                    "{route}: query for historical publications on {query_selector} failed: {e}"
                                 ),
                             }
-                        }
-                        //
-                    }
-
-                    move |cb| {
-                        use zenoh_core::SyncResolve;
-                        session
-                            .get(&query_selector)
-                            .target(QueryTarget::All)
-                            .consolidation(ConsolidationMode::None)
-                            .accept_replies(ReplyKeyExpr::Any)
-                            .timeout(query_timeout)
-                            .callback(cb)
-                            .res_sync()
+                        });
+                        Ok(())
                     }
                 })
-                .res()
-                .await
+                .res_sync()
             {
-                log::warn!(
-                    "{}: query for historical publications on {} failed: {}",
-                    self,
-                    query_selector,
-                    e
-                );
+                log::warn!("{route}: query for historical publications on {query_selector} failed: {e}");
             }
         }
     }
 
     #[inline]
     pub fn dds_writer_guid(&self) -> Result<Gid, String> {
-        get_guid(&self.dds_writer)
+        get_guid(&self.dds_writer.load(Ordering::Relaxed))
+    }
+
+    // Write a serialized message (with CDR header) to the local DDS Writer, as if routed from Zenoh
+    #[inline]
+    pub fn write_to_dds(&self, buf: Vec<u8>) -> Result<(), String> {
+        dds_write(self.dds_writer.load(Ordering::Relaxed), buf)
     }
 
     #[inline]
@@ -336,6 +604,8 @@ impl RouteSubscriber<'_> {
     pub fn remove_remote_route(&mut self, plugin_id: &str, zenoh_key_expr: &keyexpr) {
         self.remote_routes
             .remove(&format!("{plugin_id}:{zenoh_key_expr}"));
+        // the historical publications of a Publisher re-announced later must be fetched again
+        self.fetched_bridges.remove(plugin_id);
         log::debug!("{self} now serving remote routes {:?}", self.remote_routes);
     }
 
@@ -352,22 +622,37 @@ impl RouteSubscriber<'_> {
         plugin_id: &keyexpr,
         discovered_reader_qos: &Qos,
     ) {
+        let min_separation = discovered_reader_qos
+            .time_based_filter
+            .as_ref()
+            .map_or(0, |f| f.minimum_separation);
+        self.local_min_separations
+            .insert(entity_key.clone(), min_separation);
+        self.local_reliabilities.insert(
+            entity_key.clone(),
+            is_reader_reliable(&discovered_reader_qos.reliability),
+        );
         self.local_nodes.insert(entity_key);
         log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
         // if 1st local node added, activate the route
         if self.local_nodes.len() == 1 {
             if let Err(e) = self
-                .activate(config, plugin_id, discovered_reader_qos)
+                .activate(config, plugin_id, discovered_reader_qos, true)
                 .await
             {
                 log::error!("{self} activation failed: {e}");
             }
+        } else if let Err(e) = self.update_for_local_nodes(plugin_id).await {
+            log::error!("{self} update failed: {e}");
         }
     }
 
     #[inline]
     pub fn remove_local_node(&mut self, entity_key: &str) {
         self.local_nodes.remove(entity_key);
+        self.local_min_separations.remove(entity_key);
+        self.local_reliabilities.remove(entity_key);
+        self.qos_conflicts.remove(entity_key);
         log::debug!("{self} now serving local nodes {:?}", self.local_nodes);
         // if last local node removed, deactivate the route
         if self.local_nodes.is_empty() {
@@ -386,6 +671,120 @@ impl RouteSubscriber<'_> {
     }
 }
 
+impl<'a> RouteSubscriber<'a> {
+    async fn create_zenoh_subscriber(&self, config: &Config) -> Result<ZSubscriber<'a>, String> {
+        // Callback routing data received by Zenoh subscriber to DDS Writer (if set)
+        let ros2_name = self.ros2_name.clone();
+        let dds_writer = self.dds_writer.clone();
+        let sample_age_filter = self.sample_age_filter.clone();
+        let routed_timestamps = self.transient_local.then(|| self.routed_timestamps.clone());
+        let subscriber_callback = move |s: Sample| {
+            if sample_age_filter.is_too_old(&s) {
+                log::trace!(
+                    "Route Subscriber (Zenoh:{} -> ROS:{}): drop sample older than {:?}",
+                    s.key_expr,
+                    &ros2_name,
+                    sample_age_filter.max_age()
+                );
+                return;
+            }
+            if let (Some(routed), Some(timestamp)) = (&routed_timestamps, &s.timestamp) {
+                if !routed.lock().unwrap().insert(*timestamp) {
+                    log::trace!(
+                        "Route Subscriber (Zenoh:{} -> ROS:{}): drop duplicate sample with timestamp {timestamp}",
+                        s.key_expr,
+                        &ros2_name
+                    );
+                    return;
+                }
+            }
+            do_route_data(s, &ros2_name, dds_writer.load(Ordering::Relaxed));
+        };
+
+        // create zenoh subscriber
+        // if Writer is TRANSIENT_LOCAL, use a QueryingSubscriber to fetch remote historical data to write.
+        // The FetchingSubscriber merges the replies of all the bridges by timestamp, and delivers them
+        // in timestamp order before the live samples received meanwhile. The duplicates are dropped by the
+        // callback (see RoutedTimestamps).
+        if self.transient_local {
+            // query all PublicationCaches on "<KE_PREFIX_PUB_CACHE>/*/<routing_keyexpr>"
+            let query_selector: Selector =
+                (*KE_PREFIX_PUB_CACHE / *KE_ANY_1_SEGMENT / &self.zenoh_key_expr).into();
+            log::debug!("{self}: query historical data from everybody for TRANSIENT_LOCAL Reader on {query_selector}");
+
+            let sub = self
+                .zsession
+                .declare_subscriber(&self.zenoh_key_expr)
+                .callback(subscriber_callback)
+                .allowed_origin(Locality::Remote) // Allow only remote publications to avoid loops
+                .reliable()
+                .querying()
+                .query_timeout(config.queries_timeout)
+                .query_selector(query_selector)
+                .query_accept_replies(ReplyKeyExpr::Any)
+                .res()
+                .await
+                .map_err(|e| format!("{self}: failed to create FetchingSubscriber: {e}",))?;
+            Ok(ZSubscriber::FetchingSubscriber(sub))
+        } else {
+            let sub = self
+                .zsession
+                .declare_subscriber(&self.zenoh_key_expr)
+                .callback(subscriber_callback)
+                .allowed_origin(Locality::Remote) // Allow only remote publications to avoid loops
+                .reliability(if self.zenoh_reliable {
+                    Reliability::Reliable
+                } else {
+                    Reliability::BestEffort
+                })
+                .res()
+                .await
+                .map_err(|e| format!("{self}: failed to create Subscriber: {e}"))?;
+            Ok(ZSubscriber::Subscriber(sub))
+        }
+    }
+}
+
+// Return the maximum age of the samples to be routed: the shortest of the Writer's Lifespan QoS
+// and of the "max_sample_ages" option matching keyexpr (if any)
+fn get_max_sample_age(config: &Config, ke: &keyexpr, writer_qos: &Qos) -> Option<Duration> {
+    let lifespan = writer_qos
+        .lifespan
+        .as_ref()
+        .filter(|l| l.duration > 0 && l.duration != DDS_INFINITE_TIME)
+        .map(|l| Duration::from_nanos(l.duration as u64));
+    let configured = config
+        .max_sample_ages
+        .iter()
+        .find(|(re, _)| re.is_match(ke))
+        .map(|(_, age)| Duration::from_secs_f32(*age));
+    match (lifespan, configured) {
+        (Some(l), Some(c)) => Some(l.min(c)),
+        (l, c) => l.or(c),
+    }
+}
+
+fn serialize_sample_age_filter<S>(f: &Arc<SampleAgeFilter>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    use serde::ser::SerializeMap;
+    let mut map = s.serialize_map(Some(2))?;
+    map.serialize_entry("max_sample_age", &f.max_age().map(|d| d.as_secs_f64()))?;
+    map.serialize_entry("dropped_stale_samples", &f.dropped.load(Ordering::Relaxed))?;
+    map.end()
+}
+
+// Return the requested period if keyexpr matches one of the "sub_max_frequencies" option
+fn get_requested_period(config: &Config, ke: &keyexpr) -> Option<Duration> {
+    for (re, freq) in &config.sub_max_frequencies {
+        if re.is_match(ke) {
+            return Some(Duration::from_secs_f32(1f32 / freq));
+        }
+    }
+    None
+}
+
 fn do_route_data(s: Sample, ros2_name: &str, data_writer: dds_entity_t) {
     if *LOG_PAYLOAD {
         log::trace!(
@@ -402,68 +801,132 @@ fn do_route_data(s: Sample, ros2_name: &str, data_writer: dds_entity_t) {
         );
     }
 
-    unsafe {
-        let bs = s.value.payload.contiguous().into_owned();
-        // As per the Vec documentation (see https://doc.rust-lang.org/std/vec/struct.Vec.html#method.into_raw_parts)
-        // the only way to correctly releasing it is to create a vec using from_raw_parts
-        // and then have its destructor do the cleanup.
-        // Thus, while tempting to just pass the raw pointer to cyclone and then free it from C,
-        // that is not necessarily safe or guaranteed to be leak free.
-        // TODO replace when stable https://github.com/rust-lang/rust/issues/65816
-        let (ptr, len, capacity) = vec_into_raw_parts(bs);
-        let size: ddsrt_iov_len_t = match len.try_into() {
-            Ok(s) => s,
-            Err(_) => {
-                log::warn!(
-                    "Route Subscriber (Zenoh:{} -> ROS:{}): can't route data; excessive payload size ({})",
-                    s.key_expr,
-                    ros2_name,
-                    len
-                );
-                return;
-            }
-        };
+    let bs = s.value.payload.contiguous().into_owned();
+    if let Err(e) = dds_write(data_writer, bs) {
+        log::warn!(
+            "Route Subscriber (Zenoh:{} -> ROS:{}): can't route data; {e}",
+            s.key_expr,
+            ros2_name
+        );
+    }
+}
 
-        let data_out = ddsrt_iovec_t {
-            iov_base: ptr as *mut std::ffi::c_void,
-            iov_len: size,
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cyclors::qos::Lifespan;
+    use std::time::UNIX_EPOCH;
+    use zenoh::time::{new_reception_timestamp, TimestampId, NTP64};
+
+    // a sample published `age` ago
+    fn aged_sample(age: Duration) -> Sample {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() - age;
+        let timestamp = Timestamp::new(NTP64::from(time), *new_reception_timestamp().get_id());
+        Sample::new(OwnedKeyExpr::try_from("cmd_vel").unwrap(), vec![0u8]).with_timestamp(timestamp)
+    }
 
-        let mut sertype_ptr: *const ddsi_sertype = std::ptr::null_mut();
-        let ret = dds_get_entity_sertype(data_writer, &mut sertype_ptr);
-        if ret < 0 {
-            log::warn!(
-                "Route Subscriber (Zenoh:{} -> ROS:{}): can't route data; sertype lookup failed ({})",
-                s.key_expr,
-                ros2_name,
-                CStr::from_ptr(dds_strretcode(ret))
-                    .to_str()
-                    .unwrap_or("unrecoverable DDS retcode")
-            );
-            return;
+    #[test]
+    fn test_routed_timestamps() {
+        let id = TimestampId::try_from([1]).unwrap();
+        let other_id = TimestampId::try_from([2]).unwrap();
+        let mut routed = RoutedTimestamps::default();
+        assert!(routed.insert(Timestamp::new(NTP64(1), id)));
+        assert!(!routed.insert(Timestamp::new(NTP64(1), id)));
+        // the same time from another source is another sample
+        assert!(routed.insert(Timestamp::new(NTP64(1), other_id)));
+
+        // only the last MAX_ROUTED_TIMESTAMPS timestamps are remembered
+        for time in 2..=(MAX_ROUTED_TIMESTAMPS as u64) {
+            assert!(routed.insert(Timestamp::new(NTP64(time), id)));
         }
+        assert_eq!(routed.timestamps.len(), MAX_ROUTED_TIMESTAMPS);
+        assert_eq!(routed.order.len(), MAX_ROUTED_TIMESTAMPS);
+        assert!(!routed.insert(Timestamp::new(NTP64(2), id)));
+        assert!(routed.insert(Timestamp::new(NTP64(1), id)));
+        assert_eq!(routed.timestamps.len(), MAX_ROUTED_TIMESTAMPS);
+    }
 
-        let fwdp = ddsi_serdata_from_ser_iov(
-            sertype_ptr,
-            ddsi_serdata_kind_SDK_DATA,
-            1,
-            &data_out,
-            size as usize,
+    #[test]
+    fn test_fetched_bridges() {
+        let fetched = FetchedBridges::default();
+        assert!(!fetched.contains("bridge_a"));
+        // the bridges serving remote routes when the FetchingSubscriber is created are fetched
+        fetched.set_from_remote_routes(
+            &["bridge_a:map".to_string(), "bridge_b:map".to_string()].into(),
         );
+        assert!(fetched.contains("bridge_a"));
+        assert!(fetched.contains("bridge_b"));
+        assert!(!fetched.contains("bridge_c"));
+        // a bridge is fetched once its query is sent (e.g. on a remote Publisher's announcement)
+        let task_fetched = fetched.clone();
+        task_fetched.insert("bridge_c".to_string());
+        assert!(fetched.contains("bridge_c"));
+        // a bridge whose remote Publisher left must be fetched again
+        fetched.remove("bridge_a");
+        assert!(!fetched.contains("bridge_a"));
+        assert!(fetched.contains("bridge_b"));
+        fetched.set_from_remote_routes(&["bridge_a:map".to_string()].into());
+        assert!(fetched.contains("bridge_a"));
+        assert!(!fetched.contains("bridge_b"));
+        // nothing is fetched after the route's deactivation
+        fetched.clear();
+        assert!(!fetched.contains("bridge_a"));
+    }
 
-        let ret = dds_writecdr(data_writer, fwdp);
-        if ret < 0 {
-            log::warn!(
-                "Route Subscriber (Zenoh:{} -> ROS:{}): DDS write({data_writer}) failed: {}",
-                s.key_expr,
-                ros2_name,
-                CStr::from_ptr(dds_strretcode(ret))
-                    .to_str()
-                    .unwrap_or("unrecoverable DDS retcode")
-            );
-            return;
-        }
+    #[test]
+    fn test_sample_age_filter() {
+        let filter = SampleAgeFilter::default();
+        // no maximum age
+        assert!(!filter.is_too_old(&aged_sample(Duration::from_secs(3600))));
+
+        filter.set_max_age(Some(Duration::from_secs(1)));
+        assert_eq!(filter.max_age(), Some(Duration::from_secs(1)));
+        assert!(!filter.is_too_old(&aged_sample(Duration::ZERO)));
+        assert!(filter.is_too_old(&aged_sample(Duration::from_secs(10))));
+        // a sample without timestamp is not filtered
+        let sample = Sample::new(OwnedKeyExpr::try_from("cmd_vel").unwrap(), vec![0u8]);
+        assert!(!filter.is_too_old(&sample));
+        assert_eq!(filter.dropped.load(Ordering::Relaxed), 1);
+
+        filter.set_max_age(None);
+        assert_eq!(filter.max_age(), None);
+        assert!(!filter.is_too_old(&aged_sample(Duration::from_secs(10))));
+    }
 
-        drop(Vec::from_raw_parts(ptr, len, capacity));
+    #[test]
+    fn test_max_sample_age() {
+        let config: Config =
+            serde_json::from_str(r#"{"max_sample_ages": ["cmd_vel=0.5"]}"#).unwrap();
+        let ke = keyexpr::new("cmd_vel").unwrap();
+        let mut qos = Qos::default();
+        assert_eq!(
+            get_max_sample_age(&config, ke, &qos),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            get_max_sample_age(&config, keyexpr::new("odom").unwrap(), &qos),
+            None
+        );
+        // the shortest of the Lifespan QoS and of the option
+        qos.lifespan = Some(Lifespan {
+            duration: 100_000_000,
+        });
+        assert_eq!(
+            get_max_sample_age(&config, ke, &qos),
+            Some(Duration::from_millis(100))
+        );
+        qos.lifespan = Some(Lifespan {
+            duration: 2_000_000_000,
+        });
+        assert_eq!(
+            get_max_sample_age(&config, ke, &qos),
+            Some(Duration::from_millis(500))
+        );
+        // an infinite Lifespan is no maximum
+        qos.lifespan = Some(Lifespan {
+            duration: DDS_INFINITE_TIME,
+        });
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(get_max_sample_age(&config, ke, &qos), None);
     }
 }
@@ -11,14 +11,31 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use crate::config::Config;
+use crate::config::{Config, QosConflictPolicy};
 use crate::discovered_entities::DiscoveredEntities;
 use crate::events::ROS2AnnouncementEvent;
 use crate::events::ROS2DiscoveryEvent;
+use crate::gid::Gid;
+use crate::instance_cache::{CacheBudget, PersistentCaches};
 use crate::qos_helpers::adapt_reader_qos_for_writer;
 use crate::qos_helpers::adapt_writer_qos_for_reader;
+use crate::qos_helpers::apply_qos_overrides;
+use crate::qos_helpers::downgrade_reader_qos_for_writer;
+use crate::qos_helpers::get_exclusive_ownership_strength;
+use crate::qos_helpers::get_most_demanding_writer_qos;
+use crate::qos_helpers::get_partition_prefix;
+use crate::qos_helpers::get_qos_mismatch;
+use crate::qos_helpers::is_reader_reliable;
+use crate::qos_helpers::is_transient_local;
+use crate::qos_helpers::upgrade_writer_qos_for_reader;
 use crate::ros_discovery::RosDiscoveryInfoMgr;
+use crate::route_action_cli::RouteActionCli;
+use crate::route_action_srv::RouteActionSrv;
 use crate::route_publisher::RoutePublisher;
+use crate::route_service_cli::{
+    get_selection_policy, get_service_timeout, GoalTracking, RemoteBridgesZid, RouteServiceCli,
+};
+use crate::route_service_srv::RouteServiceSrv;
 use crate::route_subscriber::RouteSubscriber;
 use cyclors::dds_entity_t;
 use cyclors::qos::Qos;
@@ -27,35 +44,47 @@ use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 use zenoh::prelude::keyexpr;
 use zenoh::prelude::r#async::AsyncResolve;
 use zenoh::prelude::OwnedKeyExpr;
 use zenoh::queryable::Query;
 use zenoh::sample::Sample;
 use zenoh::Session;
-use zenoh_core::zread;
+use zenoh_core::{zread, zwrite};
 
-use crate::ke_for_sure;
+use crate::{ke_admin_prefix, ke_for_sure};
 
 lazy_static::lazy_static!(
     static ref KE_PREFIX_ROUTE_PUBLISHER: &'static keyexpr = ke_for_sure!("route/topic/pub");
     static ref KE_PREFIX_ROUTE_SUBSCRIBER: &'static keyexpr = ke_for_sure!("route/topic/sub");
     static ref KE_PREFIX_ROUTE_SERVICE_SRV: &'static keyexpr = ke_for_sure!("route/service/srv");
     static ref KE_PREFIX_ROUTE_SERVICE_CLI: &'static keyexpr = ke_for_sure!("route/service/cli");
+    static ref KE_PREFIX_ROUTE_ACTION_SRV: &'static keyexpr = ke_for_sure!("route/action/srv");
+    static ref KE_PREFIX_ROUTE_ACTION_CLI: &'static keyexpr = ke_for_sure!("route/action/cli");
 );
 
+// The character prefixing the DDS partition in the name (and key expression) of a partitioned route.
+// Not being valid in a ROS2 name, it prevents the collision of a partitioned topic (e.g. "%robot_1/chatter"
+// for "/chatter" in "robot_1" partition) with a non-partitioned one (e.g. "robot_1/chatter" for "/robot_1/chatter").
+pub const PARTITION_MARKER: char = '%';
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RouteStatus {
     Routed(OwnedKeyExpr), // Routing is active, with the zenoh key expression used for the route
     NotAllowed,           // Routing was not allowed per configuration
     CreationFailure(String), // The route creation failed
-    _QoSConflict,         // A route was already established but with conflicting QoS
+    QoSConflict(String),  // A route was already established but with conflicting QoS
 }
 
 #[derive(Debug)]
 enum RouteRef {
     PublisherRoute(String),
     SubscriberRoute(String),
+    ServiceSrvRoute(String),
+    ServiceCliRoute(String),
+    ActionSrvRoute(String),
+    ActionCliRoute(String),
 }
 
 pub struct RoutesMgr<'a> {
@@ -67,6 +96,22 @@ pub struct RoutesMgr<'a> {
     // maps of established routes - ecah map indexed by topic/service/action name
     routes_publishers: HashMap<String, RoutePublisher<'a>>,
     routes_subscribers: HashMap<String, RouteSubscriber<'a>>,
+    routes_service_srv: HashMap<String, RouteServiceSrv<'a>>,
+    routes_service_cli: HashMap<String, RouteServiceCli<'a>>,
+    routes_action_srv: HashMap<String, RouteActionSrv<'a>>,
+    routes_action_cli: HashMap<String, RouteActionCli<'a>>,
+    // the zenoh id of each remote bridge, indexed by plugin_id
+    remote_bridges_zid: RemoteBridgesZid,
+    // the ownership strength of each remote Publisher with EXCLUSIVE Ownership QoS,
+    // indexed by route name and then by plugin_id
+    remote_ownership_strengths: HashMap<String, HashMap<String, i32>>,
+    // the name of the Publisher or Subscriber route serving each local DDS Writer or Reader, when
+    // it's not the ROS2 name (i.e. prefixed with a DDS partition, see "partitions_as_key_prefix")
+    partitioned_entities: HashMap<Gid, String>,
+    // the on-disk caches of the TRANSIENT_LOCAL publications (see "persistent_caches")
+    persistent_caches: PersistentCaches<'a>,
+    // the memory budget shared by all the caches of TRANSIENT_LOCAL publications
+    cache_budget: Arc<CacheBudget>,
     // ros_discovery_info read/write manager
     ros_discovery_mgr: Arc<RosDiscoveryInfoMgr>,
     admin_prefix: OwnedKeyExpr,
@@ -84,7 +129,15 @@ impl<'a> RoutesMgr<'a> {
         ros_discovery_mgr: Arc<RosDiscoveryInfoMgr>,
         admin_prefix: OwnedKeyExpr,
     ) -> RoutesMgr<'a> {
+        let cache_budget = Arc::new(CacheBudget::new(config.transient_local_cache_max_bytes));
         RoutesMgr {
+            persistent_caches: PersistentCaches::new(
+                config.clone(),
+                zsession,
+                plugin_id.clone(),
+                cache_budget.clone(),
+            ),
+            cache_budget,
             plugin_id,
             config,
             zsession,
@@ -92,12 +145,24 @@ impl<'a> RoutesMgr<'a> {
             discovered_entities,
             routes_publishers: HashMap::new(),
             routes_subscribers: HashMap::new(),
+            routes_service_srv: HashMap::new(),
+            routes_service_cli: HashMap::new(),
+            routes_action_srv: HashMap::new(),
+            routes_action_cli: HashMap::new(),
+            remote_bridges_zid: Arc::new(RwLock::new(HashMap::new())),
+            remote_ownership_strengths: HashMap::new(),
+            partitioned_entities: HashMap::new(),
             ros_discovery_mgr,
             admin_prefix,
             admin_space: HashMap::new(),
         }
     }
 
+    // Reload the on-disk caches of the TRANSIENT_LOCAL publications, to serve them to the remote bridges
+    pub async fn load_persistent_caches(&mut self) {
+        self.persistent_caches.load().await;
+    }
+
     pub async fn on_ros_discovery_event(
         &mut self,
         event: ROS2DiscoveryEvent,
@@ -117,28 +182,46 @@ impl<'a> RoutesMgr<'a> {
                         ))?
                         .clone()
                 };
+                let (route_name, announced_qos) =
+                    get_local_route_name(&self.config, &iface.name, &entity.qos);
+                if route_name != iface.name {
+                    self.partitioned_entities
+                        .insert(iface.writer, route_name.clone());
+                }
                 // Get route (create it if not yet exists)
                 let route = self
                     .get_or_create_route_publisher(
-                        iface.name,
+                        route_name.clone(),
+                        iface.name.clone(),
                         iface.typ,
                         entity.keyless,
                         adapt_writer_qos_for_reader(&entity.qos),
                     )
                     .await?;
                 route
-                    .add_local_node(node.into(), &plugin_id, &entity.qos)
+                    .add_local_node(node.clone(), &plugin_id, &announced_qos)
                     .await;
+                route.update_ownership(&plugin_id).await?;
+                self.check_route_publisher_qos(&route_name, node, &entity.qos)
+                    .await?;
             }
 
             UndiscoveredMsgPub(node, iface) => {
-                if let Entry::Occupied(mut entry) = self.routes_publishers.entry(iface.name.clone())
+                let route_name = self
+                    .partitioned_entities
+                    .remove(&iface.writer)
+                    .unwrap_or_else(|| iface.name.clone());
+                if let Entry::Occupied(mut entry) = self.routes_publishers.entry(route_name.clone())
                 {
                     let route = entry.get_mut();
                     route.remove_local_node(&node);
+                    if route.is_serving_local_node() {
+                        route.update_ownership(&self.plugin_id).await?;
+                    }
                     if route.is_unused() {
-                        self.admin_space
-                            .remove(&(*KE_PREFIX_ROUTE_PUBLISHER / iface.name_as_keyexpr()));
+                        self.admin_space.remove(
+                            &(*KE_PREFIX_ROUTE_PUBLISHER / route_name_as_keyexpr(&route_name)),
+                        );
                         let route = entry.remove();
                         // remove reader's GID in ros_discovery_msg
                         self.ros_discovery_mgr
@@ -164,29 +247,44 @@ impl<'a> RoutesMgr<'a> {
                 };
                 let plugin_id = self.plugin_id.clone();
                 let config = self.config.clone();
+                let (route_name, announced_qos) =
+                    get_local_route_name(&self.config, &iface.name, &entity.qos);
+                if route_name != iface.name {
+                    self.partitioned_entities
+                        .insert(iface.reader, route_name.clone());
+                }
                 // Get route (create it if not yet exists)
                 let route = self
                     .get_or_create_route_subscriber(
-                        iface.name,
+                        route_name.clone(),
+                        iface.name.clone(),
                         iface.typ,
                         entity.keyless,
                         adapt_reader_qos_for_writer(&entity.qos),
                     )
                     .await?;
                 route
-                    .add_local_node(node.into(), &config, &plugin_id, &entity.qos)
+                    .add_local_node(node.clone(), &config, &plugin_id, &announced_qos)
                     .await;
+                self.check_route_subscriber_qos(&route_name, node, &entity.qos)
+                    .await?;
             }
 
             UndiscoveredMsgSub(node, iface) => {
+                let route_name = self
+                    .partitioned_entities
+                    .remove(&iface.reader)
+                    .unwrap_or_else(|| iface.name.clone());
                 if let Entry::Occupied(mut entry) =
-                    self.routes_subscribers.entry(iface.name.clone())
+                    self.routes_subscribers.entry(route_name.clone())
                 {
                     let route = entry.get_mut();
                     route.remove_local_node(&node);
+                    route.update_for_local_nodes(&self.plugin_id).await?;
                     if route.is_unused() {
-                        self.admin_space
-                            .remove(&(*KE_PREFIX_ROUTE_SUBSCRIBER / iface.name_as_keyexpr()));
+                        self.admin_space.remove(
+                            &(*KE_PREFIX_ROUTE_SUBSCRIBER / route_name_as_keyexpr(&route_name)),
+                        );
                         let route = entry.remove();
                         // remove writer's GID in ros_discovery_msg
                         self.ros_discovery_mgr
@@ -197,29 +295,135 @@ impl<'a> RoutesMgr<'a> {
                     }
                 }
             }
-            DiscoveredServiceSrv(_node, iface) => {
-                log::info!("... TODO: create Service Server route for {}", iface.name);
+            DiscoveredServiceSrv(node, iface) => {
+                let plugin_id = self.plugin_id.clone();
+                // Get route (create it if not yet exists)
+                let route = self
+                    .get_or_create_route_service_srv(iface.name, iface.typ)
+                    .await?;
+                route.add_local_node(node, &plugin_id).await;
             }
-            UndiscoveredServiceSrv(_node, iface) => {
-                log::info!("... TODO: delete Service Server route for {}", iface.name);
+
+            UndiscoveredServiceSrv(node, iface) => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_service_srv.entry(iface.name.clone())
+                {
+                    let route = entry.get_mut();
+                    route.remove_local_node(&node);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_SERVICE_SRV / iface.name_as_keyexpr()));
+                        let route = entry.remove();
+                        // remove writer's and reader's GIDs in ros_discovery_msg
+                        self.ros_discovery_mgr
+                            .remove_dds_writer(route.dds_writer_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        self.ros_discovery_mgr
+                            .remove_dds_reader(route.dds_reader_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        log::info!("{route} removed");
+                    }
+                }
             }
-            DiscoveredServiceCli(_node, iface) => {
-                log::info!("... TODO: create Service Client route for {}", iface.name);
+            DiscoveredServiceCli(node, iface) => {
+                let plugin_id = self.plugin_id.clone();
+                // Get route (create it if not yet exists)
+                let route = self
+                    .get_or_create_route_service_cli(iface.name, iface.typ)
+                    .await?;
+                route.add_local_node(node, &plugin_id).await;
             }
-            UndiscoveredServiceCli(_node, iface) => {
-                log::info!("... TODO: delete Service Client route for {}", iface.name);
+
+            UndiscoveredServiceCli(node, iface) => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_service_cli.entry(iface.name.clone())
+                {
+                    let route = entry.get_mut();
+                    route.remove_local_node(&node);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_SERVICE_CLI / iface.name_as_keyexpr()));
+                        let route = entry.remove();
+                        // remove reader's and writer's GIDs in ros_discovery_msg
+                        self.ros_discovery_mgr
+                            .remove_dds_reader(route.dds_reader_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        self.ros_discovery_mgr
+                            .remove_dds_writer(route.dds_writer_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        log::info!("{route} removed");
+                    }
+                }
             }
-            DiscoveredActionSrv(_node, iface) => {
-                log::info!("... TODO: create Action Server route for {}", iface.name);
+            DiscoveredActionSrv(node, iface) => {
+                let plugin_id = self.plugin_id.clone();
+                // Get route (create it if not yet exists)
+                let route = self
+                    .get_or_create_route_action_srv(iface.name, iface.typ)
+                    .await?;
+                route.add_local_node(node, &plugin_id).await;
             }
-            UndiscoveredActionSrv(_node, iface) => {
-                log::info!("... TODO: delete Action Server route for {}", iface.name);
+
+            UndiscoveredActionSrv(node, iface) => {
+                if let Entry::Occupied(mut entry) = self.routes_action_srv.entry(iface.name.clone())
+                {
+                    let route = entry.get_mut();
+                    route.remove_local_node(&node);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_ACTION_SRV / iface.name_as_keyexpr()));
+                        let route = entry.remove();
+                        // remove writers' and readers' GIDs in ros_discovery_msg
+                        for gid in route.dds_writers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_writer(gid);
+                        }
+                        for gid in route.dds_readers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_reader(gid);
+                        }
+                        log::info!("{route} removed");
+                    }
+                }
             }
-            DiscoveredActionCli(_node, iface) => {
-                log::info!("... TODO: create Action Client route for {}", iface.name);
+            DiscoveredActionCli(node, iface) => {
+                let plugin_id = self.plugin_id.clone();
+                // Get route (create it if not yet exists)
+                let route = self
+                    .get_or_create_route_action_cli(iface.name, iface.typ)
+                    .await?;
+                route.add_local_node(node, &plugin_id).await;
             }
-            UndiscoveredActionCli(_node, iface) => {
-                log::info!("... TODO: delete Action Client route for {}", iface.name);
+
+            UndiscoveredActionCli(node, iface) => {
+                if let Entry::Occupied(mut entry) = self.routes_action_cli.entry(iface.name.clone())
+                {
+                    let route = entry.get_mut();
+                    route.remove_local_node(&node);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_ACTION_CLI / iface.name_as_keyexpr()));
+                        let route = entry.remove();
+                        // remove readers' and writers' GIDs in ros_discovery_msg
+                        for gid in route.dds_readers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_reader(gid);
+                        }
+                        for gid in route.dds_writers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_writer(gid);
+                        }
+                        log::info!("{route} removed");
+                    }
+                }
             }
         }
         Ok(())
@@ -236,28 +440,66 @@ impl<'a> RoutesMgr<'a> {
                 zenoh_key_expr,
                 ros2_type,
                 keyless,
-                writer_qos,
+                mut writer_qos,
             } => {
+                let (route_name, ros2_name) =
+                    get_remote_route_name(&self.config, &zenoh_key_expr, &mut writer_qos);
                 // On remote Publisher route announcement, prepare a Subscriber route
                 // with an associated DDS Writer allowing local ROS2 Nodes to discover it
                 let route = self
                     .get_or_create_route_subscriber(
-                        format!("/{zenoh_key_expr}"),
+                        route_name.clone(),
+                        ros2_name,
                         ros2_type,
                         keyless,
-                        writer_qos,
+                        writer_qos.clone(),
                     )
                     .await?;
                 route.add_remote_route(&plugin_id, &zenoh_key_expr);
+                // the remote Publisher might require a more demanding QoS than the route's DDS Writer
+                self.update_route_subscriber_qos(&route_name, &writer_qos)
+                    .await?;
+                // a remote TRANSIENT_LOCAL Publisher announced by an already known bridge might have
+                // historical publications not yet fetched by the route
+                if is_transient_local(&writer_qos) {
+                    if let Some(route) = self.routes_subscribers.get_mut(&route_name) {
+                        route.fetch_historical_publications_from(
+                            &plugin_id,
+                            self.config.queries_timeout,
+                        );
+                    }
+                }
+                // with EXCLUSIVE Ownership QoS, the remote Publisher might take over the local Publisher route
+                if let Some(strength) = get_exclusive_ownership_strength(&writer_qos) {
+                    self.remote_ownership_strengths
+                        .entry(route_name.clone())
+                        .or_default()
+                        .insert(plugin_id.to_string(), strength);
+                    if let Some(route) = self.routes_publishers.get_mut(&route_name) {
+                        route.add_remote_ownership_strength(&plugin_id, strength);
+                        route.update_ownership(&self.plugin_id).await?;
+                    }
+                }
             }
 
             RetiredMsgPub {
                 plugin_id,
                 zenoh_key_expr,
             } => {
+                let route_name = get_retired_route_name(&zenoh_key_expr);
                 if let Entry::Occupied(mut entry) =
-                    self.routes_subscribers.entry(format!("/{zenoh_key_expr}"))
+                    self.remote_ownership_strengths.entry(route_name.clone())
                 {
+                    entry.get_mut().remove(plugin_id.as_str());
+                    if entry.get().is_empty() {
+                        entry.remove();
+                    }
+                    if let Some(route) = self.routes_publishers.get_mut(&route_name) {
+                        route.remove_remote_ownership_strength(&plugin_id);
+                        route.update_ownership(&self.plugin_id).await?;
+                    }
+                }
+                if let Entry::Occupied(mut entry) = self.routes_subscribers.entry(route_name) {
                     let route = entry.get_mut();
                     route.remove_remote_route(&plugin_id, &zenoh_key_expr);
                     if route.is_unused() {
@@ -279,28 +521,35 @@ impl<'a> RoutesMgr<'a> {
                 zenoh_key_expr,
                 ros2_type,
                 keyless,
-                reader_qos,
+                mut reader_qos,
             } => {
+                let (route_name, ros2_name) =
+                    get_remote_route_name(&self.config, &zenoh_key_expr, &mut reader_qos);
+                // the TimeBasedFilter is the minimum separation requested by the remote Subscriber:
+                // it's used for downsampling by the route, not as a QoS of its DDS Reader
+                let min_separation = reader_qos
+                    .time_based_filter
+                    .take()
+                    .map(|f| Duration::from_nanos(f.minimum_separation.max(0) as u64));
+                let reliable = is_reader_reliable(&reader_qos.reliability);
                 // On remote Subscriber route announcement, prepare a Publisher route
                 // with an associated DDS Reader allowing local ROS2 Nodes to discover it
                 let route = self
                     .get_or_create_route_publisher(
-                        format!("/{zenoh_key_expr}"),
-                        ros2_type,
-                        keyless,
-                        reader_qos,
+                        route_name, ros2_name, ros2_type, keyless, reader_qos,
                     )
                     .await?;
                 route.add_remote_route(&plugin_id, &zenoh_key_expr);
+                route.set_remote_min_separation(&plugin_id, &zenoh_key_expr, min_separation);
+                route.set_remote_reliability(&plugin_id, &zenoh_key_expr, reliable);
             }
 
             RetiredMsgSub {
                 plugin_id,
                 zenoh_key_expr,
             } => {
-                if let Entry::Occupied(mut entry) =
-                    self.routes_publishers.entry(format!("/{zenoh_key_expr}"))
-                {
+                let route_name = get_retired_route_name(&zenoh_key_expr);
+                if let Entry::Occupied(mut entry) = self.routes_publishers.entry(route_name) {
                     let route = entry.get_mut();
                     route.remove_remote_route(&plugin_id, &zenoh_key_expr);
                     if route.is_unused() {
@@ -317,32 +566,467 @@ impl<'a> RoutesMgr<'a> {
                 }
             }
 
-            _ => log::info!("... TODO: manage {event:?}"),
+            AnnouncedServiceSrv {
+                plugin_id,
+                zenoh_key_expr,
+                ros2_type,
+            } => {
+                // On remote Service Server route announcement, prepare a Service Client route
+                // with an associated DDS Reader/Writer allowing local ROS2 Nodes to discover it
+                let route = self
+                    .get_or_create_route_service_cli(format!("/{zenoh_key_expr}"), ros2_type)
+                    .await?;
+                route.add_remote_route(&plugin_id, &zenoh_key_expr);
+            }
+
+            RetiredServiceSrv {
+                plugin_id,
+                zenoh_key_expr,
+            } => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_service_cli.entry(format!("/{zenoh_key_expr}"))
+                {
+                    let route = entry.get_mut();
+                    route.remove_remote_route(&plugin_id, &zenoh_key_expr);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_SERVICE_CLI / &zenoh_key_expr));
+                        let route = entry.remove();
+                        // remove reader's and writer's GIDs in ros_discovery_msg
+                        self.ros_discovery_mgr
+                            .remove_dds_reader(route.dds_reader_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        self.ros_discovery_mgr
+                            .remove_dds_writer(route.dds_writer_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        log::info!("{route} removed");
+                    }
+                }
+            }
+
+            AnnouncedServiceCli {
+                plugin_id,
+                zenoh_key_expr,
+                ros2_type,
+            } => {
+                // On remote Service Client route announcement, prepare a Service Server route
+                // with an associated DDS Writer/Reader allowing local ROS2 Nodes to discover it
+                let route = self
+                    .get_or_create_route_service_srv(format!("/{zenoh_key_expr}"), ros2_type)
+                    .await?;
+                route.add_remote_route(&plugin_id, &zenoh_key_expr);
+            }
+
+            RetiredServiceCli {
+                plugin_id,
+                zenoh_key_expr,
+            } => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_service_srv.entry(format!("/{zenoh_key_expr}"))
+                {
+                    let route = entry.get_mut();
+                    route.remove_remote_route(&plugin_id, &zenoh_key_expr);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_SERVICE_SRV / &zenoh_key_expr));
+                        let route = entry.remove();
+                        // remove writer's and reader's GIDs in ros_discovery_msg
+                        self.ros_discovery_mgr
+                            .remove_dds_writer(route.dds_writer_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        self.ros_discovery_mgr
+                            .remove_dds_reader(route.dds_reader_guid().map_err(|e| {
+                                format!("Failed to update ros_discovery_info message: {e}")
+                            })?);
+                        log::info!("{route} removed");
+                    }
+                }
+            }
+
+            AnnouncedActionSrv {
+                plugin_id,
+                zenoh_key_expr,
+                ros2_type,
+            } => {
+                // On remote Action Server route announcement, prepare an Action Client route
+                // with associated DDS Readers/Writers allowing local ROS2 Nodes to discover it
+                let route = self
+                    .get_or_create_route_action_cli(format!("/{zenoh_key_expr}"), ros2_type)
+                    .await?;
+                route.add_remote_route(&plugin_id, &zenoh_key_expr);
+            }
+
+            RetiredActionSrv {
+                plugin_id,
+                zenoh_key_expr,
+            } => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_action_cli.entry(format!("/{zenoh_key_expr}"))
+                {
+                    let route = entry.get_mut();
+                    route.remove_remote_route(&plugin_id, &zenoh_key_expr);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_ACTION_CLI / &zenoh_key_expr));
+                        let route = entry.remove();
+                        // remove readers' and writers' GIDs in ros_discovery_msg
+                        for gid in route.dds_readers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_reader(gid);
+                        }
+                        for gid in route.dds_writers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_writer(gid);
+                        }
+                        log::info!("{route} removed");
+                    }
+                }
+            }
+
+            AnnouncedActionCli {
+                plugin_id,
+                zenoh_key_expr,
+                ros2_type,
+            } => {
+                // On remote Action Client route announcement, prepare an Action Server route
+                // with associated DDS Writers/Readers allowing local ROS2 Nodes to discover it
+                let route = self
+                    .get_or_create_route_action_srv(format!("/{zenoh_key_expr}"), ros2_type)
+                    .await?;
+                route.add_remote_route(&plugin_id, &zenoh_key_expr);
+            }
+
+            RetiredActionCli {
+                plugin_id,
+                zenoh_key_expr,
+            } => {
+                if let Entry::Occupied(mut entry) =
+                    self.routes_action_srv.entry(format!("/{zenoh_key_expr}"))
+                {
+                    let route = entry.get_mut();
+                    route.remove_remote_route(&plugin_id, &zenoh_key_expr);
+                    if route.is_unused() {
+                        self.admin_space
+                            .remove(&(*KE_PREFIX_ROUTE_ACTION_SRV / &zenoh_key_expr));
+                        let route = entry.remove();
+                        // remove writers' and readers' GIDs in ros_discovery_msg
+                        for gid in route.dds_writers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_writer(gid);
+                        }
+                        for gid in route.dds_readers_guids().map_err(|e| {
+                            format!("Failed to update ros_discovery_info message: {e}")
+                        })? {
+                            self.ros_discovery_mgr.remove_dds_reader(gid);
+                        }
+                        log::info!("{route} removed");
+                    }
+                }
+            }
         }
         Ok(())
     }
 
-    pub async fn query_historical_all_publications(&mut self, plugin_id: &keyexpr) {
+    // Get the zenoh id of a new remote bridge, querying its admin space.
+    // It's required to find the goals accepted by this bridge's Action Servers when it leaves
+    // (the Action Client routes only know the zenoh id of the bridge that replied to a send_goal request).
+    // The query is made in a separate task, not to block the processing of other events until its timeout.
+    pub fn on_remote_bridge_joined(&mut self, plugin_id: &keyexpr) {
+        let admin_version_ke =
+            zenoh::keformat!(ke_admin_prefix::formatter(), plugin_id = plugin_id).unwrap()
+                / ke_for_sure!("version");
+        let zsession = self.zsession.clone();
+        let queries_timeout = self.config.queries_timeout;
+        let remote_bridges_zid = self.remote_bridges_zid.clone();
+        let plugin_id = plugin_id.to_owned();
+        async_std::task::spawn(async move {
+            let replies = match zsession
+                .get(admin_version_ke.as_str())
+                .timeout(queries_timeout)
+                .res_async()
+                .await
+            {
+                Ok(replies) => replies,
+                Err(e) => {
+                    log::warn!(
+                        "Failed to query admin space of remote bridge {plugin_id}: {e} - goals accepted by its Action Servers won't be aborted if it leaves"
+                    );
+                    return;
+                }
+            };
+            match replies.recv_async().await {
+                Ok(reply) => {
+                    log::debug!("Remote bridge {plugin_id} has zenoh id {}", reply.replier_id);
+                    zwrite!(remote_bridges_zid).insert(plugin_id, reply.replier_id);
+                }
+                Err(_) => log::warn!(
+                    "No reply from admin space of remote bridge {plugin_id} - goals accepted by its Action Servers won't be aborted if it leaves"
+                ),
+            }
+        });
+    }
+
+    // Deactivate the Publisher routes for which the retention of the cache expired (see "latch_retention"),
+    // and remove those that are then unused
+    pub fn check_retained_routes(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .routes_publishers
+            .iter_mut()
+            .filter_map(|(name, route)| {
+                (route.check_retention_expiry(now) && route.is_unused()).then(|| name.clone())
+            })
+            .collect();
+        for route_name in expired {
+            if let Some(route) = self.routes_publishers.remove(&route_name) {
+                self.admin_space
+                    .remove(&(*KE_PREFIX_ROUTE_PUBLISHER / route_name_as_keyexpr(&route_name)));
+                // remove reader's GID in ros_discovery_msg
+                match route.dds_reader_guid() {
+                    Ok(gid) => self.ros_discovery_mgr.remove_dds_reader(gid),
+                    Err(e) => log::warn!("Failed to update ros_discovery_info message: {e}"),
+                }
+                log::info!("{route} removed");
+            }
+        }
+    }
+
+    // Update the routes with a new config, where only the options that can be changed at runtime might differ
+    // (see config_checker). The GIDs of the DDS Readers re-created by the routes are replaced in ros_discovery_info.
+    pub async fn update_config(&mut self, config: Arc<Config>) {
+        self.config = config.clone();
+        for route in self.routes_publishers.values_mut() {
+            let old_guids: Vec<Gid> = route.dds_reader_guid().into_iter().collect();
+            if let Err(e) = route.update_config(self.participant, config.clone()).await {
+                log::warn!("{route}: failed to apply the new config: {e}");
+            }
+            let new_guids: Vec<Gid> = route.dds_reader_guid().into_iter().collect();
+            replace_dds_readers_guids(&self.ros_discovery_mgr, &old_guids, &new_guids);
+        }
+        for route in self.routes_action_srv.values_mut() {
+            let old_guids = route.dds_readers_guids().unwrap_or_default();
+            if let Err(e) = route.update_config(self.participant, config.clone()).await {
+                log::warn!("{route}: failed to apply the new config: {e}");
+            }
+            let new_guids = route.dds_readers_guids().unwrap_or_default();
+            replace_dds_readers_guids(&self.ros_discovery_mgr, &old_guids, &new_guids);
+        }
         for route in self.routes_subscribers.values_mut() {
-            route
-                .query_historical_publications(&plugin_id, self.config.queries_timeout)
-                .await;
+            route.update_config(config.clone());
+        }
+        for (ros2_name, route) in self.routes_service_srv.iter_mut() {
+            let query_timeout = get_service_timeout(&config, route_name_as_keyexpr(ros2_name));
+            route.update_config(config.clone(), query_timeout);
+        }
+        for (ros2_name, route) in self.routes_service_cli.iter_mut() {
+            let query_timeout = get_service_timeout(&config, route_name_as_keyexpr(ros2_name));
+            route.update_config(config.clone(), query_timeout);
+        }
+        for route in self.routes_action_cli.values_mut() {
+            route.update_config(config.clone());
         }
     }
 
+    pub fn on_remote_bridge_left(&mut self, plugin_id: &keyexpr) {
+        let zid = zwrite!(self.remote_bridges_zid).remove(plugin_id);
+        if zid.is_none() {
+            log::warn!(
+                "Unknown zenoh id for remote bridge {plugin_id} - goals accepted by its Action Servers might not be aborted"
+            );
+        }
+        for route in self.routes_action_cli.values_mut() {
+            route.abort_goals_of_bridge(plugin_id, &zid);
+        }
+    }
+
+    pub fn query_historical_all_publications(&mut self, plugin_id: &keyexpr) {
+        for route in self.routes_subscribers.values_mut() {
+            route.query_historical_publications(plugin_id, self.config.queries_timeout);
+        }
+        for route in self.routes_action_cli.values_mut() {
+            route.query_historical_status(plugin_id, self.config.queries_timeout);
+        }
+    }
+
+    // Check if the QoS of a local Writer conflicts with the QoS of its Publisher route's DDS Reader,
+    // and apply the "qos_conflict_policy" if so
+    async fn check_route_publisher_qos(
+        &mut self,
+        route_name: &str,
+        node: String,
+        writer_qos: &Qos,
+    ) -> Result<(), String> {
+        let route = match self.routes_publishers.get_mut(route_name) {
+            Some(route) => route,
+            None => return Ok(()),
+        };
+        let reason = match get_qos_mismatch(route.dds_reader_qos(), writer_qos) {
+            Some(reason) => reason,
+            None => return Ok(()),
+        };
+        // the "qos_overrides" rules still apply to the downgraded QoS,
+        // and might prevent to solve the conflict
+        let reader_qos = apply_qos_overrides(
+            &self.config,
+            route.ros2_name(),
+            &downgrade_reader_qos_for_writer(route.dds_reader_qos(), writer_qos),
+        );
+        let upgradable = get_qos_mismatch(&reader_qos, writer_qos).is_none();
+        match self.config.qos_conflict_policy {
+            QosConflictPolicy::Upgrade if upgradable => {
+                log::info!("{route}: QoS conflict with Writer of {node}: {reason} - re-create the DDS Reader");
+                let old_guid = route
+                    .dds_reader_guid()
+                    .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?;
+                route
+                    .recreate_dds_reader(self.participant, reader_qos)
+                    .await?;
+                // replace reader's GID in ros_discovery_msg
+                self.ros_discovery_mgr.remove_dds_reader(old_guid);
+                self.ros_discovery_mgr.add_dds_reader(
+                    route
+                        .dds_reader_guid()
+                        .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+                );
+            }
+            _ => {
+                log::warn!("{route}: QoS conflict with Writer of {node}: {reason} - its publications won't be routed");
+                route.set_qos_conflict(node, reason);
+            }
+        }
+        Ok(())
+    }
+
+    // Check if the QoS of a local Reader conflicts with the QoS of its Subscriber route's DDS Writer,
+    // and apply the "qos_conflict_policy" if so
+    async fn check_route_subscriber_qos(
+        &mut self,
+        route_name: &str,
+        node: String,
+        reader_qos: &Qos,
+    ) -> Result<(), String> {
+        let route = match self.routes_subscribers.get_mut(route_name) {
+            Some(route) => route,
+            None => return Ok(()),
+        };
+        let reason = match get_qos_mismatch(reader_qos, route.dds_writer_qos()) {
+            Some(reason) => reason,
+            None => {
+                // no conflict, but the Reader might still require a more demanding QoS (e.g. a deeper History)
+                return self
+                    .update_route_subscriber_qos(
+                        route_name,
+                        &adapt_reader_qos_for_writer(reader_qos),
+                    )
+                    .await;
+            }
+        };
+        // the "qos_overrides" rules still apply to the upgraded QoS,
+        // and might prevent to solve the conflict
+        let writer_qos = apply_qos_overrides(
+            &self.config,
+            route.ros2_name(),
+            &upgrade_writer_qos_for_reader(route.dds_writer_qos(), reader_qos),
+        );
+        let upgradable = get_qos_mismatch(reader_qos, &writer_qos).is_none();
+        match self.config.qos_conflict_policy {
+            QosConflictPolicy::Upgrade if upgradable => {
+                log::info!("{route}: QoS conflict with Reader of {node}: {reason} - re-create the DDS Writer");
+                self.recreate_route_subscriber_dds_writer(route_name, writer_qos)
+                    .await?;
+            }
+            _ => {
+                log::warn!("{route}: QoS conflict with Reader of {node}: {reason} - it won't receive routed publications");
+                route.set_qos_conflict(node, reason);
+            }
+        }
+        Ok(())
+    }
+
+    // Re-create the DDS Writer of a Subscriber route if the specified QoS (of a remote Publisher or a local Reader)
+    // is more demanding than its current QoS, so the route always uses the most demanding QoS of all its
+    // remote routes and local nodes (e.g. TRANSIENT_LOCAL if any remote Publisher is TRANSIENT_LOCAL)
+    async fn update_route_subscriber_qos(
+        &mut self,
+        route_name: &str,
+        qos: &Qos,
+    ) -> Result<(), String> {
+        let route = match self.routes_subscribers.get(route_name) {
+            Some(route) => route,
+            None => return Ok(()),
+        };
+        let writer_qos = match get_most_demanding_writer_qos(route.dds_writer_qos(), qos) {
+            Some(writer_qos) => apply_qos_overrides(&self.config, route.ros2_name(), &writer_qos),
+            None => return Ok(()),
+        };
+        // the "qos_overrides" rules might have cancelled the upgrade
+        if get_most_demanding_writer_qos(route.dds_writer_qos(), &writer_qos).is_none() {
+            return Ok(());
+        }
+        log::info!("{route}: re-create the DDS Writer with a more demanding QoS");
+        self.recreate_route_subscriber_dds_writer(route_name, writer_qos)
+            .await
+    }
+
+    // Re-create the DDS Writer of a Subscriber route with a new QoS, and replace its GID in ros_discovery_msg
+    async fn recreate_route_subscriber_dds_writer(
+        &mut self,
+        route_name: &str,
+        writer_qos: Qos,
+    ) -> Result<(), String> {
+        let route = match self.routes_subscribers.get_mut(route_name) {
+            Some(route) => route,
+            None => return Ok(()),
+        };
+        let old_guid = route
+            .dds_writer_guid()
+            .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?;
+        route
+            .recreate_dds_writer(self.participant, writer_qos)
+            .await?;
+        self.ros_discovery_mgr.remove_dds_writer(old_guid);
+        self.ros_discovery_mgr.add_dds_writer(
+            route
+                .dds_writer_guid()
+                .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+        );
+        Ok(())
+    }
+
     async fn get_or_create_route_publisher(
         &mut self,
+        route_name: String,
         ros2_name: String,
         ros2_type: String,
         keyless: bool,
         reader_qos: Qos,
     ) -> Result<&mut RoutePublisher<'a>, String> {
-        match self.routes_publishers.entry(ros2_name.clone()) {
+        match self.routes_publishers.entry(route_name.clone()) {
             Entry::Vacant(entry) => {
-                // ROS2 topic name => Zenoh key expr : strip '/' prefix
-                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // route name => Zenoh key expr : strip '/' prefix (if not prefixed with a partition)
+                let zenoh_key_expr = route_name_as_keyexpr(&route_name);
+                // apply the first matching "qos_overrides" rule (if any)
+                let reader_qos = apply_qos_overrides(&self.config, &ros2_name, &reader_qos);
+                // get the on-disk cache if TRANSIENT_LOCAL and matching the "persistent_caches" option
+                let persistent_cache = if is_transient_local(&reader_qos) {
+                    self.persistent_caches
+                        .get_or_create(zenoh_key_expr, keyless, &reader_qos)
+                        .await
+                        .unwrap_or_else(|e| {
+                            log::warn!("Route Publisher (ROS:{ros2_name} -> Zenoh:{zenoh_key_expr}): on-disk cache not available: {e}");
+                            None
+                        })
+                } else {
+                    None
+                };
                 // create route
-                let route = RoutePublisher::create(
+                let mut route = RoutePublisher::create(
                     self.config.clone(),
                     &self.zsession,
                     self.participant,
@@ -352,14 +1036,23 @@ impl<'a> RoutesMgr<'a> {
                     &None,
                     keyless,
                     reader_qos,
+                    persistent_cache,
+                    self.cache_budget.clone(),
                 )
                 .await?;
                 log::info!("{route} created");
 
+                // initialize the ownership strengths of the already announced remote Publishers
+                if let Some(strengths) = self.remote_ownership_strengths.get(&route_name) {
+                    for (plugin_id, strength) in strengths {
+                        route.add_remote_ownership_strength(plugin_id, *strength);
+                    }
+                }
+
                 // insert reference in admin_space
                 let admin_ke = *KE_PREFIX_ROUTE_PUBLISHER / zenoh_key_expr;
                 self.admin_space
-                    .insert(admin_ke, RouteRef::PublisherRoute(ros2_name));
+                    .insert(admin_ke, RouteRef::PublisherRoute(route_name));
 
                 // insert reader's GID in ros_discovery_msg
                 self.ros_discovery_mgr.add_dds_reader(
@@ -376,15 +1069,18 @@ impl<'a> RoutesMgr<'a> {
 
     async fn get_or_create_route_subscriber(
         &mut self,
+        route_name: String,
         ros2_name: String,
         ros2_type: String,
         keyless: bool,
         writer_qos: Qos,
     ) -> Result<&mut RouteSubscriber<'a>, String> {
-        match self.routes_subscribers.entry(ros2_name.clone()) {
+        match self.routes_subscribers.entry(route_name.clone()) {
             Entry::Vacant(entry) => {
-                // ROS2 topic name => Zenoh key expr : strip '/' prefix
-                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // route name => Zenoh key expr : strip '/' prefix (if not prefixed with a partition)
+                let zenoh_key_expr = route_name_as_keyexpr(&route_name);
+                // apply the first matching "qos_overrides" rule (if any)
+                let writer_qos = apply_qos_overrides(&self.config, &ros2_name, &writer_qos);
                 // create route
                 let route = RouteSubscriber::create(
                     self.config.clone(),
@@ -402,7 +1098,7 @@ impl<'a> RoutesMgr<'a> {
                 // insert reference in admin_space
                 let admin_ke = *KE_PREFIX_ROUTE_SUBSCRIBER / zenoh_key_expr;
                 self.admin_space
-                    .insert(admin_ke, RouteRef::SubscriberRoute(ros2_name));
+                    .insert(admin_ke, RouteRef::SubscriberRoute(route_name));
 
                 // insert writer's GID in ros_discovery_msg
                 self.ros_discovery_mgr.add_dds_writer(
@@ -417,6 +1113,194 @@ impl<'a> RoutesMgr<'a> {
         }
     }
 
+    async fn get_or_create_route_service_srv(
+        &mut self,
+        ros2_name: String,
+        ros2_type: String,
+    ) -> Result<&mut RouteServiceSrv<'a>, String> {
+        match self.routes_service_srv.entry(ros2_name.clone()) {
+            Entry::Vacant(entry) => {
+                // ROS2 service name => Zenoh key expr : strip '/' prefix
+                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // create route
+                let route = RouteServiceSrv::create(
+                    self.config.clone(),
+                    &self.zsession,
+                    self.participant,
+                    ros2_name.clone(),
+                    ros2_type,
+                    zenoh_key_expr.to_owned(),
+                    &None,
+                    get_service_timeout(&self.config, zenoh_key_expr),
+                )
+                .await?;
+                log::info!("{route} created");
+
+                // insert reference in admin_space
+                let admin_ke = *KE_PREFIX_ROUTE_SERVICE_SRV / zenoh_key_expr;
+                self.admin_space
+                    .insert(admin_ke, RouteRef::ServiceSrvRoute(ros2_name));
+
+                // insert writer's and reader's GIDs in ros_discovery_msg
+                self.ros_discovery_mgr.add_dds_writer(
+                    route
+                        .dds_writer_guid()
+                        .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+                );
+                self.ros_discovery_mgr.add_dds_reader(
+                    route
+                        .dds_reader_guid()
+                        .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+                );
+
+                Ok(entry.insert(route))
+            }
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+        }
+    }
+
+    async fn get_or_create_route_service_cli(
+        &mut self,
+        ros2_name: String,
+        ros2_type: String,
+    ) -> Result<&mut RouteServiceCli<'a>, String> {
+        match self.routes_service_cli.entry(ros2_name.clone()) {
+            Entry::Vacant(entry) => {
+                // ROS2 service name => Zenoh key expr : strip '/' prefix
+                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // create route
+                let route = RouteServiceCli::create(
+                    self.config.clone(),
+                    &self.zsession,
+                    self.participant,
+                    ros2_name.clone(),
+                    ros2_type,
+                    zenoh_key_expr.to_owned(),
+                    &None,
+                    get_service_timeout(&self.config, zenoh_key_expr),
+                    get_selection_policy(&self.config, zenoh_key_expr),
+                    GoalTracking::None,
+                )
+                .await?;
+                log::info!("{route} created");
+
+                // insert reference in admin_space
+                let admin_ke = *KE_PREFIX_ROUTE_SERVICE_CLI / zenoh_key_expr;
+                self.admin_space
+                    .insert(admin_ke, RouteRef::ServiceCliRoute(ros2_name));
+
+                // insert reader's and writer's GIDs in ros_discovery_msg
+                self.ros_discovery_mgr.add_dds_reader(
+                    route
+                        .dds_reader_guid()
+                        .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+                );
+                self.ros_discovery_mgr.add_dds_writer(
+                    route
+                        .dds_writer_guid()
+                        .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?,
+                );
+
+                Ok(entry.insert(route))
+            }
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+        }
+    }
+
+    async fn get_or_create_route_action_srv(
+        &mut self,
+        ros2_name: String,
+        ros2_type: String,
+    ) -> Result<&mut RouteActionSrv<'a>, String> {
+        match self.routes_action_srv.entry(ros2_name.clone()) {
+            Entry::Vacant(entry) => {
+                // ROS2 action name => Zenoh key expr : strip '/' prefix
+                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // create route
+                let route = RouteActionSrv::create(
+                    self.config.clone(),
+                    &self.zsession,
+                    self.participant,
+                    ros2_name.clone(),
+                    ros2_type,
+                    zenoh_key_expr.to_owned(),
+                    self.cache_budget.clone(),
+                )
+                .await?;
+                log::info!("{route} created");
+
+                // insert reference in admin_space
+                let admin_ke = *KE_PREFIX_ROUTE_ACTION_SRV / zenoh_key_expr;
+                self.admin_space
+                    .insert(admin_ke, RouteRef::ActionSrvRoute(ros2_name));
+
+                // insert writers' and readers' GIDs in ros_discovery_msg
+                for gid in route
+                    .dds_writers_guids()
+                    .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?
+                {
+                    self.ros_discovery_mgr.add_dds_writer(gid);
+                }
+                for gid in route
+                    .dds_readers_guids()
+                    .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?
+                {
+                    self.ros_discovery_mgr.add_dds_reader(gid);
+                }
+
+                Ok(entry.insert(route))
+            }
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+        }
+    }
+
+    async fn get_or_create_route_action_cli(
+        &mut self,
+        ros2_name: String,
+        ros2_type: String,
+    ) -> Result<&mut RouteActionCli<'a>, String> {
+        match self.routes_action_cli.entry(ros2_name.clone()) {
+            Entry::Vacant(entry) => {
+                // ROS2 action name => Zenoh key expr : strip '/' prefix
+                let zenoh_key_expr = ke_for_sure!(&ros2_name[1..]);
+                // create route
+                let route = RouteActionCli::create(
+                    self.config.clone(),
+                    &self.zsession,
+                    self.participant,
+                    ros2_name.clone(),
+                    ros2_type,
+                    zenoh_key_expr.to_owned(),
+                    self.remote_bridges_zid.clone(),
+                )
+                .await?;
+                log::info!("{route} created");
+
+                // insert reference in admin_space
+                let admin_ke = *KE_PREFIX_ROUTE_ACTION_CLI / zenoh_key_expr;
+                self.admin_space
+                    .insert(admin_ke, RouteRef::ActionCliRoute(ros2_name));
+
+                // insert readers' and writers' GIDs in ros_discovery_msg
+                for gid in route
+                    .dds_readers_guids()
+                    .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?
+                {
+                    self.ros_discovery_mgr.add_dds_reader(gid);
+                }
+                for gid in route
+                    .dds_writers_guids()
+                    .map_err(|e| format!("Failed to update ros_discovery_info message: {e}"))?
+                {
+                    self.ros_discovery_mgr.add_dds_writer(gid);
+                }
+
+                Ok(entry.insert(route))
+            }
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+        }
+    }
+
     pub async fn treat_admin_query(&self, query: &Query) {
         let selector = query.selector();
 
@@ -480,6 +1364,165 @@ impl<'a> RoutesMgr<'a> {
                 .get(ke)
                 .map(serde_json::to_value)
                 .transpose(),
+            RouteRef::ServiceSrvRoute(ke) => self
+                .routes_service_srv
+                .get(ke)
+                .map(serde_json::to_value)
+                .transpose(),
+            RouteRef::ServiceCliRoute(ke) => self
+                .routes_service_cli
+                .get(ke)
+                .map(serde_json::to_value)
+                .transpose(),
+            RouteRef::ActionSrvRoute(ke) => self
+                .routes_action_srv
+                .get(ke)
+                .map(serde_json::to_value)
+                .transpose(),
+            RouteRef::ActionCliRoute(ke) => self
+                .routes_action_cli
+                .get(ke)
+                .map(serde_json::to_value)
+                .transpose(),
+        }
+    }
+}
+
+// Route name => Zenoh key expr : strip '/' prefix (a route name prefixed with a partition has none)
+fn route_name_as_keyexpr(route_name: &str) -> &keyexpr {
+    ke_for_sure!(route_name.strip_prefix('/').unwrap_or(route_name))
+}
+
+// Replace in ros_discovery_info the GIDs of the DDS Readers re-created by a route
+fn replace_dds_readers_guids(
+    ros_discovery_mgr: &RosDiscoveryInfoMgr,
+    old_guids: &[Gid],
+    new_guids: &[Gid],
+) {
+    for gid in old_guids.iter().filter(|gid| !new_guids.contains(gid)) {
+        ros_discovery_mgr.remove_dds_reader(*gid);
+    }
+    for gid in new_guids.iter().filter(|gid| !old_guids.contains(gid)) {
+        ros_discovery_mgr.add_dds_reader(*gid);
+    }
+}
+
+// Return the name of the route for a local Writer or Reader, prefixed with its DDS partition
+// if "partitions_as_key_prefix" is enabled (e.g. "%robot_1/chatter" instead of "/chatter"),
+// and its QoS to be announced to the remote bridges (with only this partition, if any)
+fn get_local_route_name(config: &Config, ros2_name: &str, qos: &Qos) -> (String, Qos) {
+    let mut announced_qos = qos.clone();
+    match get_partition_prefix(config, qos) {
+        Some(partition) => {
+            let route_name = format!("{PARTITION_MARKER}{partition}{ros2_name}");
+            announced_qos.partition = Some(vec![partition.to_string()]);
+            (route_name, announced_qos)
+        }
+        None => {
+            announced_qos.partition = None;
+            (ros2_name.to_string(), announced_qos)
+        }
+    }
+}
+
+// Return the route name and the ROS2 name for a remote route announcement,
+// keeping in the announced QoS only the partition used as key expression prefix (if any)
+fn get_remote_route_name(
+    config: &Config,
+    zenoh_key_expr: &keyexpr,
+    qos: &mut Qos,
+) -> (String, String) {
+    if let Some(partition) = get_partition_prefix(config, qos) {
+        if let Some(ros2_name) = zenoh_key_expr
+            .as_str()
+            .strip_prefix(PARTITION_MARKER)
+            .and_then(|s| s.strip_prefix(partition))
+            .filter(|s| s.starts_with('/'))
+        {
+            let ros2_name = ros2_name.to_string();
+            qos.partition = Some(vec![partition.to_string()]);
+            return (zenoh_key_expr.to_string(), ros2_name);
         }
     }
+    qos.partition = None;
+    (format!("/{zenoh_key_expr}"), format!("/{zenoh_key_expr}"))
+}
+
+// Return the name of the route corresponding to a retired remote route's key expression:
+// the key expression itself if it's the name of a partitioned route, or the ROS2 name otherwise
+fn get_retired_route_name(zenoh_key_expr: &keyexpr) -> String {
+    if zenoh_key_expr.starts_with(PARTITION_MARKER) {
+        zenoh_key_expr.to_string()
+    } else {
+        format!("/{zenoh_key_expr}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partitioned_route_names() {
+        let config: Config = serde_json::from_str(r#"{"partitions_as_key_prefix": true}"#).unwrap();
+        let mut qos = Qos::default();
+        qos.partition = Some(vec!["robot_1".into(), "robots".into()]);
+
+        // "/chatter" in "robot_1" partition, and "/robot_1/chatter" without partition
+        let (partitioned_name, announced_qos) = get_local_route_name(&config, "/chatter", &qos);
+        assert_eq!(partitioned_name, "%robot_1/chatter");
+        assert_eq!(announced_qos.partition, Some(vec!["robot_1".to_string()]));
+        let (name, announced_qos) =
+            get_local_route_name(&config, "/robot_1/chatter", &Qos::default());
+        assert_eq!(name, "/robot_1/chatter");
+        assert_eq!(announced_qos.partition, None);
+
+        // their key expressions don't collide
+        let partitioned_ke = route_name_as_keyexpr(&partitioned_name);
+        let ke = route_name_as_keyexpr(&name);
+        assert_eq!(ke.as_str(), "robot_1/chatter");
+        assert!(!partitioned_ke.intersects(ke));
+
+        // the remote bridge finds the same route and ROS2 names
+        let mut remote_qos = announced_qos_with_partition("robot_1");
+        assert_eq!(
+            get_remote_route_name(&config, partitioned_ke, &mut remote_qos),
+            ("%robot_1/chatter".to_string(), "/chatter".to_string())
+        );
+        assert_eq!(remote_qos.partition, Some(vec!["robot_1".to_string()]));
+        let mut remote_qos = Qos::default();
+        assert_eq!(
+            get_remote_route_name(&config, ke, &mut remote_qos),
+            (
+                "/robot_1/chatter".to_string(),
+                "/robot_1/chatter".to_string()
+            )
+        );
+        assert_eq!(get_retired_route_name(partitioned_ke), "%robot_1/chatter");
+        assert_eq!(get_retired_route_name(ke), "/robot_1/chatter");
+
+        // a key expression not prefixed with the partition is not considered as partitioned
+        let mut remote_qos = announced_qos_with_partition("robot_1");
+        assert_eq!(
+            get_remote_route_name(&config, ke, &mut remote_qos),
+            (
+                "/robot_1/chatter".to_string(),
+                "/robot_1/chatter".to_string()
+            )
+        );
+        assert_eq!(remote_qos.partition, None);
+
+        // "partitions_as_key_prefix" disabled
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            get_local_route_name(&config, "/chatter", &qos).0,
+            "/chatter"
+        );
+    }
+
+    fn announced_qos_with_partition(partition: &str) -> Qos {
+        let mut qos = Qos::default();
+        qos.partition = Some(vec![partition.to_string()]);
+        qos
+    }
 }